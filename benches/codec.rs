@@ -0,0 +1,120 @@
+/*
+Purpose: Delta codec encode and decode throughput over a fixed synthetic feed, run with cargo bench --bench codec
+*/
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use orderbook::{DeltaDecoder, DeltaEncoder, Orderbook, Side, WireDelta};
+
+// Time spent per case after one warm-up run; the median run is reported
+const TARGET: Duration = Duration::from_millis(300);
+const FRAMES: usize = 20_000;
+
+/*
+Frames of 1 to 8 sets and removes within 200 ticks of a drifting mid, starting with a Clear and a
+500 level snapshot. A fixed xorshift seed makes every run encode the same bytes
+*/
+fn feed() -> Vec<Vec<WireDelta>> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut mid: i64 = 5_000_000;
+    let mut snapshot = vec![WireDelta::Clear];
+    for offset in 1..=250 {
+        snapshot.push(WireDelta::Set { side: Side::Bid, price: mid - offset, quantity: next() % 100_000 + 1 });
+        snapshot.push(WireDelta::Set { side: Side::Ask, price: mid + offset, quantity: next() % 100_000 + 1 });
+    }
+    let mut frames = vec![snapshot];
+    for _ in 1..FRAMES {
+        mid += (next() % 3) as i64 - 1;
+        let frame = (0..next() % 8 + 1).map(|_| {
+            let offset = (next() % 200) as i64 + 1;
+            let (side, price) = match next() % 2 {
+                0 => (Side::Bid, mid - offset),
+                _ => (Side::Ask, mid + offset)
+            };
+            match next() % 4 {
+                0 => WireDelta::Remove { side, price },
+                _ => WireDelta::Set { side, price, quantity: next() % 100_000 + 1 }
+            }
+        }).collect();
+        frames.push(frame);
+    }
+    frames
+}
+
+/*
+Median wall time of run over as many runs as fit in TARGET, at least five
+*/
+fn measure(name: &str, deltas: usize, bytes: usize, mut run: impl FnMut()) {
+    run();
+    let mut samples = Vec::new();
+    let started = Instant::now();
+    while samples.len() < 5 || started.elapsed() < TARGET {
+        let start = Instant::now();
+        run();
+        samples.push(start.elapsed());
+    }
+    samples.sort();
+    let median = samples[samples.len() / 2].as_secs_f64();
+    println!(
+        "{:<20} {:>10.1} us {:>7.1} ns/delta {:>8.1} MB/s ({} runs)",
+        name,
+        median * 1e6,
+        median * 1e9 / deltas as f64,
+        bytes as f64 / median / 1e6,
+        samples.len()
+    );
+}
+
+fn main() {
+    let frames = feed();
+    let deltas: usize = frames.iter().map(Vec::len).sum();
+    let mut encoded = Vec::new();
+    let mut encoder = DeltaEncoder::new();
+    for frame in frames.iter() {
+        encoder.encode(frame, &mut encoded);
+    }
+    println!("{} frames, {} deltas, {} bytes ({:.2} bytes/delta)", frames.len(), deltas, encoded.len(), encoded.len() as f64 / deltas as f64);
+
+    let mut output = Vec::with_capacity(encoded.len());
+    measure("encode", deltas, encoded.len(), || {
+        output.clear();
+        let mut encoder = DeltaEncoder::new();
+        for frame in frames.iter() {
+            encoder.encode(frame, &mut output);
+        }
+        black_box(&output);
+    });
+    measure("decode", deltas, encoded.len(), || {
+        let mut decoder = DeltaDecoder::new();
+        let mut position = 0;
+        while position < encoded.len() {
+            match decoder.decode(&encoded[position..]) {
+                Ok((decoded, consumed)) => {
+                    black_box(decoded);
+                    position += consumed;
+                },
+                Err(error) => panic!("benchmark feed failed to decode: {error}")
+            }
+        }
+    });
+    measure("decode and apply", deltas, encoded.len(), || {
+        let mut decoder = DeltaDecoder::new();
+        let mut book = Orderbook::new(Some(2), Some(0));
+        let mut position = 0;
+        while let Ok((decoded, consumed)) = decoder.decode(&encoded[position..]) {
+            book.apply_wire_deltas(&decoded);
+            position += consumed;
+            if position == encoded.len() {
+                break;
+            }
+        }
+        black_box(book);
+    });
+}
@@ -0,0 +1,84 @@
+/*
+Purpose: Bulk snapshot loading against level-by-level insertion, run with cargo bench --bench snapshot_load
+*/
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use orderbook::Orderbook;
+
+// Time spent per case after one warm-up run; the median run is reported
+const TARGET: Duration = Duration::from_millis(300);
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+type Levels = Vec<(f64, f64)>;
+
+/*
+Levels around 50_000 with two decimals, shuffled by a fixed xorshift seed so every run sees the same input
+*/
+fn levels(count: usize) -> (Levels, Levels) {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut bids: Levels = (0..count).map(|index| (50_000.0 - (index + 1) as f64 * 0.01, (next() % 10_000 + 1) as f64 / 100.0)).collect();
+    let mut asks: Levels = (0..count).map(|index| (50_000.0 + (index + 1) as f64 * 0.01, (next() % 10_000 + 1) as f64 / 100.0)).collect();
+    for index in (1..count).rev() {
+        bids.swap(index, (next() % (index as u64 + 1)) as usize);
+        asks.swap(index, (next() % (index as u64 + 1)) as usize);
+    }
+    (bids, asks)
+}
+
+/*
+Median wall time of run over as many runs as fit in TARGET, at least five
+*/
+fn measure(name: &str, levels: usize, mut run: impl FnMut()) {
+    run();
+    let mut samples = Vec::new();
+    let started = Instant::now();
+    while samples.len() < 5 || started.elapsed() < TARGET {
+        let start = Instant::now();
+        run();
+        samples.push(start.elapsed());
+    }
+    samples.sort();
+    let median = samples[samples.len() / 2];
+    println!(
+        "{:<28} {:>7} levels/side {:>12.1} us {:>8.1} ns/level ({} runs)",
+        name,
+        levels,
+        median.as_secs_f64() * 1e6,
+        median.as_secs_f64() * 1e9 / (2 * levels) as f64,
+        samples.len()
+    );
+}
+
+fn main() {
+    for size in SIZES {
+        let (bids, asks) = levels(size);
+        measure("from_snapshot", size, || {
+            black_box(Orderbook::from_snapshot(Some(2), Some(2), bids.clone(), asks.clone()));
+        });
+        let mut book = Orderbook::new(Some(2), Some(2));
+        measure("load_snapshot (reused book)", size, || {
+            book.load_snapshot(bids.clone(), asks.clone());
+            black_box(&book);
+        });
+        measure("process snapshot", size, || {
+            let mut book = Orderbook::new(Some(2), Some(2));
+            book.process(bids.clone(), asks.clone(), true);
+            black_box(book);
+        });
+        measure("process per level", size, || {
+            let mut book = Orderbook::new(Some(2), Some(2));
+            for (bid, ask) in bids.iter().zip(asks.iter()) {
+                book.process(vec![*bid], vec![*ask], false);
+            }
+            black_box(book);
+        });
+    }
+}
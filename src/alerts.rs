@@ -0,0 +1,177 @@
+/*
+Purpose: Threshold alert rules over book analytics, evaluated incrementally per update
+*/
+
+use crate::{divergence, DivergenceMetric, Orderbook};
+
+/*
+Analytics a rule can watch. SpreadBps is spread over |mid| in basis points; Imbalance is
+(bid - ask) / (bid + ask) quantity over the best depth levels per side; depths are unscaled quantity
+within bps of mid and read as zero without a two-sided book. Divergence compares against a second
+venue's book and is only evaluated by AlertEngine::evaluate_pair
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertMetric {
+    SpreadBps,
+    Imbalance { depth: usize },
+    BidDepth { bps: f64 },
+    AskDepth { bps: f64 },
+    TotalDepth { bps: f64 },
+    Divergence(DivergenceMetric)
+}
+
+/*
+Outside(limit) holds when the absolute value exceeds limit
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertCondition {
+    Above(f64),
+    Below(f64),
+    Outside(f64)
+}
+
+/*
+Fires once when condition has held on every evaluation for at least hold_ms, and re-arms once it stops holding
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: AlertMetric,
+    pub condition: AlertCondition,
+    pub hold_ms: u64
+}
+
+/*
+A fired rule: value is the metric at firing time, since the timestamp the condition started holding
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: usize,
+    pub name: String,
+    pub value: f64,
+    pub since: u64,
+    pub timestamp: u64
+}
+
+pub type AlertListener = Box<dyn FnMut(&Alert) + Send>;
+
+struct RuleState {
+    rule: AlertRule,
+    since: Option<u64>,
+    fired: bool
+}
+
+/*
+Registered rules evaluated against one book. Call evaluate after each update with the update's
+timestamp (ms, non-decreasing); conditions are only checked at those points
+*/
+#[derive(Default)]
+pub struct AlertEngine {
+    rules: Vec<RuleState>,
+    listener: Option<AlertListener>
+}
+
+impl AlertEngine {
+    pub fn new() -> AlertEngine {
+        AlertEngine::default()
+    }
+
+    /*
+    Returns the rule's index, reported in its alerts
+    */
+    pub fn add_rule(&mut self, rule: AlertRule) -> usize {
+        self.rules.push(RuleState { rule, since: None, fired: false });
+        self.rules.len() - 1
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &AlertRule> {
+        self.rules.iter().map(|state| &state.rule)
+    }
+
+    /*
+    Register a callback invoked for every alert in addition to evaluate's return value
+    */
+    pub fn on_alert(&mut self, listener: AlertListener) {
+        self.listener = Some(listener);
+    }
+
+    /*
+    True while a fired rule's condition continues to hold
+    */
+    pub fn is_active(&self, rule: usize) -> bool {
+        self.rules.get(rule).is_some_and(|state| state.fired)
+    }
+
+    /*
+    Alerts fired by this evaluation
+    */
+    pub fn evaluate<M>(&mut self, book: &Orderbook<M>, timestamp: u64) -> Vec<Alert> {
+        self.evaluate_with(|metric| metric_value(book, metric), timestamp)
+    }
+
+    /*
+    evaluate for a book alongside the same instrument's book on another venue. Divergence rules measure
+    other against book; all other rules read book
+    */
+    pub fn evaluate_pair<M, N>(&mut self, book: &Orderbook<M>, other: &Orderbook<N>, timestamp: u64) -> Vec<Alert> {
+        self.evaluate_with(
+            |metric| match metric {
+                AlertMetric::Divergence(divergence_metric) => divergence(book, other, divergence_metric),
+                metric => metric_value(book, metric)
+            },
+            timestamp
+        )
+    }
+
+    fn evaluate_with(&mut self, value_of: impl Fn(AlertMetric) -> Option<f64>, timestamp: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (index, state) in self.rules.iter_mut().enumerate() {
+            let value = value_of(state.rule.metric);
+            let holds = value.is_some_and(|value| match state.rule.condition {
+                AlertCondition::Above(limit) => value > limit,
+                AlertCondition::Below(limit) => value < limit,
+                AlertCondition::Outside(limit) => value.abs() > limit
+            });
+            if !holds {
+                state.since = None;
+                state.fired = false;
+                continue;
+            }
+            let since = *state.since.get_or_insert(timestamp);
+            if !state.fired && timestamp.saturating_sub(since) >= state.rule.hold_ms {
+                state.fired = true;
+                alerts.push(Alert {
+                    rule: index,
+                    name: state.rule.name.clone(),
+                    value: value.unwrap_or_default(),
+                    since,
+                    timestamp
+                });
+            }
+        }
+        if let Some(listener) = self.listener.as_mut() {
+            for alert in alerts.iter() {
+                listener(alert);
+            }
+        }
+        alerts
+    }
+}
+
+fn metric_value<M>(book: &Orderbook<M>, metric: AlertMetric) -> Option<f64> {
+    match metric {
+        AlertMetric::SpreadBps => {
+            let summary = book.summary(Some(1));
+            let mid_price = summary.mid_price?.abs();
+            match mid_price > 0.0 {
+                true => Some((summary.spread? as f64) / mid_price * 10_000.0),
+                false => None
+            }
+        },
+        AlertMetric::Imbalance { depth } => book.summary(Some(depth)).imbalance,
+        AlertMetric::BidDepth { bps } => Some(book.depth_grid(bps, 1).map_or(0.0, |grid| grid.bids[0])),
+        AlertMetric::AskDepth { bps } => Some(book.depth_grid(bps, 1).map_or(0.0, |grid| grid.asks[0])),
+        AlertMetric::TotalDepth { bps } => Some(book.depth_grid(bps, 1).map_or(0.0, |grid| grid.bids[0] + grid.asks[0])),
+        AlertMetric::Divergence(_) => None
+    }
+}
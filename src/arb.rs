@@ -0,0 +1,126 @@
+/*
+Purpose: Triangular arbitrage scanning through the depth of several books
+*/
+
+use crate::{Orderbook, Side};
+
+/*
+One conversion in a cycle. Side::Bid buys the book's base with its quote currency by lifting asks,
+Side::Ask sells base for quote by hitting bids. fee is the taker fee as a fraction of the leg's output
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArbLeg {
+    pub book: usize,
+    pub side: Side,
+    pub fee: f64
+}
+
+/*
+size is the amount of the starting currency put through the cycle and output the amount returned after fees.
+edge is output / size - 1
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArbOpportunity {
+    pub size: f64,
+    pub output: f64,
+    pub profit: f64,
+    pub edge: f64
+}
+
+/*
+A closed chain of legs, e.g. USDT -> BTC -> ETH -> USDT as
+[buy BTC/USDT, buy ETH/BTC, sell ETH/USDT]. Leg books index into the slice passed at scan time
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbCycle {
+    pub legs: Vec<ArbLeg>
+}
+
+impl ArbCycle {
+    pub fn new(legs: Vec<ArbLeg>) -> ArbCycle {
+        ArbCycle { legs }
+    }
+
+    /*
+    Amount of the starting currency returned after walking amount through every leg's depth.
+    None if a leg lacks depth or a book with require_live set does not accept taker orders
+    */
+    pub fn output<M>(&self, books: &[&Orderbook<M>], amount: f64) -> Option<f64> {
+        let mut amount = amount;
+        for leg in self.legs.iter() {
+            let book = books.get(leg.book)?;
+            if book.require_live && !book.accepts_taker_orders() {
+                return None;
+            }
+            let received = match leg.side {
+                Side::Bid => spend_quote(book, amount)?,
+                Side::Ask => sell_base(book, amount)?
+            };
+            amount = received * (1.0 - leg.fee);
+        }
+        Some(amount)
+    }
+
+    /*
+    Evaluate the cycle at each candidate size and return the most profitable, if any size is profitable
+    */
+    pub fn scan<M>(&self, books: &[&Orderbook<M>], sizes: &[f64]) -> Option<ArbOpportunity> {
+        let mut best: Option<ArbOpportunity> = None;
+        for size in sizes.iter().filter(|size| **size > 0.0) {
+            let output = match self.output(books, *size) {
+                Some(output) => output,
+                None => continue
+            };
+            let profit = output - size;
+            if profit > 0.0 && best.is_none_or(|best| profit > best.profit) {
+                best = Some(ArbOpportunity {
+                    size: *size,
+                    output,
+                    profit,
+                    edge: output / size - 1.0
+                });
+            }
+        }
+        best
+    }
+}
+
+/*
+Base received for spending quote_amount (unscaled) on the asks
+*/
+fn spend_quote<M>(book: &Orderbook<M>, quote_amount: f64) -> Option<f64> {
+    let mut remaining = quote_amount;
+    let mut received = 0.0;
+    for (price, quantity) in book.asks.iter() {
+        let price = book.unscale_price(*price);
+        let quantity = book.unscale_qty(*quantity);
+        if price <= 0.0 {
+            return None;
+        }
+        let cost = price * quantity;
+        if cost >= remaining {
+            return Some(received + remaining / price);
+        }
+        received += quantity;
+        remaining -= cost;
+    }
+    None
+}
+
+/*
+Quote received for selling base_amount (unscaled) into the bids
+*/
+fn sell_base<M>(book: &Orderbook<M>, base_amount: f64) -> Option<f64> {
+    let mut remaining = base_amount;
+    let mut received = 0.0;
+    for (price, quantity) in book.bids.iter().rev() {
+        let price = book.unscale_price(*price);
+        let quantity = book.unscale_qty(*quantity);
+        if quantity >= remaining {
+            return Some(received + remaining * price);
+        }
+        received += quantity * price;
+        remaining -= quantity;
+    }
+    None
+}
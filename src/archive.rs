@@ -0,0 +1,250 @@
+/*
+Purpose: Keyframe plus diff archival of snapshot series with reconstruction at any timestamp
+*/
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::flat::{state_code, state_from_code};
+use crate::{BookSnapshot, DecodeError, DeltaDecoder, DeltaEncoder, FlatError, FlatSnapshot, Side, WireDelta};
+
+const ARCHIVE_MAGIC: &[u8; 4] = b"OBA1";
+const RECORD_KEYFRAME: u8 = b'K';
+const RECORD_DIFF: u8 = b'D';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveError {
+    BadMagic,
+    Truncated,
+    InvalidRecord(usize),
+    Flat(FlatError),
+    Decode(DecodeError)
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::BadMagic => write!(f, "buffer does not start with the archive magic"),
+            ArchiveError::Truncated => write!(f, "archive ends mid-record"),
+            ArchiveError::InvalidRecord(index) => write!(f, "invalid archive record at index {}", index),
+            ArchiveError::Flat(error) => write!(f, "invalid keyframe: {}", error),
+            ArchiveError::Decode(error) => write!(f, "invalid diff: {}", error)
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+/*
+Changes turning before into after: a Set for every level added or changed and a Remove for every level
+gone, bids then asks. Both snapshots must use the same decimals
+*/
+pub fn diff_snapshots(before: &BookSnapshot, after: &BookSnapshot) -> Vec<WireDelta> {
+    let mut deltas = Vec::new();
+    for (side, before_levels, after_levels) in [(Side::Bid, before.bids(), after.bids()), (Side::Ask, before.asks(), after.asks())] {
+        let before_levels: BTreeMap<i64, u64> = before_levels.iter().copied().collect();
+        for (price, quantity) in after_levels.iter() {
+            if before_levels.get(price) != Some(quantity) {
+                deltas.push(WireDelta::Set { side, price: *price, quantity: *quantity });
+            }
+        }
+        let after_levels: BTreeMap<i64, u64> = after_levels.iter().copied().collect();
+        for price in before_levels.keys().filter(|price| !after_levels.contains_key(price)) {
+            deltas.push(WireDelta::Remove { side, price: *price });
+        }
+    }
+    deltas
+}
+
+/*
+Writes a snapshot series as magic "OBA1" followed by records. Every keyframe_interval-th snapshot, and
+any snapshot whose decimals differ from the previous one, is a keyframe: 'K', length u32, then the flat
+snapshot. The others are diffs against the previous snapshot: 'D', timestamp u64, state u8, length u32,
+then one DeltaEncoder frame of diff_snapshots. Integers are little-endian. Snapshots should be appended in
+timestamp order
+*/
+pub struct ArchiveWriter<W: Write> {
+    pub keyframe_interval: usize,
+    writer: W,
+    previous: Option<BookSnapshot>,
+    since_keyframe: usize
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut writer: W, keyframe_interval: usize) -> io::Result<ArchiveWriter<W>> {
+        writer.write_all(ARCHIVE_MAGIC)?;
+        Ok(ArchiveWriter {
+            keyframe_interval: keyframe_interval.max(1),
+            writer,
+            previous: None,
+            since_keyframe: 0
+        })
+    }
+
+    pub fn append(&mut self, snapshot: &BookSnapshot) -> io::Result<()> {
+        let previous = match self.previous.take() {
+            Some(previous) if self.since_keyframe < self.keyframe_interval
+                && previous.price_factor() == snapshot.price_factor()
+                && previous.quantity_factor() == snapshot.quantity_factor() => Some(previous),
+            _ => None
+        };
+        let mut record = Vec::new();
+        match previous {
+            Some(previous) => {
+                let mut frame = Vec::new();
+                DeltaEncoder::new().encode(&diff_snapshots(&previous, snapshot), &mut frame);
+                record.push(RECORD_DIFF);
+                record.extend_from_slice(&snapshot.timestamp().to_le_bytes());
+                record.push(state_code(snapshot.state()));
+                record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+                record.extend_from_slice(&frame);
+                self.since_keyframe += 1;
+            },
+            None => {
+                let flat = snapshot.to_flat();
+                record.push(RECORD_KEYFRAME);
+                record.extend_from_slice(&(flat.len() as u32).to_le_bytes());
+                record.extend_from_slice(&flat);
+                self.since_keyframe = 1;
+            }
+        }
+        self.writer.write_all(&record)?;
+        self.previous = Some(snapshot.clone());
+        Ok(())
+    }
+
+    /*
+    Flush and return the underlying writer
+    */
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    timestamp: u64,
+    keyframe: bool,
+    offset: usize,
+    length: usize,
+    state: u8
+}
+
+/*
+Borrowed view over an archive. Construction indexes the records once; reconstruction decodes from the
+nearest keyframe at or before the requested record
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotArchive<'a> {
+    bytes: &'a [u8],
+    entries: Vec<Entry>
+}
+
+impl<'a> SnapshotArchive<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<SnapshotArchive<'a>, ArchiveError> {
+        if bytes.get(..4) != Some(ARCHIVE_MAGIC.as_slice()) {
+            return Err(ArchiveError::BadMagic);
+        }
+        let mut entries = Vec::new();
+        let mut position = 4;
+        while position < bytes.len() {
+            let index = entries.len();
+            let entry = match bytes[position] {
+                RECORD_KEYFRAME => {
+                    let length = read_u32(bytes, position + 1)?;
+                    let flat = bytes.get(position + 5..position + 5 + length).ok_or(ArchiveError::Truncated)?;
+                    let snapshot = FlatSnapshot::new(flat).map_err(ArchiveError::Flat)?;
+                    Entry { timestamp: snapshot.timestamp(), keyframe: true, offset: position + 5, length, state: state_code(snapshot.state()) }
+                },
+                RECORD_DIFF => {
+                    let timestamp = u64::from_le_bytes(read_array(bytes, position + 1)?);
+                    let state = *bytes.get(position + 9).ok_or(ArchiveError::Truncated)?;
+                    if state_from_code(state).is_none() || entries.is_empty() {
+                        return Err(ArchiveError::InvalidRecord(index));
+                    }
+                    let length = read_u32(bytes, position + 10)?;
+                    Entry { timestamp, keyframe: false, offset: position + 14, length, state }
+                },
+                _ => return Err(ArchiveError::InvalidRecord(index))
+            };
+            if bytes.len() < entry.offset + entry.length {
+                return Err(ArchiveError::Truncated);
+            }
+            position = entry.offset + entry.length;
+            entries.push(entry);
+        }
+        Ok(SnapshotArchive { bytes, entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn timestamp(&self, index: usize) -> Option<u64> {
+        self.entries.get(index).map(|entry| entry.timestamp)
+    }
+
+    /*
+    Reconstruct the snapshot at record index
+    */
+    pub fn get(&self, index: usize) -> Result<Option<BookSnapshot>, ArchiveError> {
+        if index >= self.entries.len() {
+            return Ok(None);
+        }
+        let start = self.entries[..=index].iter().rposition(|entry| entry.keyframe).ok_or(ArchiveError::InvalidRecord(index))?;
+        let keyframe = &self.entries[start];
+        let flat = FlatSnapshot::new(&self.bytes[keyframe.offset..keyframe.offset + keyframe.length]).map_err(ArchiveError::Flat)?;
+        let mut bids: BTreeMap<i64, u64> = flat.bids().collect();
+        let mut asks: BTreeMap<i64, u64> = flat.asks().collect();
+        for entry in self.entries[start + 1..=index].iter() {
+            let (deltas, _) = DeltaDecoder::new().decode(&self.bytes[entry.offset..entry.offset + entry.length]).map_err(ArchiveError::Decode)?;
+            for delta in deltas {
+                match delta {
+                    WireDelta::Set { side: Side::Bid, price, quantity } => { bids.insert(price, quantity); },
+                    WireDelta::Set { side: Side::Ask, price, quantity } => { asks.insert(price, quantity); },
+                    WireDelta::Remove { side: Side::Bid, price } => { bids.remove(&price); },
+                    WireDelta::Remove { side: Side::Ask, price } => { asks.remove(&price); },
+                    WireDelta::Clear => {
+                        bids.clear();
+                        asks.clear();
+                    }
+                }
+            }
+        }
+        let entry = &self.entries[index];
+        let state = state_from_code(entry.state).ok_or(ArchiveError::InvalidRecord(index))?;
+        Ok(Some(BookSnapshot::from_parts(
+            bids.into_iter().rev().collect(),
+            asks.into_iter().collect(),
+            entry.timestamp,
+            state,
+            flat.price_factor(),
+            flat.quantity_factor()
+        )))
+    }
+
+    /*
+    The book as of timestamp: the last snapshot taken at or before it. None before the first
+    */
+    pub fn at(&self, timestamp: u64) -> Result<Option<BookSnapshot>, ArchiveError> {
+        match self.entries.partition_point(|entry| entry.timestamp <= timestamp) {
+            0 => Ok(None),
+            after => self.get(after - 1)
+        }
+    }
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], ArchiveError> {
+    bytes.get(offset..offset + N).and_then(|slice| slice.try_into().ok()).ok_or(ArchiveError::Truncated)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<usize, ArchiveError> {
+    Ok(u32::from_le_bytes(read_array(bytes, offset)?) as usize)
+}
@@ -0,0 +1,308 @@
+/*
+Purpose: Hash-chained append-only audit log of accepted book changes and fills, with verification
+*/
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{BookEvent, Fill, Projection, Side};
+
+/*
+Outcome of verifying a log: the number of records and the sequence, timestamp and hash of the last
+one (all zero for an empty log). Keeping last_hash elsewhere, e.g. in a daily report, also makes
+truncation of the log detectable
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    pub records: u64,
+    pub last_sequence: u64,
+    pub last_timestamp: u64,
+    pub last_hash: String
+}
+
+/*
+Malformed: a line that does not parse. Sequence: records missing, repeated or reordered. HashMismatch:
+a record or one before it was altered. line is 1-based
+*/
+#[derive(Debug)]
+pub enum AuditError {
+    Io(io::Error),
+    Malformed { line: u64 },
+    Sequence { line: u64, expected: u64, found: u64 },
+    HashMismatch { line: u64 }
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Io(error) => write!(f, "audit log unreadable: {}", error),
+            AuditError::Malformed { line } => write!(f, "audit log line {} is malformed", line),
+            AuditError::Sequence { line, expected, found } =>
+                write!(f, "audit log line {} has sequence {}, expected {}", line, found, expected),
+            AuditError::HashMismatch { line } => write!(f, "audit log line {} fails its hash chain", line)
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl From<io::Error> for AuditError {
+    fn from(error: io::Error) -> AuditError {
+        AuditError::Io(error)
+    }
+}
+
+/*
+Projection appending every change the book accepts to a log file, one line per record:
+<sequence>,<timestamp>,<record>,<hash> where record is set|remove,bid|ask,<scaled price>,<scaled quantity>,
+clear, reset, rescale,<price multiplier>,<quantity multiplier>, or fill,bid|ask,<price>,<quantity> with
+unscaled fill values. hash is the hex SHA-256 of
+the previous record's hash (32 zero bytes for the first) followed by the line up to its last comma, so
+editing, removing or reordering any record breaks every hash after it. Sequences start at 1.
+Opening an existing log verifies it and continues its chain; the file is only ever appended to.
+Registering the log records the book's current levels after a clear. Records are buffered: call flush
+(or sync for durability) at checkpoints. After a write error the log stops recording and keeps the error
+*/
+pub struct AuditLog {
+    pub path: PathBuf,
+    writer: BufWriter<File>,
+    sequence: u64,
+    last_hash: [u8; 32],
+    error: Option<io::Error>
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<AuditLog, AuditError> {
+        let (sequence, last_hash) = match path.exists() {
+            true => chain_head(path)?,
+            false => (0, [0u8; 32])
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            sequence,
+            last_hash,
+            error: None
+        })
+    }
+
+    /*
+    Record an execution, e.g. from a matching engine or simulator. Use Orderbook::projection_mut to reach
+    the log registered on the book
+    */
+    pub fn record_fill(&mut self, fill: &Fill) {
+        self.append(fill.timestamp, &format!("fill,{},{},{}", side_name(fill.side), fill.price, fill.quantity));
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn last_hash(&self) -> String {
+        hex(&self.last_hash)
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /*
+    Flush and wait until the records are on disk
+    */
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+
+    fn append(&mut self, timestamp: u64, record: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let body = format!("{},{},{}", self.sequence + 1, timestamp, record);
+        let hash = chain_hash(&self.last_hash, &body);
+        match writeln!(self.writer, "{},{}", body, hex(&hash)) {
+            Ok(()) => {
+                self.sequence += 1;
+                self.last_hash = hash;
+            },
+            Err(error) => self.error = Some(error)
+        }
+    }
+}
+
+impl Projection for AuditLog {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        let record = match *event {
+            BookEvent::LevelSet { side, price, quantity, .. } => format!("set,{},{},{}", side_name(side), price, quantity),
+            BookEvent::LevelRemoved { side, price, quantity } => format!("remove,{},{},{}", side_name(side), price, quantity),
+            BookEvent::Cleared => String::from("clear"),
+            BookEvent::Reset => String::from("reset"),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => format!("rescale,{},{}", price_multiplier, quantity_multiplier)
+        };
+        self.append(timestamp, &record);
+    }
+}
+
+/*
+Check the sequence and hash chain of the log at path from the first record
+*/
+pub fn verify_audit_log(path: &Path) -> Result<AuditSummary, AuditError> {
+    let mut summary = AuditSummary { records: 0, last_sequence: 0, last_timestamp: 0, last_hash: hex(&[0u8; 32]) };
+    let mut last_hash = [0u8; 32];
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let number = index as u64 + 1;
+        let (body, hash) = line.rsplit_once(',').ok_or(AuditError::Malformed { line: number })?;
+        let mut fields = body.splitn(3, ',');
+        let sequence = fields.next().and_then(|field| field.parse::<u64>().ok()).ok_or(AuditError::Malformed { line: number })?;
+        let timestamp = fields.next().and_then(|field| field.parse::<u64>().ok()).ok_or(AuditError::Malformed { line: number })?;
+        if sequence != summary.last_sequence + 1 {
+            return Err(AuditError::Sequence { line: number, expected: summary.last_sequence + 1, found: sequence });
+        }
+        let expected = chain_hash(&last_hash, body);
+        if hex(&expected) != hash {
+            return Err(AuditError::HashMismatch { line: number });
+        }
+        last_hash = expected;
+        summary = AuditSummary { records: number, last_sequence: sequence, last_timestamp: timestamp, last_hash: hex(&expected) };
+    }
+    Ok(summary)
+}
+
+/*
+Sequence and hash to continue an existing log from
+*/
+fn chain_head(path: &Path) -> Result<(u64, [u8; 32]), AuditError> {
+    let summary = verify_audit_log(path)?;
+    let mut hash = [0u8; 32];
+    for (byte, pair) in hash.iter_mut().zip(summary.last_hash.as_bytes().chunks(2)) {
+        *byte = std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()).unwrap_or(0);
+    }
+    Ok((summary.last_sequence, hash))
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Bid => "bid",
+        Side::Ask => "ask"
+    }
+}
+
+fn chain_hash(previous: &[u8; 32], body: &str) -> [u8; 32] {
+    let mut message = Vec::with_capacity(32 + body.len());
+    message.extend_from_slice(previous);
+    message.extend_from_slice(body.as_bytes());
+    sha256(&message)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+];
+
+/*
+FIPS 180-4 SHA-256
+*/
+fn sha256(message: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64).wrapping_mul(8)).to_be_bytes());
+    for block in padded.chunks_exact(64) {
+        let mut schedule = [0u32; 64];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+            let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+            schedule[i] = schedule[i - 16].wrapping_add(s0).wrapping_add(schedule[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(SHA256_K[i]).wrapping_add(schedule[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_known_answers() {
+        // FIPS 180-4 examples plus lengths either side of the padding and block boundaries
+        let two_block = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        let cases: [(&[u8], &str); 6] = [
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (&[b'a'; 55], "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318"),
+            (two_block, "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+            (&[b'a'; 64], "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"),
+            (&[b'a'; 1000], "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3")
+        ];
+        for (message, expected) in cases {
+            assert_eq!(hex(&sha256(message)), expected, "{} byte message", message.len());
+        }
+    }
+
+    #[test]
+    fn edited_line_breaks_the_chain() -> Result<(), AuditError> {
+        let path = std::env::temp_dir().join(format!("orderbook-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut log = AuditLog::open(&path)?;
+        log.apply(&BookEvent::Cleared, 1);
+        log.apply(&BookEvent::LevelSet { side: Side::Bid, price: 9900, quantity: 100, previous: None }, 2);
+        log.record_fill(&Fill { timestamp: 3, side: Side::Ask, price: 99.0, quantity: 0.5 });
+        log.flush()?;
+        assert_eq!(verify_audit_log(&path)?.records, 3);
+
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, contents.replacen(",9900,100,", ",9900,101,", 1))?;
+        let result = verify_audit_log(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(AuditError::HashMismatch { line: 2 })));
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+/*
+Purpose: Reference price and limit-up/limit-down price band tracking
+*/
+
+use crate::Orderbook;
+
+/*
+Scaled reference price and inclusive lower/upper band limits
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBand {
+    pub reference_price: i64,
+    pub lower: i64,
+    pub upper: i64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandCheck {
+    Within,
+    BelowBand,
+    AboveBand
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Apply band limits published by the venue
+    */
+    pub fn set_price_band(&mut self, reference_price: f64, lower: f64, upper: f64) {
+        self.price_band = Some(PriceBand {
+            reference_price: self.scale_price(reference_price),
+            lower: self.scale_price(lower),
+            upper: self.scale_price(upper)
+        });
+    }
+
+    /*
+    Derive band limits as a fraction of the reference price's magnitude either side of it (0.05 = 5%)
+    */
+    pub fn set_reference_price(&mut self, reference_price: f64, band_fraction: f64) {
+        let width = reference_price.abs() * band_fraction;
+        self.set_price_band(reference_price, reference_price - width, reference_price + width);
+    }
+
+    pub fn clear_price_band(&mut self) {
+        self.price_band = None;
+    }
+
+    /*
+    Check an unscaled order price against the band. Always Within when no band is set
+    */
+    pub fn check_price(&self, price: f64) -> BandCheck {
+        let band = match self.price_band {
+            Some(band) => band,
+            None => return BandCheck::Within
+        };
+        let scaled_price = self.scale_price(price);
+        if scaled_price < band.lower {
+            BandCheck::BelowBand
+        } else if scaled_price > band.upper {
+            BandCheck::AboveBand
+        } else {
+            BandCheck::Within
+        }
+    }
+
+    /*
+    LULD limit state: the best bid sits at the upper band or the best ask at the lower band
+    */
+    pub fn is_limit_state(&self) -> bool {
+        let band = match self.price_band {
+            Some(band) => band,
+            None => return false
+        };
+        let limit_up = matches!(self.get_best_bid(), Some((price, _)) if price >= band.upper);
+        let limit_down = matches!(self.get_best_ask(), Some((price, _)) if price <= band.lower);
+        limit_up || limit_down
+    }
+}
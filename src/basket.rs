@@ -0,0 +1,113 @@
+/*
+Purpose: Executable basket (ETF) fair value from constituent books and premium/discount to the basket's own book
+*/
+
+use crate::{CurrencyConverter, Orderbook};
+
+/*
+quantity is units of symbol per basket share (negative for a short leg); currency is the book's quote currency
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasketComponent {
+    pub symbol: String,
+    pub quantity: f64,
+    pub currency: String
+}
+
+/*
+Per-share value in the converter's reference currency of unwinding (bid) or assembling (ask) the
+basket by taking liquidity in every leg. stale is set when a leg's book is not Live or its rate is stale
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasketNav {
+    pub bid: f64,
+    pub ask: f64,
+    pub stale: bool
+}
+
+/*
+etf_bid and etf_ask are per-share executable prices for the same shares in the basket's own book,
+converted likewise. premium is (etf_bid - nav.ask) / nav.ask, the edge of selling the ETF against
+buying the legs; discount is (nav.bid - etf_ask) / nav.bid, the edge of buying the ETF against selling them
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasketValuation {
+    pub nav: BasketNav,
+    pub etf_bid: f64,
+    pub etf_ask: f64,
+    pub premium: f64,
+    pub discount: f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Basket {
+    pub components: Vec<BasketComponent>
+}
+
+impl Basket {
+    pub fn new(components: Vec<BasketComponent>) -> Basket {
+        Basket { components }
+    }
+
+    /*
+    NAV for shares basket shares, looking legs up by symbol in books. None if a leg has no book,
+    no rate, or too little depth for its quantity
+    */
+    pub fn nav<M>(&self, books: &[(&str, &Orderbook<M>)], converter: &CurrencyConverter, shares: f64, now: u64) -> Option<BasketNav> {
+        if !(shares.is_finite() && shares > 0.0) {
+            return None;
+        }
+        let mut bid_total = 0.0;
+        let mut ask_total = 0.0;
+        let mut stale = false;
+        for component in self.components.iter() {
+            let (_, book) = books.iter().find(|(symbol, _)| *symbol == component.symbol)?;
+            let (rate, rate_stale) = converter.rate(&component.currency, now)?;
+            stale |= rate_stale || !book.is_live();
+            let quantity = component.quantity * shares;
+            if quantity == 0.0 {
+                continue;
+            }
+            let size = quantity.abs();
+            let sell = book.simulate_taker_sell(size)? / book.price_factor * size * rate;
+            let buy = book.simulate_taker_buy(size)? / book.price_factor * size * rate;
+            match quantity > 0.0 {
+                true => {
+                    bid_total += sell;
+                    ask_total += buy;
+                },
+                false => {
+                    bid_total -= buy;
+                    ask_total -= sell;
+                }
+            }
+        }
+        Some(BasketNav {
+            bid: bid_total / shares,
+            ask: ask_total / shares,
+            stale
+        })
+    }
+
+    /*
+    NAV together with the executable price of shares in the basket's own book quoted in currency.
+    None if either side cannot be filled or a NAV side is zero
+    */
+    pub fn valuation<M, N>(&self, books: &[(&str, &Orderbook<M>)], etf: &Orderbook<N>, currency: &str, converter: &CurrencyConverter, shares: f64, now: u64) -> Option<BasketValuation> {
+        let mut nav = self.nav(books, converter, shares, now)?;
+        let (rate, rate_stale) = converter.rate(currency, now)?;
+        nav.stale |= rate_stale || !etf.is_live();
+        let etf_bid = etf.simulate_taker_sell(shares)? / etf.price_factor * rate;
+        let etf_ask = etf.simulate_taker_buy(shares)? / etf.price_factor * rate;
+        if nav.ask == 0.0 || nav.bid == 0.0 {
+            return None;
+        }
+        Some(BasketValuation {
+            nav,
+            etf_bid,
+            etf_ask,
+            premium: (etf_bid - nav.ask) / nav.ask,
+            discount: (nav.bid - etf_ask) / nav.bid
+        })
+    }
+}
@@ -0,0 +1,269 @@
+/*
+Purpose: Per-instrument trading sessions, breaks and holidays to tell a closed market from a dead feed
+*/
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::{days_from_civil, LocalTime};
+
+const MINUTE_MS: i64 = 60_000;
+const DAY_MS: i64 = 86_400_000;
+const DAY_MINUTES: u32 = 1_440;
+const HOLIDAY_SCAN_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday
+}
+
+impl Weekday {
+    pub const WEEKDAYS: [Weekday; 5] = [Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday, Weekday::Friday];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /*
+    Weekday of a day number counted from 1970-01-01, a Thursday
+    */
+    fn of_day(day: i64) -> usize {
+        (day + 3).rem_euclid(7) as usize
+    }
+}
+
+/*
+Open, Break: inside a session, continuous trading or a scheduled pause. Closed: outside every session,
+including holidays and weekends
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketPhase {
+    Open,
+    Break,
+    Closed
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarError {
+    InvalidSession { open: u32, close: u32 },
+    InvalidBreak { start: u32, end: u32 },
+    InvalidDate { year: i32, month: u32, day: u32 }
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarError::InvalidSession { open, close } =>
+                write!(f, "session {}-{} must open before it closes and last at most a day", open, close),
+            CalendarError::InvalidBreak { start, end } => write!(f, "break {}-{} must lie inside the session and not overlap another", start, end),
+            CalendarError::InvalidDate { year, month, day } => write!(f, "{:04}-{:02}-{:02} is not a valid date", year, month, day)
+        }
+    }
+}
+
+impl Error for CalendarError {}
+
+/*
+A daily session in minutes after local midnight of the day it opens. close may pass 1440 for sessions
+running overnight (e.g. 1080 to 2460 for 18:00 to 17:00 the next day). Breaks use the same clock
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradingSession {
+    pub open: u32,
+    pub close: u32,
+    pub breaks: Vec<(u32, u32)>
+}
+
+impl TradingSession {
+    pub fn new(open: u32, close: u32) -> Result<TradingSession, CalendarError> {
+        match open < close && close - open <= DAY_MINUTES {
+            true => Ok(TradingSession { open, close, breaks: Vec::new() }),
+            false => Err(CalendarError::InvalidSession { open, close })
+        }
+    }
+
+    pub fn with_break(mut self, start: u32, end: u32) -> Result<TradingSession, CalendarError> {
+        let overlaps = self.breaks.iter().any(|(other_start, other_end)| start < *other_end && *other_start < end);
+        if !(self.open < start && start < end && end < self.close) || overlaps {
+            return Err(CalendarError::InvalidBreak { start, end });
+        }
+        self.breaks.push((start, end));
+        self.breaks.sort_unstable();
+        Ok(self)
+    }
+}
+
+/*
+Trading sessions by local weekday and holiday dates for one instrument. Timestamps are UTC ms; local
+time is UTC plus utc_offset_minutes, fixed for the calendar (daylight saving shifts are not modeled).
+A session belongs to the weekday it opens on and is skipped entirely when that date is a holiday
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TradingCalendar {
+    pub utc_offset_minutes: i32,
+    sessions: [Option<TradingSession>; 7],
+    holidays: BTreeSet<i64>
+}
+
+impl TradingCalendar {
+    pub fn new(utc_offset_minutes: i32) -> TradingCalendar {
+        TradingCalendar {
+            utc_offset_minutes,
+            ..TradingCalendar::default()
+        }
+    }
+
+    /*
+    Open every day around the clock, e.g. crypto venues
+    */
+    pub fn always_open() -> TradingCalendar {
+        let mut calendar = TradingCalendar::new(0);
+        for day in 0..7 {
+            calendar.sessions[day] = Some(TradingSession { open: 0, close: DAY_MINUTES, breaks: Vec::new() });
+        }
+        calendar
+    }
+
+    pub fn set_session(&mut self, days: &[Weekday], session: TradingSession) {
+        for day in days.iter() {
+            self.sessions[day.index()] = Some(session.clone());
+        }
+    }
+
+    pub fn session(&self, day: Weekday) -> Option<&TradingSession> {
+        self.sessions[day.index()].as_ref()
+    }
+
+    /*
+    Mark a local calendar date as closed
+    */
+    pub fn add_holiday(&mut self, year: i32, month: u32, day: u32) -> Result<(), CalendarError> {
+        self.holidays.insert(day_number(year, month, day)?);
+        Ok(())
+    }
+
+    pub fn is_holiday(&self, year: i32, month: u32, day: u32) -> bool {
+        day_number(year, month, day).is_ok_and(|day| self.holidays.contains(&day))
+    }
+
+    /*
+    Exchange-local wall-clock time of a UTC ms timestamp, for reports and session logic
+    */
+    pub fn local_time(&self, timestamp: u64) -> LocalTime {
+        LocalTime::from_millis(timestamp, self.utc_offset_minutes)
+    }
+
+    pub fn phase(&self, timestamp: u64) -> MarketPhase {
+        let timestamp = timestamp as i64;
+        let today = self.local_day(timestamp);
+        let mut phase = MarketPhase::Closed;
+        for day in [today - 1, today] {
+            if let Some((open, close)) = self.session_bounds(day) {
+                if open <= timestamp && timestamp < close {
+                    phase = MarketPhase::Break;
+                }
+            }
+            if self.intervals(day).iter().any(|(start, end)| *start <= timestamp && timestamp < *end) {
+                return MarketPhase::Open;
+            }
+        }
+        phase
+    }
+
+    pub fn is_open(&self, timestamp: u64) -> bool {
+        self.phase(timestamp) == MarketPhase::Open
+    }
+
+    /*
+    timestamp itself when open, otherwise the start of the next open interval within a year, so replay
+    can jump over closed periods. None when no session opens in that time
+    */
+    pub fn next_open(&self, timestamp: u64) -> Option<u64> {
+        let timestamp = timestamp as i64;
+        let today = self.local_day(timestamp);
+        (today - 1..=today + HOLIDAY_SCAN_DAYS)
+            .flat_map(|day| self.intervals(day))
+            .find(|(_, end)| *end > timestamp)
+            .map(|(start, _)| start.max(timestamp) as u64)
+    }
+
+    /*
+    End of the session containing timestamp, the natural end-of-day boundary for rollups. None outside a session
+    */
+    pub fn session_close(&self, timestamp: u64) -> Option<u64> {
+        let timestamp = timestamp as i64;
+        let today = self.local_day(timestamp);
+        [today - 1, today]
+            .into_iter()
+            .filter_map(|day| self.session_bounds(day))
+            .find(|(open, close)| *open <= timestamp && timestamp < *close)
+            .map(|(_, close)| close as u64)
+    }
+
+    /*
+    Milliseconds of open trading in [from, to), excluding breaks, weekends and holidays
+    */
+    pub fn open_ms(&self, from: u64, to: u64) -> u64 {
+        if to <= from {
+            return 0;
+        }
+        let (from, to) = (from as i64, to as i64);
+        (self.local_day(from) - 1..=self.local_day(to))
+            .flat_map(|day| self.intervals(day))
+            .map(|(start, end)| (end.min(to) - start.max(from)).max(0) as u64)
+            .sum()
+    }
+
+    fn local_day(&self, timestamp: i64) -> i64 {
+        (timestamp + self.utc_offset_minutes as i64 * MINUTE_MS).div_euclid(DAY_MS)
+    }
+
+    /*
+    The session opening on local day with that day's local midnight in UTC ms. None on holidays
+    */
+    fn session_on(&self, day: i64) -> Option<(&TradingSession, i64)> {
+        if self.holidays.contains(&day) {
+            return None;
+        }
+        let session = self.sessions[Weekday::of_day(day)].as_ref()?;
+        Some((session, day * DAY_MS - self.utc_offset_minutes as i64 * MINUTE_MS))
+    }
+
+    /*
+    UTC ms of the session opening on local day, from open to close including breaks
+    */
+    fn session_bounds(&self, day: i64) -> Option<(i64, i64)> {
+        let (session, midnight) = self.session_on(day)?;
+        Some((midnight + session.open as i64 * MINUTE_MS, midnight + session.close as i64 * MINUTE_MS))
+    }
+
+    /*
+    Open intervals in UTC ms of the session opening on local day, in order
+    */
+    fn intervals(&self, day: i64) -> Vec<(i64, i64)> {
+        let (session, midnight) = match self.session_on(day) {
+            Some(session) => session,
+            None => return Vec::new()
+        };
+        let at = |minutes: u32| midnight + minutes as i64 * MINUTE_MS;
+        let mut intervals = Vec::with_capacity(session.breaks.len() + 1);
+        let mut start = at(session.open);
+        for (break_start, break_end) in session.breaks.iter() {
+            intervals.push((start, at(*break_start)));
+            start = at(*break_end);
+        }
+        intervals.push((start, at(session.close)));
+        intervals
+    }
+}
+
+fn day_number(year: i32, month: u32, day: u32) -> Result<i64, CalendarError> {
+    days_from_civil(year, month, day).ok_or(CalendarError::InvalidDate { year, month, day })
+}
@@ -0,0 +1,87 @@
+/*
+Purpose: Bounded log of recent book events for catch-up by late or reconnecting consumers
+*/
+
+use std::collections::VecDeque;
+
+use crate::{BookEvent, Projection};
+
+/*
+Answer to changes_since. Deltas are (sequence, event) in order and bring a consumer at the requested
+sequence up to date; SnapshotRequired means the log no longer covers it and a full snapshot is needed
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Changes {
+    Deltas(Vec<(u64, BookEvent)>),
+    SnapshotRequired
+}
+
+/*
+Projection numbering every book event from 1 and retaining the last capacity of them.
+Sequence 0 is the state before the first event the log saw
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeLog {
+    pub capacity: usize,
+    pub events: VecDeque<(u64, BookEvent)>,
+    sequence: u64
+}
+
+impl ChangeLog {
+    pub fn new(capacity: usize) -> ChangeLog {
+        ChangeLog {
+            capacity: capacity.max(1),
+            events: VecDeque::with_capacity(capacity.max(1)),
+            sequence: 0
+        }
+    }
+
+    /*
+    Sequence of the latest event; a consumer that has applied it is up to date
+    */
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn changes_since(&self, sequence: u64) -> Changes {
+        if sequence > self.sequence {
+            return Changes::SnapshotRequired;
+        }
+        let oldest = self.events.front().map_or(self.sequence + 1, |(oldest, _)| *oldest);
+        if sequence + 1 < oldest {
+            return Changes::SnapshotRequired;
+        }
+        Changes::Deltas(self.events.iter().filter(|(event_sequence, _)| *event_sequence > sequence).copied().collect())
+    }
+}
+
+impl Projection for ChangeLog {
+    fn apply(&mut self, event: &BookEvent, _timestamp: u64) {
+        self.sequence += 1;
+        // capacity is pub and may have been lowered or zeroed since the last event
+        while self.events.len() >= self.capacity.max(1) {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.sequence, *event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowered_capacity_trims_on_the_next_event() {
+        let mut log = ChangeLog::new(8);
+        for _ in 0..8 {
+            log.apply(&BookEvent::Cleared, 0);
+        }
+        log.capacity = 3;
+        log.apply(&BookEvent::Cleared, 0);
+        assert_eq!(log.events.iter().map(|(sequence, _)| *sequence).collect::<Vec<_>>(), vec![7, 8, 9]);
+        log.capacity = 0;
+        log.apply(&BookEvent::Cleared, 0);
+        assert_eq!(log.events.len(), 1);
+        assert_eq!(log.changes_since(9), Changes::Deltas(vec![(10, BookEvent::Cleared)]));
+    }
+}
@@ -0,0 +1,216 @@
+/*
+Purpose: Pluggable venue book checksums with shared level serialization and CRC32
+*/
+
+use crate::{BookSnapshot, Orderbook};
+
+/*
+A venue's book checksum over the best depth levels of a snapshot. Implementations serialize levels
+with the snapshot's decimals, so the book must be configured with the venue's precision
+*/
+pub trait BookChecksum {
+    fn depth(&self) -> usize;
+    fn checksum(&self, snapshot: &BookSnapshot) -> u32;
+}
+
+/*
+Order levels are serialized in. Interleaved alternates bid and ask from the best, continuing with the
+longer side once the shorter runs out
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelOrder {
+    AsksThenBids,
+    BidsThenAsks,
+    Interleaved
+}
+
+/*
+How a scaled value is written. Decimal keeps the decimal point, with trailing fractional zeros removed
+when trim_zeros is set. DigitsOnly drops the point and leading zeros
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigitStyle {
+    Decimal { trim_zeros: bool },
+    DigitsOnly
+}
+
+/*
+CRC32 (IEEE) of the best depth levels per side, each written as price, separator, quantity and joined
+with separator in order
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcChecksum {
+    pub depth: usize,
+    pub order: LevelOrder,
+    pub style: DigitStyle,
+    pub separator: String
+}
+
+impl CrcChecksum {
+    /*
+    Kraken: top 10 asks then top 10 bids, digits only, no separator
+    */
+    pub fn kraken() -> CrcChecksum {
+        CrcChecksum { depth: 10, order: LevelOrder::AsksThenBids, style: DigitStyle::DigitsOnly, separator: String::new() }
+    }
+
+    /*
+    OKX: top 25 levels interleaved bid then ask as price:size. The venue publishes the value as a signed
+    32-bit integer; Orderbook::verify_checksum accepts either sign convention. Values must match the feed's
+    strings, so trim_zeros depends on how the instrument is quoted
+    */
+    pub fn okx(trim_zeros: bool) -> CrcChecksum {
+        CrcChecksum { depth: 25, order: LevelOrder::Interleaved, style: DigitStyle::Decimal { trim_zeros }, separator: String::from(":") }
+    }
+
+    /*
+    The exact string the CRC is taken over, for comparing against a venue's documentation
+    */
+    pub fn payload(&self, snapshot: &BookSnapshot) -> String {
+        let price_decimals = decimals(snapshot.price_factor());
+        let quantity_decimals = decimals(snapshot.quantity_factor());
+        let level = |(price, quantity): &(i64, u64)| {
+            format!(
+                "{}{}{}",
+                format_scaled(*price as i128, price_decimals, self.style),
+                self.separator,
+                format_scaled(*quantity as i128, quantity_decimals, self.style)
+            )
+        };
+        let bids = snapshot.bids().iter().take(self.depth).map(level);
+        let asks = snapshot.asks().iter().take(self.depth).map(level);
+        let levels: Vec<String> = match self.order {
+            LevelOrder::AsksThenBids => asks.chain(bids).collect(),
+            LevelOrder::BidsThenAsks => bids.chain(asks).collect(),
+            LevelOrder::Interleaved => {
+                let (mut bids, mut asks) = (bids.fuse(), asks.fuse());
+                let mut levels = Vec::new();
+                loop {
+                    let (bid, ask) = (bids.next(), asks.next());
+                    if bid.is_none() && ask.is_none() {
+                        break;
+                    }
+                    levels.extend(bid);
+                    levels.extend(ask);
+                }
+                levels
+            }
+        };
+        levels.join(&self.separator)
+    }
+}
+
+impl BookChecksum for CrcChecksum {
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn checksum(&self, snapshot: &BookSnapshot) -> u32 {
+        crc32(self.payload(snapshot).as_bytes())
+    }
+}
+
+impl<M> Orderbook<M> {
+    pub fn checksum(&self, algorithm: &dyn BookChecksum) -> u32 {
+        algorithm.checksum(&self.snapshot_depth(algorithm.depth()))
+    }
+
+    /*
+    Compare against a venue-published checksum given as either an unsigned or a signed 32-bit value
+    */
+    pub fn verify_checksum(&self, algorithm: &dyn BookChecksum, expected: i64) -> bool {
+        match u32::try_from(expected).ok().or_else(|| i32::try_from(expected).ok().map(|expected| expected as u32)) {
+            Some(expected) => self.checksum(algorithm) == expected,
+            None => false
+        }
+    }
+}
+
+/*
+Write a scaled integer with decimals fractional digits in style. With 0 decimals there is no fractional
+part; beyond 38 decimals every value is below one, as 10^decimals exceeds any i128
+*/
+pub fn format_scaled(value: i128, decimals: u8, style: DigitStyle) -> String {
+    let sign = match value < 0 {
+        true => "-",
+        false => ""
+    };
+    let (whole, fraction) = match 10u128.checked_pow(decimals as u32) {
+        Some(factor) => (value.unsigned_abs() / factor, value.unsigned_abs() % factor),
+        None => (0, value.unsigned_abs())
+    };
+    let fraction = match decimals {
+        0 => String::new(),
+        _ => format!("{:0width$}", fraction, width = decimals as usize)
+    };
+    match style {
+        DigitStyle::Decimal { trim_zeros } => {
+            let fraction = match trim_zeros {
+                true => fraction.trim_end_matches('0'),
+                false => fraction.as_str()
+            };
+            match fraction.is_empty() {
+                true => format!("{}{}", sign, whole),
+                false => format!("{}{}.{}", sign, whole, fraction)
+            }
+        },
+        DigitStyle::DigitsOnly => {
+            let digits = format!("{}{}", whole, fraction);
+            let digits = digits.trim_start_matches('0');
+            match digits.is_empty() {
+                true => String::from("0"),
+                false => format!("{}{}", sign, digits)
+            }
+        }
+    }
+}
+
+/*
+CRC-32/ISO-HDLC as used by zlib and the venues' checksums
+*/
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb8_8320,
+                _ => crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn decimals(factor: f64) -> u8 {
+    factor.log10().round().max(0.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_whole_and_very_fine_values() {
+        assert_eq!(format_scaled(5, 0, DigitStyle::DigitsOnly), "5");
+        assert_eq!(format_scaled(-5, 0, DigitStyle::Decimal { trim_zeros: false }), "-5");
+        assert_eq!(format_scaled(0, 0, DigitStyle::Decimal { trim_zeros: true }), "0");
+        assert_eq!(format_scaled(500, 2, DigitStyle::DigitsOnly), "500");
+        assert_eq!(format_scaled(500, 2, DigitStyle::Decimal { trim_zeros: false }), "5.00");
+        assert_eq!(format_scaled(12, 40, DigitStyle::Decimal { trim_zeros: true }), format!("0.{}12", "0".repeat(38)));
+        assert_eq!(format_scaled(i128::MIN, 255, DigitStyle::DigitsOnly), i128::MIN.to_string());
+    }
+
+    #[test]
+    fn okx_documented_payload() {
+        // Example book from OKX's order book checksum documentation, quoted to 0.1 with whole sizes
+        let mut book = Orderbook::new(Some(1), Some(0));
+        book.process(vec![(3366.1, 7.0), (3366.0, 6.0)], vec![(3366.8, 9.0), (3368.0, 8.0)], true);
+        let okx = CrcChecksum::okx(true);
+        assert_eq!(okx.payload(&book.snapshot()), "3366.1:7:3366.8:9:3366:6:3368:8");
+        assert_eq!(book.checksum(&okx), 2_413_953_002);
+        assert!(book.verify_checksum(&okx, -1_881_014_294));
+        assert_eq!(CrcChecksum::okx(false).payload(&book.snapshot()), "3366.1:7:3366.8:9:3366.0:6:3368.0:8");
+        assert_eq!(CrcChecksum::kraken().payload(&book.snapshot()), "336689336808336617336606");
+    }
+}
@@ -0,0 +1,266 @@
+/*
+Purpose: Compact binary wire codec for book deltas (flag byte + zigzag varint price diffs)
+*/
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{BookEvent, Orderbook, Side};
+
+// Flag byte layout: bit 0 side (0 bid, 1 ask), bits 1-2 action
+const FLAG_ASK: u8 = 0b001;
+const ACTION_SET: u8 = 0b000;
+const ACTION_REMOVE: u8 = 0b010;
+const ACTION_CLEAR: u8 = 0b100;
+const ACTION_MASK: u8 = 0b110;
+
+/*
+One change to apply, in scaled prices and quantities. Clear empties both sides
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireDelta {
+    Set { side: Side, price: i64, quantity: u64 },
+    Remove { side: Side, price: i64 },
+    Clear
+}
+
+impl WireDelta {
+    /*
+    The delta a book event applies. None for Reset, which changes no levels, and for Rescaled, which
+    changes the scale of every level and needs a resync at the new decimals
+    */
+    pub fn from_event(event: &BookEvent) -> Option<WireDelta> {
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, .. } => Some(WireDelta::Set { side, price, quantity }),
+            BookEvent::LevelRemoved { side, price, .. } => Some(WireDelta::Remove { side, price }),
+            BookEvent::Cleared => Some(WireDelta::Clear),
+            BookEvent::Reset | BookEvent::Rescaled { .. } => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated,
+    VarintOverflow,
+    InvalidFlags(u8)
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "frame ends mid-delta"),
+            DecodeError::VarintOverflow => write!(f, "varint longer than 64 bits"),
+            DecodeError::InvalidFlags(flags) => write!(f, "invalid delta flags {:#04x}", flags)
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/*
+Last price seen per side. Encoder and decoder each keep one and must see the same frames in order;
+Clear resets both sides to 0
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PriceState {
+    bid: i64,
+    ask: i64
+}
+
+impl PriceState {
+    fn last(&mut self, side: Side) -> &mut i64 {
+        match side {
+            Side::Bid => &mut self.bid,
+            Side::Ask => &mut self.ask
+        }
+    }
+}
+
+/*
+A frame is a varint delta count followed by each delta: a flag byte, then for Set and Remove the
+zigzag varint difference from the previous price on that side, then for Set the varint quantity
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeltaEncoder {
+    prices: PriceState
+}
+
+impl DeltaEncoder {
+    pub fn new() -> DeltaEncoder {
+        DeltaEncoder::default()
+    }
+
+    /*
+    Append one frame to output
+    */
+    pub fn encode(&mut self, deltas: &[WireDelta], output: &mut Vec<u8>) {
+        write_varint(output, deltas.len() as u64);
+        for delta in deltas.iter() {
+            match *delta {
+                WireDelta::Set { side, price, quantity } => {
+                    output.push(ACTION_SET | side_flag(side));
+                    self.write_price(output, side, price);
+                    write_varint(output, quantity);
+                },
+                WireDelta::Remove { side, price } => {
+                    output.push(ACTION_REMOVE | side_flag(side));
+                    self.write_price(output, side, price);
+                },
+                WireDelta::Clear => {
+                    output.push(ACTION_CLEAR);
+                    self.prices = PriceState::default();
+                }
+            }
+        }
+    }
+
+    fn write_price(&mut self, output: &mut Vec<u8>, side: Side, price: i64) {
+        let last = self.prices.last(side);
+        write_varint(output, zigzag(price.wrapping_sub(*last)));
+        *last = price;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeltaDecoder {
+    prices: PriceState
+}
+
+impl DeltaDecoder {
+    pub fn new() -> DeltaDecoder {
+        DeltaDecoder::default()
+    }
+
+    /*
+    Decode the frame at the start of input. Returns its deltas and the bytes consumed.
+    On error the decoder state is unchanged
+    */
+    pub fn decode(&mut self, input: &[u8]) -> Result<(Vec<WireDelta>, usize), DecodeError> {
+        let mut position = 0;
+        let mut prices = self.prices;
+        let count = read_varint(input, &mut position)?;
+        let mut deltas = Vec::with_capacity((count as usize).min(input.len()));
+        for _ in 0..count {
+            let flags = *input.get(position).ok_or(DecodeError::Truncated)?;
+            position += 1;
+            let side = match flags & FLAG_ASK {
+                0 => Side::Bid,
+                _ => Side::Ask
+            };
+            let delta = match flags & ACTION_MASK {
+                _ if flags & !(ACTION_MASK | FLAG_ASK) != 0 => return Err(DecodeError::InvalidFlags(flags)),
+                ACTION_SET => {
+                    let price = read_price(input, &mut position, prices.last(side))?;
+                    WireDelta::Set { side, price, quantity: read_varint(input, &mut position)? }
+                },
+                ACTION_REMOVE => WireDelta::Remove { side, price: read_price(input, &mut position, prices.last(side))? },
+                ACTION_CLEAR if flags == ACTION_CLEAR => {
+                    prices = PriceState::default();
+                    WireDelta::Clear
+                },
+                _ => return Err(DecodeError::InvalidFlags(flags))
+            };
+            deltas.push(delta);
+        }
+        self.prices = prices;
+        Ok((deltas, position))
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Apply decoded deltas as process would, but in the book's scale with no f64 round trip, so keys and
+    quantities beyond 2^51 arrive exactly. A Clear makes the update a snapshot of the deltas after it.
+    The deltas must be in this book's decimals; adaptive precision is not applied
+    */
+    pub fn apply_wire_deltas(&mut self, deltas: &[WireDelta]) {
+        let start = deltas.iter().rposition(|delta| *delta == WireDelta::Clear);
+        if start.is_some() {
+            self.clear_levels();
+        }
+        for delta in deltas[start.map_or(0, |clear| clear + 1)..].iter() {
+            match *delta {
+                WireDelta::Set { side, price, quantity } => self.apply_scaled_level(side, price, quantity),
+                WireDelta::Remove { side, price } => self.apply_scaled_level(side, price, 0),
+                WireDelta::Clear => ()
+            }
+        }
+        self.finish_update(start.is_some());
+    }
+}
+
+fn side_flag(side: Side) -> u8 {
+    match side {
+        Side::Bid => 0,
+        Side::Ask => FLAG_ASK
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &[u8], position: &mut usize) -> Result<u64, DecodeError> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *input.get(*position).ok_or(DecodeError::Truncated)?;
+        *position += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::VarintOverflow)
+}
+
+fn read_price(input: &[u8], position: &mut usize, last: &mut i64) -> Result<i64, DecodeError> {
+    let price = last.wrapping_add(unzigzag(read_varint(input, position)?));
+    *last = price;
+    Ok(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_deltas_apply_in_scale() {
+        let mut book = Orderbook::new(Some(2), Some(0));
+        book.apply_wire_deltas(&[
+            WireDelta::Set { side: Side::Bid, price: 1, quantity: 1 },
+            WireDelta::Clear,
+            WireDelta::Set { side: Side::Bid, price: (1 << 55) + 1, quantity: u64::MAX },
+            WireDelta::Set { side: Side::Ask, price: (1 << 55) + 3, quantity: (1 << 54) + 1 },
+            WireDelta::Set { side: Side::Ask, price: (1 << 55) + 5, quantity: 2 }
+        ]);
+        book.apply_wire_deltas(&[WireDelta::Remove { side: Side::Ask, price: (1 << 55) + 5 }]);
+        assert_eq!(book.bids.iter().collect::<Vec<_>>(), vec![(&((1 << 55) + 1), &u64::MAX)]);
+        assert_eq!(book.asks.iter().collect::<Vec<_>>(), vec![(&((1 << 55) + 3), &((1 << 54) + 1))]);
+
+        let mut processed = Orderbook::new(Some(2), Some(0));
+        processed.process(vec![(99.5, 3.0), (99.0, 1.0)], vec![(100.5, 2.0)], true);
+        processed.process(vec![(99.0, 0.0)], vec![(101.0, 4.0)], false);
+        let mut applied = Orderbook::new(Some(2), Some(0));
+        applied.apply_wire_deltas(&[
+            WireDelta::Clear,
+            WireDelta::Set { side: Side::Bid, price: 9950, quantity: 3 },
+            WireDelta::Set { side: Side::Bid, price: 9900, quantity: 1 },
+            WireDelta::Set { side: Side::Ask, price: 10050, quantity: 2 }
+        ]);
+        applied.apply_wire_deltas(&[WireDelta::Remove { side: Side::Bid, price: 9900 }, WireDelta::Set { side: Side::Ask, price: 10100, quantity: 4 }]);
+        assert_eq!((applied.bids, applied.asks), (processed.bids, processed.asks));
+    }
+}
@@ -0,0 +1,170 @@
+/*
+Purpose: Validated book configuration and builder
+*/
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Orderbook, PrunePolicy, MAX_DECIMALS};
+
+/*
+Everything needed to construct a configured book, in a form that can be stored per symbol.
+max_depth is shorthand for PrunePolicy::MaxLevels and cannot be combined with another prune policy.
+adaptive_precision enables adaptive decimals with that sample size; unset decimals then start at 0
+rather than the default
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BookConfig {
+    pub price_decimals: Option<u8>,
+    pub quantity_decimals: Option<u8>,
+    pub max_depth: Option<usize>,
+    pub prune_policy: Option<PrunePolicy>,
+    pub level_ttl: Option<u64>,
+    pub stale_after: Option<u64>,
+    pub require_live: bool,
+    pub adaptive_precision: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    TooManyDecimals { field: &'static str, decimals: u8 },
+    ZeroDepth,
+    InvalidDistance(f64),
+    ConflictingPrunePolicy,
+    ZeroDuration { field: &'static str }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::TooManyDecimals { field, decimals } =>
+                write!(f, "{} of {} exceeds the maximum of {}", field, decimals, MAX_DECIMALS),
+            ConfigError::ZeroDepth => write!(f, "max depth must be at least one level"),
+            ConfigError::InvalidDistance(fraction) => write!(f, "prune distance {} must be finite and positive", fraction),
+            ConfigError::ConflictingPrunePolicy => write!(f, "max depth cannot be combined with another prune policy"),
+            ConfigError::ZeroDuration { field } => write!(f, "{} must be greater than zero", field)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl BookConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (field, decimals) in [("price_decimals", self.price_decimals), ("quantity_decimals", self.quantity_decimals)] {
+            if let Some(decimals) = decimals {
+                if decimals > MAX_DECIMALS {
+                    return Err(ConfigError::TooManyDecimals { field, decimals });
+                }
+            }
+        }
+        if self.max_depth.is_some() && self.prune_policy.is_some() {
+            return Err(ConfigError::ConflictingPrunePolicy);
+        }
+        match self.effective_prune_policy() {
+            Some(PrunePolicy::MaxLevels(0)) => return Err(ConfigError::ZeroDepth),
+            Some(PrunePolicy::MaxDistance(fraction)) if !(fraction.is_finite() && fraction > 0.0) =>
+                return Err(ConfigError::InvalidDistance(fraction)),
+            _ => ()
+        }
+        for (field, duration) in [("level_ttl", self.level_ttl), ("stale_after", self.stale_after)] {
+            if duration == Some(0) {
+                return Err(ConfigError::ZeroDuration { field });
+            }
+        }
+        Ok(())
+    }
+
+    /*
+    Validate and construct an empty book with this configuration
+    */
+    pub fn build<M>(&self) -> Result<Orderbook<M>, ConfigError> {
+        self.validate()?;
+        let mut book = match self.adaptive_precision {
+            Some(sample_size) => {
+                let mut book = Orderbook::with_meta(self.price_decimals.or(Some(0)), self.quantity_decimals.or(Some(0)));
+                book.enable_adaptive_precision(sample_size);
+                book
+            },
+            None => Orderbook::with_meta(self.price_decimals, self.quantity_decimals)
+        };
+        book.prune_policy = self.effective_prune_policy();
+        book.level_ttl = self.level_ttl;
+        book.stale_after = self.stale_after;
+        book.require_live = self.require_live;
+        Ok(book)
+    }
+
+    fn effective_prune_policy(&self) -> Option<PrunePolicy> {
+        match self.max_depth {
+            Some(depth) => Some(PrunePolicy::MaxLevels(depth)),
+            None => self.prune_policy
+        }
+    }
+}
+
+/*
+Chained construction of a BookConfig; build validates the combination
+*/
+#[derive(Debug, Clone, Default)]
+pub struct OrderbookBuilder {
+    pub config: BookConfig
+}
+
+impl OrderbookBuilder {
+    pub fn new() -> OrderbookBuilder {
+        OrderbookBuilder::default()
+    }
+
+    pub fn from_config(config: BookConfig) -> OrderbookBuilder {
+        OrderbookBuilder { config }
+    }
+
+    pub fn price_decimals(mut self, decimals: u8) -> OrderbookBuilder {
+        self.config.price_decimals = Some(decimals);
+        self
+    }
+
+    pub fn quantity_decimals(mut self, decimals: u8) -> OrderbookBuilder {
+        self.config.quantity_decimals = Some(decimals);
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> OrderbookBuilder {
+        self.config.max_depth = Some(depth);
+        self
+    }
+
+    pub fn prune_policy(mut self, policy: PrunePolicy) -> OrderbookBuilder {
+        self.config.prune_policy = Some(policy);
+        self
+    }
+
+    pub fn level_ttl(mut self, level_ttl: u64) -> OrderbookBuilder {
+        self.config.level_ttl = Some(level_ttl);
+        self
+    }
+
+    pub fn stale_after(mut self, stale_after: u64) -> OrderbookBuilder {
+        self.config.stale_after = Some(stale_after);
+        self
+    }
+
+    pub fn require_live(mut self, require_live: bool) -> OrderbookBuilder {
+        self.config.require_live = require_live;
+        self
+    }
+
+    pub fn adaptive_precision(mut self, sample_size: usize) -> OrderbookBuilder {
+        self.config.adaptive_precision = Some(sample_size);
+        self
+    }
+
+    pub fn build<M>(&self) -> Result<Orderbook<M>, ConfigError> {
+        self.config.build()
+    }
+}
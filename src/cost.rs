@@ -0,0 +1,66 @@
+/*
+Purpose: Pre-trade cost estimate split into spread, impact and fees
+*/
+
+use crate::{Orderbook, Side};
+
+/*
+Cost of taking quantity (unscaled) against the book, in unscaled quote terms and always positive for a
+normal book. half_spread is paid moving from mid to the best price, impact walking beyond the best
+price to the average fill, fee is fee_rate of the traded notional. Prices are unscaled
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostBreakdown {
+    pub side: Side,
+    pub quantity: f64,
+    pub mid_price: f64,
+    pub best_price: f64,
+    pub average_price: f64,
+    pub half_spread: f64,
+    pub impact: f64,
+    pub fee: f64
+}
+
+impl CostBreakdown {
+    pub fn total(&self) -> f64 {
+        self.half_spread + self.impact + self.fee
+    }
+
+    /*
+    Total cost in basis points of the order's notional at mid, None when mid is zero
+    */
+    pub fn total_bps(&self) -> Option<f64> {
+        let notional = (self.mid_price * self.quantity).abs();
+        match notional > 0.0 {
+            true => Some(self.total() / notional * 10_000.0),
+            false => None
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Side::Bid buys by lifting asks, Side::Ask sells by hitting bids. None without a two-sided book
+    or whenever the matching simulate_taker call returns None
+    */
+    pub fn estimate_cost(&self, side: Side, quantity: f64, fee_rate: f64) -> Option<CostBreakdown> {
+        let summary = self.summary(Some(1));
+        let mid_price = summary.mid_price? / self.price_factor;
+        let (best_price, average_price, direction) = match side {
+            Side::Bid => (summary.best_ask?, self.simulate_taker_buy(quantity)?, 1.0),
+            Side::Ask => (summary.best_bid?, self.simulate_taker_sell(quantity)?, -1.0)
+        };
+        let best_price = self.unscale_price(best_price.0);
+        let average_price = average_price / self.price_factor;
+        Some(CostBreakdown {
+            side,
+            quantity,
+            mid_price,
+            best_price,
+            average_price,
+            half_spread: direction * (best_price - mid_price) * quantity,
+            impact: direction * (average_price - best_price) * quantity,
+            fee: (average_price * quantity).abs() * fee_rate
+        })
+    }
+}
@@ -0,0 +1,272 @@
+/*
+Purpose: Opt-in dumps of book state and recent events on panic or signal for post-mortem reconstruction
+*/
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread;
+
+use crate::events::rescale_tree;
+use crate::{BookEvent, Orderbook, Projection, Side};
+
+// Attempts the panic hook makes to take a lock before giving up on it
+const HOOK_LOCK_ATTEMPTS: usize = 1000;
+
+/*
+Mirror of one book kept by its tap: scaled levels, the book clock, and the last capacity events
+numbered from 1
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SymbolState {
+    price_factor: f64,
+    quantity_factor: f64,
+    timestamp: u64,
+    bids: BTreeMap<i64, u64>,
+    asks: BTreeMap<i64, u64>,
+    sequence: u64,
+    events: VecDeque<(u64, BookEvent)>
+}
+
+type Mirror = Arc<Mutex<SymbolState>>;
+
+/*
+Mirrors by symbol. Taken only to attach a book and to collect the mirrors for a dump, never by a tap
+*/
+type Registry = Arc<Mutex<HashMap<String, Mirror>>>;
+
+/*
+Shared registry of per-symbol book mirrors. Attach each book on its owning thread; each tap then writes
+only its own mirror, so books on different threads never contend, and a dump can be taken from any
+thread, a panic hook or a signal watcher without touching the books themselves.
+Each symbol is written to <dir>/<symbol>.dump ('/' in symbols becomes '_') as lines of:
+factors,<price_factor>,<quantity_factor> / timestamp,<ms> / bid|ask,<scaled price>,<scaled quantity>
+best first / event,<sequence>,<event>
+*/
+#[derive(Debug, Clone)]
+pub struct CrashDump {
+    pub capacity: usize,
+    registry: Registry
+}
+
+/*
+Projection feeding one symbol's mirror. Added to the book by CrashDump::attach
+*/
+pub struct CrashTap {
+    capacity: usize,
+    mirror: Mirror
+}
+
+impl CrashDump {
+    pub fn new(capacity: usize) -> CrashDump {
+        CrashDump {
+            capacity,
+            registry: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    /*
+    Start mirroring book under symbol, replacing any previous mirror of that symbol
+    */
+    pub fn attach<M>(&self, symbol: &str, book: &mut Orderbook<M>) {
+        let mirror = Arc::new(Mutex::new(SymbolState {
+            price_factor: book.price_factor,
+            quantity_factor: book.quantity_factor,
+            ..SymbolState::default()
+        }));
+        lock(&self.registry).insert(symbol.to_string(), mirror.clone());
+        book.add_projection(CrashTap {
+            capacity: self.capacity,
+            mirror
+        });
+    }
+
+    /*
+    Write every attached symbol to dir, creating it if needed. Returns the files written
+    */
+    pub fn write(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let collected = mirrors(&lock(&self.registry));
+        write_mirrors(&collected, dir, true)
+    }
+
+    /*
+    Dump to dir on any panic, then run the previously installed hook. A mirror held on the panicking
+    thread, e.g. a panic inside its tap, is left out of the dump instead of deadlocking
+    */
+    pub fn install_panic_hook(&self, dir: PathBuf) {
+        let registry = self.registry.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let collected = try_lock(&registry).map(|guard| mirrors(&guard));
+            if let Some(collected) = collected {
+                let _ = write_mirrors(&collected, &dir, false);
+            }
+            previous(info);
+        }));
+    }
+
+    /*
+    Dump to dir on SIGUSR1 and SIGTERM from a background thread. SIGUSR1 leaves the process running;
+    SIGTERM then terminates it as the default handler would
+    */
+    #[cfg(all(feature = "signals", unix))]
+    pub fn watch_signals(&self, dir: PathBuf) -> io::Result<thread::JoinHandle<()>> {
+        use signal_hook::consts::{SIGTERM, SIGUSR1};
+        use signal_hook::iterator::Signals;
+        use signal_hook::low_level::emulate_default_handler;
+
+        let mut signals = Signals::new([SIGTERM, SIGUSR1])?;
+        let registry = self.registry.clone();
+        Ok(thread::spawn(move || {
+            for signal in signals.forever() {
+                let collected = mirrors(&lock(&registry));
+                let _ = write_mirrors(&collected, &dir, true);
+                if signal == SIGTERM {
+                    let _ = emulate_default_handler(SIGTERM);
+                }
+            }
+        }))
+    }
+}
+
+impl Projection for CrashTap {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        let mut state = lock(&self.mirror);
+        state.timestamp = timestamp;
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, .. } => {
+                match side {
+                    Side::Bid => state.bids.insert(price, quantity),
+                    Side::Ask => state.asks.insert(price, quantity)
+                };
+            },
+            BookEvent::LevelRemoved { side, price, .. } => {
+                match side {
+                    Side::Bid => state.bids.remove(&price),
+                    Side::Ask => state.asks.remove(&price)
+                };
+            },
+            BookEvent::Cleared => {
+                state.bids.clear();
+                state.asks.clear();
+            },
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => {
+                rescale_tree(&mut state.bids, price_multiplier, quantity_multiplier);
+                rescale_tree(&mut state.asks, price_multiplier, quantity_multiplier);
+                state.price_factor *= price_multiplier as f64;
+                state.quantity_factor *= quantity_multiplier as f64;
+            }
+        }
+        state.sequence += 1;
+        if self.capacity > 0 {
+            if state.events.len() == self.capacity {
+                state.events.pop_front();
+            }
+            let sequence = state.sequence;
+            state.events.push_back((sequence, *event));
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    for _ in 0..HOOK_LOCK_ATTEMPTS {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => return Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => thread::yield_now()
+        }
+    }
+    None
+}
+
+/*
+The registered mirrors, so the registry is released before any mirror is locked
+*/
+fn mirrors(registry: &HashMap<String, Mirror>) -> Vec<(String, Mirror)> {
+    registry.iter().map(|(symbol, mirror)| (symbol.clone(), mirror.clone())).collect()
+}
+
+/*
+Each mirror is formatted under its own lock and written after releasing it. Without wait, a mirror
+that stays locked is skipped
+*/
+fn write_mirrors(mirrors: &[(String, Mirror)], dir: &Path, wait: bool) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::with_capacity(mirrors.len());
+    for (symbol, mirror) in mirrors.iter() {
+        let state = match wait {
+            true => Some(lock(mirror)),
+            false => try_lock(mirror)
+        };
+        let dump = match state {
+            Some(state) => format_state(&state),
+            None => continue
+        };
+        let path = dir.join(format!("{}.dump", symbol.replace('/', "_")));
+        fs::write(&path, dump)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn format_state(state: &SymbolState) -> String {
+    let mut dump = String::new();
+    let _ = writeln!(dump, "factors,{},{}", state.price_factor, state.quantity_factor);
+    let _ = writeln!(dump, "timestamp,{}", state.timestamp);
+    for (price, quantity) in state.bids.iter().rev() {
+        let _ = writeln!(dump, "bid,{},{}", price, quantity);
+    }
+    for (price, quantity) in state.asks.iter() {
+        let _ = writeln!(dump, "ask,{},{}", price, quantity);
+    }
+    for (sequence, event) in state.events.iter() {
+        let _ = writeln!(dump, "event,{},{:?}", sequence, event);
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("orderbook-crashdump-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn writes_each_mirror_and_skips_locked_ones_without_waiting() {
+        let dump = CrashDump::new(2);
+        let mut btc = Orderbook::new(Some(2), Some(2));
+        let mut eth = Orderbook::new(Some(2), Some(2));
+        dump.attach("BTC/USD", &mut btc);
+        dump.attach("ETH/USD", &mut eth);
+        btc.process(vec![(99.0, 1.0)], vec![(101.0, 2.0)], true);
+        eth.process(vec![(9.0, 1.0)], Vec::new(), true);
+
+        let dir = dump_dir("write");
+        let mut written = dump.write(&dir).unwrap_or_default();
+        written.sort();
+        assert_eq!(written, vec![dir.join("BTC_USD.dump"), dir.join("ETH_USD.dump")]);
+        let contents = fs::read_to_string(dir.join("BTC_USD.dump")).unwrap_or_default();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(&lines[..4], &["factors,100,100", "timestamp,0", "bid,9900,100", "ask,10100,200"]);
+        assert_eq!(lines.iter().filter(|line| line.starts_with("event,")).count(), 2);
+
+        let collected = mirrors(&lock(&dump.registry));
+        let held = collected.iter().find(|(symbol, _)| symbol == "ETH/USD").map(|(_, mirror)| lock(mirror));
+        let skipped = dump_dir("skip");
+        assert_eq!(write_mirrors(&collected, &skipped, false).ok(), Some(vec![skipped.join("BTC_USD.dump")]));
+        drop(held);
+        let _ = fs::remove_dir_all(dir);
+        let _ = fs::remove_dir_all(skipped);
+    }
+}
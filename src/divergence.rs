@@ -0,0 +1,149 @@
+/*
+Purpose: Divergence between two venues' books for the same instrument, per update and rolling
+*/
+
+use std::collections::VecDeque;
+
+use crate::Orderbook;
+
+/*
+MidBps and MicropriceBps: signed (b - a) / a of the unscaled prices in basis points.
+DepthDistance: earth mover's distance in basis points between the books' depth profiles within
+steps * step_bps either side of the average of both mids, each normalized to unit mass. A book that is
+a copy of the other shifted by x bps scores about x; the score is always non-negative
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DivergenceMetric {
+    MidBps,
+    MicropriceBps,
+    DepthDistance { step_bps: f64, steps: usize }
+}
+
+/*
+Divergence of b from a. None without two-sided books, with a non-positive reference price, or for
+DepthDistance when either book has no depth in the window. Books may use different scaling
+*/
+pub fn divergence<M, N>(a: &Orderbook<M>, b: &Orderbook<N>, metric: DivergenceMetric) -> Option<f64> {
+    match metric {
+        DivergenceMetric::MidBps => {
+            let (a_mid, b_mid) = (a.summary(Some(1)).mid_price? / a.price_factor, b.summary(Some(1)).mid_price? / b.price_factor);
+            relative_bps(a_mid, b_mid)
+        },
+        DivergenceMetric::MicropriceBps => {
+            let (a_micro, b_micro) = (a.summary(Some(1)).microprice? / a.price_factor, b.summary(Some(1)).microprice? / b.price_factor);
+            relative_bps(a_micro, b_micro)
+        },
+        DivergenceMetric::DepthDistance { step_bps, steps } => {
+            if !(step_bps.is_finite() && step_bps > 0.0 && steps > 0) {
+                return None;
+            }
+            let reference = (a.summary(Some(1)).mid_price? / a.price_factor + b.summary(Some(1)).mid_price? / b.price_factor) / 2.0;
+            if reference <= 0.0 {
+                return None;
+            }
+            let a_profile = depth_profile(a, reference, step_bps, steps)?;
+            let b_profile = depth_profile(b, reference, step_bps, steps)?;
+            let mut cumulative = 0.0;
+            let mut distance = 0.0;
+            for (a_mass, b_mass) in a_profile.iter().zip(b_profile.iter()) {
+                cumulative += a_mass - b_mass;
+                distance += cumulative.abs();
+            }
+            Some(distance * step_bps)
+        }
+    }
+}
+
+/*
+Rolling divergence over the last window_ms of book clock. Observe after an update to either book
+with the latest timestamp (ms, non-decreasing)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivergenceMonitor {
+    pub metric: DivergenceMetric,
+    pub window_ms: u64,
+    pub values: VecDeque<(u64, f64)>
+}
+
+impl DivergenceMonitor {
+    pub fn new(metric: DivergenceMetric, window_ms: u64) -> DivergenceMonitor {
+        DivergenceMonitor {
+            metric,
+            window_ms,
+            values: VecDeque::new()
+        }
+    }
+
+    /*
+    Returns the current divergence, which is retained unless it could not be computed
+    */
+    pub fn observe<M, N>(&mut self, a: &Orderbook<M>, b: &Orderbook<N>, timestamp: u64) -> Option<f64> {
+        while self.values.front().is_some_and(|(sampled, _)| timestamp.saturating_sub(*sampled) > self.window_ms) {
+            self.values.pop_front();
+        }
+        let value = divergence(a, b, self.metric)?;
+        self.values.push_back((timestamp, value));
+        Some(value)
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.values.back().map(|(_, value)| *value)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        match self.values.len() {
+            0 => None,
+            count => Some(self.values.iter().map(|(_, value)| value).sum::<f64>() / count as f64)
+        }
+    }
+
+    pub fn max_abs(&self) -> Option<f64> {
+        self.values.iter().map(|(_, value)| value.abs()).reduce(f64::max)
+    }
+
+    /*
+    Latest value in standard deviations from the window mean, flagging a dislocation against the usual
+    basis between venues. None with fewer than two values or no variance
+    */
+    pub fn zscore(&self) -> Option<f64> {
+        if self.values.len() < 2 {
+            return None;
+        }
+        let mean = self.mean()?;
+        let count = self.values.len() as f64;
+        let variance = self.values.iter().map(|(_, value)| (value - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        match variance > 0.0 {
+            true => Some((self.latest()? - mean) / variance.sqrt()),
+            false => None
+        }
+    }
+}
+
+fn relative_bps(a: f64, b: f64) -> Option<f64> {
+    match a > 0.0 {
+        true => Some((b - a) / a * 10_000.0),
+        false => None
+    }
+}
+
+/*
+Unscaled depth of both sides in 2 * steps buckets of step_bps from -steps * step_bps to +steps * step_bps
+around reference, normalized to unit mass. None when no depth falls in the window
+*/
+fn depth_profile<M>(book: &Orderbook<M>, reference: f64, step_bps: f64, steps: usize) -> Option<Vec<f64>> {
+    let mut profile = vec![0.0; 2 * steps];
+    let width = step_bps * steps as f64;
+    for (price, quantity) in book.bids.iter().chain(book.asks.iter()) {
+        let offset = (book.unscale_price(*price) - reference) / reference * 10_000.0 + width;
+        if (0.0..2.0 * width).contains(&offset) {
+            let bucket = ((offset / step_bps) as usize).min(2 * steps - 1);
+            profile[bucket] += book.unscale_qty(*quantity);
+        }
+    }
+    let total: f64 = profile.iter().sum();
+    if total <= 0.0 {
+        return None;
+    }
+    profile.iter_mut().for_each(|mass| *mass /= total);
+    Some(profile)
+}
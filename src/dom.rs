@@ -0,0 +1,106 @@
+/*
+Purpose: Depth-of-market ladder widget for ratatui frontends
+*/
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::Widget;
+
+use crate::BookSnapshot;
+
+const COLUMN_WIDTH: usize = 14;
+
+/*
+Renders a price ladder with asks stacked above bids, best prices meeting in the middle.
+Each row shows price, bid quantity, ask quantity and a bar scaled to the largest visible level.
+Trade markers and position highlights are scaled prices supplied by the caller. Renders from a
+BookSnapshot, e.g. Orderbook::snapshot_depth or BookReader::load on a render thread
+*/
+pub struct DomWidget<'a> {
+    snapshot: &'a BookSnapshot,
+    levels: usize,
+    trades: &'a [i64],
+    positions: &'a [i64]
+}
+
+impl<'a> DomWidget<'a> {
+    pub fn new(snapshot: &'a BookSnapshot, levels: usize) -> DomWidget<'a> {
+        DomWidget {
+            snapshot,
+            levels,
+            trades: &[],
+            positions: &[]
+        }
+    }
+
+    pub fn trades(mut self, trades: &'a [i64]) -> DomWidget<'a> {
+        self.trades = trades;
+        self
+    }
+
+    pub fn positions(mut self, positions: &'a [i64]) -> DomWidget<'a> {
+        self.positions = positions;
+        self
+    }
+
+    fn format_value(value: f64, factor: f64) -> String {
+        let decimals = factor.log10() as usize;
+        format!("{:.*}", decimals, value / factor)
+    }
+}
+
+impl Widget for DomWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows_per_side = self.levels.min((area.height / 2) as usize);
+        let asks = &self.snapshot.asks()[..rows_per_side.min(self.snapshot.asks().len())];
+        let bids = &self.snapshot.bids()[..rows_per_side.min(self.snapshot.bids().len())];
+        let max_quantity = asks.iter().chain(bids.iter()).map(|(_, q)| *q).max().unwrap_or(0);
+        let text_width = 3 * COLUMN_WIDTH + 2;
+        let bar_width = (area.width as usize).saturating_sub(text_width + 1);
+
+        // Asks are drawn worst-first so the best ask sits directly above the best bid
+        let rows = asks.iter().rev().map(|level| (level, false)).chain(bids.iter().map(|level| (level, true)));
+        for (row, ((price, quantity), is_bid)) in rows.enumerate() {
+            let y = area.y + row as u16;
+            let price_text = Self::format_value(*price as f64, self.snapshot.price_factor());
+            let quantity_text = Self::format_value(*quantity as f64, self.snapshot.quantity_factor());
+            let marker = if self.trades.contains(price) { "*" } else { " " };
+            let text = match is_bid {
+                true => format!("{:>w$} {:>w$} {:>w$}{}", price_text, quantity_text, "", marker, w = COLUMN_WIDTH),
+                false => format!("{:>w$} {:>w$} {:>w$}{}", price_text, "", quantity_text, marker, w = COLUMN_WIDTH)
+            };
+            let color = if is_bid { Color::Green } else { Color::Red };
+            let mut style = Style::default().fg(color);
+            if self.positions.contains(price) {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            buf.set_stringn(area.x, y, &text, area.width as usize, style);
+            if bar_width > 0 && max_quantity > 0 {
+                let length = ((*quantity as f64) / (max_quantity as f64) * (bar_width as f64)).ceil() as usize;
+                let bar = "█".repeat(length.min(bar_width));
+                buf.set_stringn(area.x + (text_width + 1) as u16, y, &bar, bar_width, Style::default().fg(color));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Orderbook;
+
+    #[test]
+    fn renders_a_snapshot_with_best_prices_in_the_middle() {
+        let book = Orderbook::from_snapshot(Some(2), Some(2), vec![(99.0, 1.0), (98.0, 2.0), (97.0, 3.0)], vec![(101.0, 4.0), (102.0, 1.0)]);
+        let snapshot = book.snapshot_depth(2);
+        let area = Rect::new(0, 0, 60, 4);
+        let mut buf = Buffer::empty(area);
+        DomWidget::new(&snapshot, 5).trades(&[10100]).render(area, &mut buf);
+        let rows: Vec<String> = (0..4).map(|y| (0..60).map(|x| buf[(x, y)].symbol()).collect::<String>()).collect();
+        let prices: Vec<&str> = rows.iter().filter_map(|row| row.split_whitespace().next()).collect();
+        assert_eq!(prices, vec!["102.00", "101.00", "99.00", "98.00"]);
+        assert!(rows[1].contains("4.00*"));
+        assert!(rows[1].ends_with(&"█".repeat(60 - 3 * COLUMN_WIDTH - 3)));
+    }
+}
@@ -0,0 +1,133 @@
+/*
+Purpose: Book mutation events and projections maintained from them
+*/
+
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use crate::{Orderbook, Side};
+
+/*
+Every change to the bid and ask trees, in application order. Prices and quantities are scaled.
+LevelSet carries the quantity it replaced, None for a new level. Cleared empties both sides.
+Reset marks a snapshot reload on a running book and is followed by Cleared and the new levels.
+Rescaled marks a refinement of the book's decimals: every scaled price held so far is multiplied by
+price_multiplier and every scaled quantity by quantity_multiplier (saturating), and no level changes
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookEvent {
+    LevelSet { side: Side, price: i64, quantity: u64, previous: Option<u64> },
+    LevelRemoved { side: Side, price: i64, quantity: u64 },
+    Cleared,
+    Reset,
+    Rescaled { price_multiplier: i64, quantity_multiplier: u64 }
+}
+
+/*
+Derived state updated in the same pass as the book, with the book clock (ms) at the time of the event.
+A projection registered on a non-empty book first receives Cleared followed by a LevelSet for every existing level
+*/
+pub trait Projection: Any + Send {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64);
+}
+
+/*
+Level counts and scaled total quantity per side, maintained incrementally
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepthTotals {
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub bid_quantity: u64,
+    pub ask_quantity: u64
+}
+
+impl Projection for DepthTotals {
+    fn apply(&mut self, event: &BookEvent, _timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, quantity, previous, .. } => {
+                let (levels, total) = match side {
+                    Side::Bid => (&mut self.bid_levels, &mut self.bid_quantity),
+                    Side::Ask => (&mut self.ask_levels, &mut self.ask_quantity)
+                };
+                match previous {
+                    Some(previous) => *total = total.saturating_sub(previous),
+                    None => *levels += 1
+                }
+                *total = total.saturating_add(quantity);
+            },
+            BookEvent::LevelRemoved { side, quantity, .. } => match side {
+                Side::Bid => {
+                    self.bid_levels = self.bid_levels.saturating_sub(1);
+                    self.bid_quantity = self.bid_quantity.saturating_sub(quantity);
+                },
+                Side::Ask => {
+                    self.ask_levels = self.ask_levels.saturating_sub(1);
+                    self.ask_quantity = self.ask_quantity.saturating_sub(quantity);
+                }
+            },
+            BookEvent::Cleared => *self = DepthTotals::default(),
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { quantity_multiplier, .. } => {
+                self.bid_quantity = self.bid_quantity.saturating_mul(quantity_multiplier);
+                self.ask_quantity = self.ask_quantity.saturating_mul(quantity_multiplier);
+            }
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Register a projection, replaying the current levels into it
+    */
+    pub fn add_projection<P: Projection>(&mut self, projection: P) {
+        let mut projection: Box<dyn Projection> = Box::new(projection);
+        replay(projection.as_mut(), &self.bids, &self.asks, self.timestamp);
+        self.projections.push(projection);
+    }
+
+    /*
+    First registered projection of type P
+    */
+    pub fn projection<P: Projection>(&self) -> Option<&P> {
+        self.projections.iter().find_map(|projection| (projection.as_ref() as &dyn Any).downcast_ref::<P>())
+    }
+
+    /*
+    Mutable access to the first registered projection of type P, for projections that also take input
+    from outside the book (e.g. trades)
+    */
+    pub fn projection_mut<P: Projection>(&mut self) -> Option<&mut P> {
+        self.projections.iter_mut().find_map(|projection| (projection.as_mut() as &mut dyn Any).downcast_mut::<P>())
+    }
+
+    pub fn clear_projections(&mut self) {
+        self.projections.clear();
+    }
+
+    pub(crate) fn emit(&mut self, event: BookEvent) {
+        for projection in self.projections.iter_mut() {
+            projection.apply(&event, self.timestamp);
+        }
+    }
+}
+
+/*
+Multiply the keys of a scaled price tree and its values, for handling Rescaled
+*/
+pub(crate) fn rescale_tree(tree: &mut BTreeMap<i64, u64>, price_multiplier: i64, value_multiplier: u64) {
+    *tree = std::mem::take(tree).into_iter().map(|(price, value)| (price.saturating_mul(price_multiplier), value.saturating_mul(value_multiplier))).collect();
+}
+
+/*
+Bring a projection to the state of the given trees: Cleared followed by every level
+*/
+pub(crate) fn replay(projection: &mut dyn Projection, bids: &BTreeMap<i64, u64>, asks: &BTreeMap<i64, u64>, timestamp: u64) {
+    projection.apply(&BookEvent::Cleared, timestamp);
+    for (price, quantity) in bids.iter() {
+        projection.apply(&BookEvent::LevelSet { side: Side::Bid, price: *price, quantity: *quantity, previous: None }, timestamp);
+    }
+    for (price, quantity) in asks.iter() {
+        projection.apply(&BookEvent::LevelSet { side: Side::Ask, price: *price, quantity: *quantity, previous: None }, timestamp);
+    }
+}
@@ -0,0 +1,86 @@
+/*
+Purpose: Fixed-layout feature vector extraction for model pipelines
+*/
+
+use crate::Orderbook;
+
+/*
+Layout of the vector produced by Orderbook::features, in order:
+bid price, bid quantity, ask price, ask quantity for each of the best levels,
+then spread, mid price and microprice if enabled, then one imbalance per entry of imbalance_depths.
+Values are unscaled; levels missing from the book are NaN so the length never changes
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureSpec {
+    pub levels: usize,
+    pub include_spread: bool,
+    pub include_mid_price: bool,
+    pub include_microprice: bool,
+    pub imbalance_depths: Vec<usize>
+}
+
+impl FeatureSpec {
+    /*
+    Column names matching the vector layout
+    */
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for level in 0..self.levels {
+            names.push(format!("bid_price_{}", level));
+            names.push(format!("bid_quantity_{}", level));
+            names.push(format!("ask_price_{}", level));
+            names.push(format!("ask_quantity_{}", level));
+        }
+        if self.include_spread {
+            names.push(String::from("spread"));
+        }
+        if self.include_mid_price {
+            names.push(String::from("mid_price"));
+        }
+        if self.include_microprice {
+            names.push(String::from("microprice"));
+        }
+        for depth in self.imbalance_depths.iter() {
+            names.push(format!("imbalance_{}", depth));
+        }
+        names
+    }
+}
+
+impl<M> Orderbook<M> {
+    pub fn features(&self, spec: &FeatureSpec) -> Vec<f64> {
+        let mut features: Vec<f64> = Vec::with_capacity(4 * spec.levels + 3 + spec.imbalance_depths.len());
+        let mut bids = self.bids.iter().rev();
+        let mut asks = self.asks.iter();
+        for _ in 0..spec.levels {
+            match bids.next() {
+                Some((price, quantity)) => {
+                    features.push(self.unscale_price(*price));
+                    features.push(self.unscale_qty(*quantity));
+                },
+                None => features.extend([f64::NAN, f64::NAN])
+            }
+            match asks.next() {
+                Some((price, quantity)) => {
+                    features.push(self.unscale_price(*price));
+                    features.push(self.unscale_qty(*quantity));
+                },
+                None => features.extend([f64::NAN, f64::NAN])
+            }
+        }
+        let top = self.summary(Some(1));
+        if spec.include_spread {
+            features.push(top.spread.map_or(f64::NAN, |spread| self.unscale_price(spread)));
+        }
+        if spec.include_mid_price {
+            features.push(top.mid_price.map_or(f64::NAN, |mid_price| mid_price / self.price_factor));
+        }
+        if spec.include_microprice {
+            features.push(top.microprice.map_or(f64::NAN, |microprice| microprice / self.price_factor));
+        }
+        for depth in spec.imbalance_depths.iter() {
+            features.push(self.summary(Some(*depth)).imbalance.unwrap_or(f64::NAN));
+        }
+        features
+    }
+}
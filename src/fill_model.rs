@@ -0,0 +1,171 @@
+/*
+Purpose: Fill probability of passive orders from queue position and recent trade-through rates
+*/
+
+use std::collections::VecDeque;
+
+use crate::{BookEvent, Orderbook, Projection, QueueInference, Side};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TapeTrade {
+    resting: Side,
+    price: i64,
+    quantity: u64,
+    timestamp: u64
+}
+
+/*
+Trades at or through a price over the model's window. rate is trades per ms, mean_size their average
+scaled quantity
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeThrough {
+    pub trades: usize,
+    pub quantity: u64,
+    pub rate: f64,
+    pub mean_size: f64
+}
+
+/*
+Projection keeping the trade tape of the last window ms for fill probabilities. A passive order at a
+price is reached by trades against its side at that price or worse for the maker (at or below for bids,
+at or above for asks). Those are modelled as a Poisson process at the window's rate with the window's
+mean size, and the order fills once they have executed more than the quantity ahead of it. Cancels ahead
+are ignored, which makes the estimate conservative. Use Orderbook::projection_mut to record trades
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillModel {
+    pub window: u64,
+    trades: VecDeque<TapeTrade>,
+    started: Option<u64>
+}
+
+impl FillModel {
+    pub fn new(window: u64) -> FillModel {
+        FillModel {
+            window: window.max(1),
+            trades: VecDeque::new(),
+            started: None
+        }
+    }
+
+    /*
+    Record a trade by aggressor side (Side::Bid for buyer-initiated) at a scaled price and quantity
+    */
+    pub fn record_trade(&mut self, aggressor: Side, price: i64, quantity: u64, timestamp: u64) {
+        let resting = match aggressor {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid
+        };
+        self.started.get_or_insert(timestamp);
+        self.expire(timestamp);
+        self.trades.push_back(TapeTrade { resting, price, quantity, timestamp });
+    }
+
+    /*
+    Trades that reached a resting order on side at price within the window ending at now
+    */
+    pub fn trade_through(&self, side: Side, price: i64, now: u64) -> TradeThrough {
+        let since = now.saturating_sub(self.window);
+        let (trades, quantity) = self.trades.iter()
+            .filter(|trade| trade.timestamp > since && trade.timestamp <= now && trade.resting == side)
+            .filter(|trade| match side {
+                Side::Bid => trade.price <= price,
+                Side::Ask => trade.price >= price
+            })
+            .fold((0usize, 0u64), |(trades, quantity), trade| (trades + 1, quantity.saturating_add(trade.quantity)));
+        let span = self.started.map_or(0, |started| now.saturating_sub(started)).clamp(1, self.window.max(1));
+        TradeThrough {
+            trades,
+            quantity,
+            rate: trades as f64 / span as f64,
+            mean_size: match trades {
+                0 => 0.0,
+                trades => quantity as f64 / trades as f64
+            }
+        }
+    }
+
+    /*
+    Probability that an order on side at price with ahead (scaled) queued in front of it receives a fill
+    within horizon ms of now
+    */
+    pub fn probability(&self, side: Side, price: i64, ahead: u64, horizon: u64, now: u64) -> f64 {
+        let through = self.trade_through(side, price, now);
+        if through.trades == 0 {
+            return 0.0;
+        }
+        let needed = (ahead as f64 / through.mean_size).floor() as u64 + 1;
+        poisson_at_least(through.rate * horizon as f64, needed)
+    }
+
+    fn expire(&mut self, now: u64) {
+        while self.trades.front().is_some_and(|trade| now.saturating_sub(trade.timestamp) >= self.window) {
+            self.trades.pop_front();
+        }
+    }
+}
+
+impl Projection for FillModel {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        self.expire(timestamp);
+        if let BookEvent::Rescaled { price_multiplier, quantity_multiplier } = *event {
+            for trade in self.trades.iter_mut() {
+                trade.price = trade.price.saturating_mul(price_multiplier);
+                trade.quantity = trade.quantity.saturating_mul(quantity_multiplier);
+            }
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Chance that a new passive order on side at price (scaled) fills within horizon ms of the book clock,
+    joining behind the displayed quantity there. A price that would cross the book fills at once. None
+    without a registered FillModel
+    */
+    pub fn fill_probability(&self, side: Side, price: i64, horizon: u64) -> Option<f64> {
+        let model = self.projection::<FillModel>()?;
+        let (crosses, ahead) = match side {
+            Side::Bid => (self.asks.first_key_value().is_some_and(|(ask, _)| price >= *ask), self.bids.get(&price)),
+            Side::Ask => (self.bids.last_key_value().is_some_and(|(bid, _)| price <= *bid), self.asks.get(&price))
+        };
+        match crosses {
+            true => Some(1.0),
+            false => Some(model.probability(side, price, ahead.copied().unwrap_or(0), horizon, self.timestamp))
+        }
+    }
+
+    /*
+    fill_probability for an order tracked by the registered QueueInference, using its estimated
+    quantity ahead. None without both projections or for an unknown id
+    */
+    pub fn order_fill_probability(&self, id: u64, horizon: u64) -> Option<f64> {
+        let position = self.projection::<QueueInference>()?.position(id)?;
+        let model = self.projection::<FillModel>()?;
+        Some(model.probability(position.side, position.price, position.ahead, horizon, self.timestamp))
+    }
+}
+
+/*
+P(N >= count) for N Poisson with the given mean, summing the lower tail in log space so large means do
+not underflow
+*/
+fn poisson_at_least(mean: f64, count: u64) -> f64 {
+    if count == 0 {
+        return 1.0;
+    }
+    if mean.is_nan() || mean <= 0.0 {
+        return 0.0;
+    }
+    if count as f64 > mean + 40.0 * mean.sqrt() + 40.0 {
+        return 0.0;
+    }
+    let mut log_term = -mean;
+    let mut below = 0.0;
+    for k in 0..count {
+        below += log_term.exp();
+        log_term += mean.ln() - ((k + 1) as f64).ln();
+    }
+    (1.0 - below).clamp(0.0, 1.0)
+}
@@ -0,0 +1,279 @@
+/*
+Purpose: Fixed-layout binary snapshots and events read in place without deserialization
+*/
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{BookEvent, BookSnapshot, BookState, Side};
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"OBS1";
+const EVENTS_MAGIC: &[u8; 4] = b"OBE1";
+const SNAPSHOT_HEADER: usize = 40;
+const EVENTS_HEADER: usize = 8;
+const LEVEL_SIZE: usize = 16;
+const EVENT_SIZE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlatError {
+    TooShort,
+    BadMagic,
+    LengthMismatch { expected: usize, actual: usize },
+    InvalidRecord(usize)
+}
+
+impl fmt::Display for FlatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlatError::TooShort => write!(f, "buffer shorter than the header"),
+            FlatError::BadMagic => write!(f, "buffer does not start with the expected magic"),
+            FlatError::LengthMismatch { expected, actual } => write!(f, "expected {} bytes, got {}", expected, actual),
+            FlatError::InvalidRecord(index) => write!(f, "invalid record at index {}", index)
+        }
+    }
+}
+
+impl Error for FlatError {}
+
+/*
+Little-endian layout: magic "OBS1", timestamp u64, price_factor f64, quantity_factor f64, state u8,
+3 reserved bytes, bid count u32, ask count u32, then 16-byte (price i64, quantity u64) scaled levels,
+bids best first followed by asks best first
+*/
+impl BookSnapshot {
+    pub fn to_flat(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(SNAPSHOT_HEADER + LEVEL_SIZE * (self.bids().len() + self.asks().len()));
+        output.extend_from_slice(SNAPSHOT_MAGIC);
+        output.extend_from_slice(&self.timestamp().to_le_bytes());
+        output.extend_from_slice(&self.price_factor().to_le_bytes());
+        output.extend_from_slice(&self.quantity_factor().to_le_bytes());
+        output.extend_from_slice(&[state_code(self.state()), 0, 0, 0]);
+        output.extend_from_slice(&(self.bids().len() as u32).to_le_bytes());
+        output.extend_from_slice(&(self.asks().len() as u32).to_le_bytes());
+        for (price, quantity) in self.bids().iter().chain(self.asks().iter()) {
+            output.extend_from_slice(&price.to_le_bytes());
+            output.extend_from_slice(&quantity.to_le_bytes());
+        }
+        output
+    }
+}
+
+/*
+Borrowed view over a flat snapshot. Construction checks the header and length once; accessors then
+read fields directly from the buffer
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatSnapshot<'a> {
+    bytes: &'a [u8],
+    bid_count: usize,
+    ask_count: usize
+}
+
+impl<'a> FlatSnapshot<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<FlatSnapshot<'a>, FlatError> {
+        if bytes.len() < SNAPSHOT_HEADER {
+            return Err(FlatError::TooShort);
+        }
+        if &bytes[0..4] != SNAPSHOT_MAGIC {
+            return Err(FlatError::BadMagic);
+        }
+        if state_from_code(bytes[28]).is_none() {
+            return Err(FlatError::InvalidRecord(0));
+        }
+        let bid_count = read_u32(bytes, 32) as usize;
+        let ask_count = read_u32(bytes, 36) as usize;
+        let expected = LEVEL_SIZE.saturating_mul(bid_count.saturating_add(ask_count)).saturating_add(SNAPSHOT_HEADER);
+        if bytes.len() != expected {
+            return Err(FlatError::LengthMismatch { expected, actual: bytes.len() });
+        }
+        Ok(FlatSnapshot { bytes, bid_count, ask_count })
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        read_u64(self.bytes, 4)
+    }
+
+    pub fn price_factor(&self) -> f64 {
+        f64::from_bits(read_u64(self.bytes, 12))
+    }
+
+    pub fn quantity_factor(&self) -> f64 {
+        f64::from_bits(read_u64(self.bytes, 20))
+    }
+
+    pub fn state(&self) -> BookState {
+        state_from_code(self.bytes[28]).unwrap_or(BookState::Initializing)
+    }
+
+    pub fn bid_count(&self) -> usize {
+        self.bid_count
+    }
+
+    pub fn ask_count(&self) -> usize {
+        self.ask_count
+    }
+
+    /*
+    Scaled level index from the best bid
+    */
+    pub fn bid(&self, index: usize) -> Option<(i64, u64)> {
+        (index < self.bid_count).then(|| self.level(index))
+    }
+
+    pub fn ask(&self, index: usize) -> Option<(i64, u64)> {
+        (index < self.ask_count).then(|| self.level(self.bid_count + index))
+    }
+
+    pub fn bids(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        (0..self.bid_count).map(|index| self.level(index))
+    }
+
+    pub fn asks(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        (0..self.ask_count).map(|index| self.level(self.bid_count + index))
+    }
+
+    /*
+    Copy into an owned BookSnapshot
+    */
+    pub fn to_snapshot(&self) -> BookSnapshot {
+        BookSnapshot::from_parts(self.bids().collect(), self.asks().collect(), self.timestamp(), self.state(), self.price_factor(), self.quantity_factor())
+    }
+
+    fn level(&self, index: usize) -> (i64, u64) {
+        let offset = SNAPSHOT_HEADER + index * LEVEL_SIZE;
+        (read_u64(self.bytes, offset) as i64, read_u64(self.bytes, offset + 8))
+    }
+}
+
+/*
+Little-endian layout: magic "OBE1", count u32, then 40-byte records of sequence u64, kind u8
+(0 LevelSet, 1 LevelRemoved, 2 Cleared, 3 Reset, 4 Rescaled with the price multiplier in price and the
+quantity multiplier in quantity), side u8 (0 bid, 1 ask), has_previous u8,
+5 reserved bytes, price i64, quantity u64, previous u64
+*/
+pub fn events_to_flat(events: &[(u64, BookEvent)]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(EVENTS_HEADER + EVENT_SIZE * events.len());
+    output.extend_from_slice(EVENTS_MAGIC);
+    output.extend_from_slice(&(events.len() as u32).to_le_bytes());
+    for (sequence, event) in events.iter() {
+        let (kind, side, price, quantity, previous) = match *event {
+            BookEvent::LevelSet { side, price, quantity, previous } => (0, Some(side), price, quantity, previous),
+            BookEvent::LevelRemoved { side, price, quantity } => (1, Some(side), price, quantity, None),
+            BookEvent::Cleared => (2, None, 0, 0, None),
+            BookEvent::Reset => (3, None, 0, 0, None),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => (4, None, price_multiplier, quantity_multiplier, None)
+        };
+        output.extend_from_slice(&sequence.to_le_bytes());
+        output.extend_from_slice(&[kind, (side == Some(Side::Ask)) as u8, previous.is_some() as u8, 0, 0, 0, 0, 0]);
+        output.extend_from_slice(&price.to_le_bytes());
+        output.extend_from_slice(&quantity.to_le_bytes());
+        output.extend_from_slice(&previous.unwrap_or(0).to_le_bytes());
+    }
+    output
+}
+
+/*
+Borrowed view over flat events, validated on construction
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatEvents<'a> {
+    bytes: &'a [u8],
+    count: usize
+}
+
+impl<'a> FlatEvents<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<FlatEvents<'a>, FlatError> {
+        if bytes.len() < EVENTS_HEADER {
+            return Err(FlatError::TooShort);
+        }
+        if &bytes[0..4] != EVENTS_MAGIC {
+            return Err(FlatError::BadMagic);
+        }
+        let count = read_u32(bytes, 4) as usize;
+        let expected = EVENT_SIZE.saturating_mul(count).saturating_add(EVENTS_HEADER);
+        if bytes.len() != expected {
+            return Err(FlatError::LengthMismatch { expected, actual: bytes.len() });
+        }
+        if let Some(index) = (0..count).find(|index| bytes[EVENTS_HEADER + index * EVENT_SIZE + 8] > 4) {
+            return Err(FlatError::InvalidRecord(index));
+        }
+        Ok(FlatEvents { bytes, count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn sequence(&self, index: usize) -> Option<u64> {
+        (index < self.count).then(|| read_u64(self.bytes, EVENTS_HEADER + index * EVENT_SIZE))
+    }
+
+    pub fn get(&self, index: usize) -> Option<(u64, BookEvent)> {
+        if index >= self.count {
+            return None;
+        }
+        let offset = EVENTS_HEADER + index * EVENT_SIZE;
+        let side = match self.bytes[offset + 9] {
+            0 => Side::Bid,
+            _ => Side::Ask
+        };
+        let price = read_u64(self.bytes, offset + 16) as i64;
+        let quantity = read_u64(self.bytes, offset + 24);
+        let previous = match self.bytes[offset + 10] {
+            0 => None,
+            _ => Some(read_u64(self.bytes, offset + 32))
+        };
+        let event = match self.bytes[offset + 8] {
+            0 => BookEvent::LevelSet { side, price, quantity, previous },
+            1 => BookEvent::LevelRemoved { side, price, quantity },
+            2 => BookEvent::Cleared,
+            3 => BookEvent::Reset,
+            _ => BookEvent::Rescaled { price_multiplier: price, quantity_multiplier: quantity }
+        };
+        Some((read_u64(self.bytes, offset), event))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, BookEvent)> + '_ {
+        (0..self.count).filter_map(|index| self.get(index))
+    }
+}
+
+pub(crate) fn state_code(state: BookState) -> u8 {
+    match state {
+        BookState::Initializing => 0,
+        BookState::Syncing => 1,
+        BookState::Live => 2,
+        BookState::Stale => 3,
+        BookState::Halted => 4
+    }
+}
+
+pub(crate) fn state_from_code(code: u8) -> Option<BookState> {
+    match code {
+        0 => Some(BookState::Initializing),
+        1 => Some(BookState::Syncing),
+        2 => Some(BookState::Live),
+        3 => Some(BookState::Stale),
+        4 => Some(BookState::Halted),
+        _ => None
+    }
+}
+
+/*
+Callers have checked the buffer length, so the slice is always in bounds
+*/
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buffer)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buffer)
+}
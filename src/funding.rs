@@ -0,0 +1,284 @@
+/*
+Purpose: Perpetual premium index over an index price and predicted funding with settled history
+*/
+
+use std::collections::VecDeque;
+
+use crate::{IndexPrice, Orderbook, Side};
+
+/*
+How premium samples within a funding interval are averaged. Linear weights the i-th sample by i, so
+later samples count more
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PremiumAverage {
+    Mean,
+    Linear
+}
+
+/*
+Funding formula of a linear perpetual: premium samples every sample_interval ms are averaged over each
+funding interval (ms, aligned to multiples of it), and the rate is average + clamp(interest_rate - average,
+-clamp, clamp), limited to +/-cap. Rates are per interval. impact_notional is the quote notional whose
+average fill gives the impact bid and ask
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingParams {
+    pub interval: u64,
+    pub sample_interval: u64,
+    pub impact_notional: f64,
+    pub interest_rate: f64,
+    pub clamp: f64,
+    pub cap: f64,
+    pub average: PremiumAverage
+}
+
+impl FundingParams {
+    /*
+    Binance USD-M: 8h intervals, 5s samples weighted linearly, 0.01% interest, 0.05% clamp. cap and
+    impact_notional vary by symbol
+    */
+    pub fn binance(impact_notional: f64, cap: f64) -> FundingParams {
+        FundingParams {
+            interval: 8 * 3_600_000,
+            sample_interval: 5_000,
+            impact_notional,
+            interest_rate: 0.0001,
+            clamp: 0.0005,
+            cap,
+            average: PremiumAverage::Linear
+        }
+    }
+
+    /*
+    BitMEX: 8h intervals, one-minute samples averaged evenly, 0.01% interest, 0.05% clamp
+    */
+    pub fn bitmex(impact_notional: f64, cap: f64) -> FundingParams {
+        FundingParams {
+            interval: 8 * 3_600_000,
+            sample_interval: 60_000,
+            impact_notional,
+            interest_rate: 0.0001,
+            clamp: 0.0005,
+            cap,
+            average: PremiumAverage::Mean
+        }
+    }
+
+    /*
+    Funding rate for an average premium. clamp and cap apply by magnitude; a NaN one is no bound
+    */
+    pub fn rate(&self, average_premium: f64) -> f64 {
+        let rate = average_premium + bound(self.interest_rate - average_premium, self.clamp.abs());
+        bound(rate, self.cap.abs())
+    }
+}
+
+/*
+One premium sample. Prices are unscaled; premium is (max(0, impact_bid - index) - max(0, index - impact_ask)) / index
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PremiumSample {
+    pub timestamp: u64,
+    pub index: f64,
+    pub impact_bid: f64,
+    pub impact_ask: f64,
+    pub premium: f64
+}
+
+/*
+Funding for the interval starting at interval_start, predicted from samples so far or settled once the
+interval has passed
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingEstimate {
+    pub interval_start: u64,
+    pub funding_time: u64,
+    pub samples: usize,
+    pub average_premium: f64,
+    pub rate: f64
+}
+
+/*
+Samples the premium of a perpetual's book over an index and predicts the current interval's funding.
+Samples are skipped while the book does not accept taker orders or lacks depth for impact_notional.
+Settled intervals are kept up to capacity
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingPredictor {
+    pub params: FundingParams,
+    pub capacity: usize,
+    interval_start: Option<u64>,
+    samples: VecDeque<PremiumSample>,
+    history: VecDeque<FundingEstimate>
+}
+
+impl FundingPredictor {
+    pub fn new(params: FundingParams, capacity: usize) -> FundingPredictor {
+        FundingPredictor {
+            params: FundingParams {
+                interval: params.interval.max(1),
+                sample_interval: params.sample_interval.max(1),
+                ..params
+            },
+            capacity,
+            interval_start: None,
+            samples: VecDeque::new(),
+            history: VecDeque::new()
+        }
+    }
+
+    /*
+    Sample the premium of book against index at the index's timestamp, at most once per sample_interval.
+    Returns the sample taken, if any
+    */
+    pub fn observe<M>(&mut self, index: &IndexPrice, book: &Orderbook<M>) -> Option<PremiumSample> {
+        let timestamp = index.timestamp;
+        self.roll(timestamp);
+        if self.samples.back().is_some_and(|sample| timestamp < sample.timestamp.saturating_add(self.params.sample_interval.max(1))) {
+            return None;
+        }
+        if !book.accepts_taker_orders() || !index.price.is_finite() || index.price <= 0.0 {
+            return None;
+        }
+        let impact_bid = book.impact_price(Side::Bid, self.params.impact_notional)?;
+        let impact_ask = book.impact_price(Side::Ask, self.params.impact_notional)?;
+        let sample = PremiumSample {
+            timestamp,
+            index: index.price,
+            impact_bid,
+            impact_ask,
+            premium: ((impact_bid - index.price).max(0.0) - (index.price - impact_ask).max(0.0)) / index.price
+        };
+        self.samples.push_back(sample);
+        Some(sample)
+    }
+
+    /*
+    Settle the current interval when now has moved past it
+    */
+    pub fn roll(&mut self, now: u64) {
+        let start = now - now % self.params.interval.max(1);
+        if self.interval_start.is_some_and(|interval_start| interval_start < start) {
+            if let Some(estimate) = self.predicted() {
+                self.history.push_back(estimate);
+                while self.history.len() > self.capacity {
+                    self.history.pop_front();
+                }
+            }
+            self.samples.clear();
+        }
+        if self.interval_start.is_none_or(|interval_start| interval_start < start) {
+            self.interval_start = Some(start);
+        }
+    }
+
+    /*
+    Predicted funding for the current interval. None before the first sample in it
+    */
+    pub fn predicted(&self) -> Option<FundingEstimate> {
+        let interval_start = self.interval_start?;
+        if self.samples.is_empty() {
+            return None;
+        }
+        let (weighted, weights) = self.samples.iter().enumerate().fold((0.0, 0.0), |(weighted, weights), (position, sample)| {
+            let weight = match self.params.average {
+                PremiumAverage::Mean => 1.0,
+                PremiumAverage::Linear => (position + 1) as f64
+            };
+            (weighted + sample.premium * weight, weights + weight)
+        });
+        let average_premium = weighted / weights;
+        Some(FundingEstimate {
+            interval_start,
+            funding_time: interval_start.saturating_add(self.params.interval.max(1)),
+            samples: self.samples.len(),
+            average_premium,
+            rate: self.params.rate(average_premium)
+        })
+    }
+
+    pub fn samples(&self) -> &VecDeque<PremiumSample> {
+        &self.samples
+    }
+
+    /*
+    Settled intervals, oldest first
+    */
+    pub fn history(&self) -> &VecDeque<FundingEstimate> {
+        &self.history
+    }
+}
+
+/*
+value limited to +/-limit for a non-negative limit; f64::clamp panics on a NaN one, taken here as no limit
+*/
+fn bound(value: f64, limit: f64) -> f64 {
+    match limit.is_nan() {
+        true => value,
+        false => value.clamp(-limit, limit)
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Unscaled average price of filling notional (unscaled quote) against the book: selling into the bids
+    for Side::Bid, buying from the asks for Side::Ask. None for a non-positive notional or insufficient depth
+    */
+    pub fn impact_price(&self, side: Side, notional: f64) -> Option<f64> {
+        if !(notional.is_finite() && notional > 0.0) {
+            return None;
+        }
+        let levels: Box<dyn Iterator<Item = (&i64, &u64)>> = match side {
+            Side::Bid => Box::new(self.bids.iter().rev()),
+            Side::Ask => Box::new(self.asks.iter())
+        };
+        let mut remaining = notional;
+        let mut quantity = 0.0;
+        for (price, level_quantity) in levels {
+            let (price, level_quantity) = (self.unscale_price(*price), self.unscale_qty(*level_quantity));
+            if price <= 0.0 {
+                continue;
+            }
+            let taken = level_quantity.min(remaining / price);
+            quantity += taken;
+            remaining -= taken * price;
+            if remaining <= notional * 1e-12 {
+                return Some(notional / quantity);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_bounds_by_magnitude() {
+        let params = FundingParams::binance(1000.0, 0.0075);
+        assert!((params.rate(0.0001) - 0.0001).abs() < 1e-12);
+        assert!((params.rate(0.002) - 0.0015).abs() < 1e-12);
+        assert_eq!(params.rate(0.02), 0.0075);
+        let negative = FundingParams { clamp: -0.0005, cap: -0.0075, ..params };
+        assert_eq!(negative.rate(0.002), params.rate(0.002));
+        let unbounded = FundingParams { clamp: f64::NAN, cap: f64::NAN, ..params };
+        assert!((unbounded.rate(0.02) - 0.0001).abs() < 1e-12);
+        assert!(params.rate(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn zero_intervals_and_late_timestamps_do_not_panic() {
+        let mut predictor = FundingPredictor::new(FundingParams::bitmex(1000.0, 0.0075), 4);
+        predictor.params.interval = 0;
+        predictor.params.sample_interval = 0;
+        let mut book = Orderbook::new(Some(2), Some(2));
+        book.process(vec![(99.0, 100.0)], vec![(101.0, 100.0)], true);
+        for timestamp in [u64::MAX - 1, u64::MAX] {
+            let index = IndexPrice { timestamp, price: 100.0, venues: vec![1], excluded: Vec::new() };
+            assert!(predictor.observe(&index, &book).is_some());
+        }
+        assert_eq!(predictor.predicted().map(|estimate| estimate.funding_time), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,154 @@
+/*
+Purpose: Currency conversion of books quoted in different currencies into one reference currency
+*/
+
+use std::collections::HashMap;
+
+use crate::Orderbook;
+
+/*
+Units of the reference currency per unit of currency, and the time (ms) it was last set
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FxRate {
+    pub rate: f64,
+    pub updated: u64
+}
+
+/*
+A level converted to the reference currency. Prices and quantities are unscaled; source is the index of
+the book in the slice passed to consolidate. Stale is set when the book is not Live or its rate is stale.
+Synthetic levels are model quotes added with ConvertedView::insert_synthetic rather than displayed liquidity
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedLevel {
+    pub price: f64,
+    pub quantity: f64,
+    pub source: usize,
+    pub stale: bool,
+    pub synthetic: bool
+}
+
+/*
+Bids best (highest) first and asks best (lowest) first across every converted book
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedView {
+    pub currency: String,
+    pub bids: Vec<ConvertedLevel>,
+    pub asks: Vec<ConvertedLevel>,
+    pub stale: bool
+}
+
+/*
+Holds conversion rates into the reference currency. Rates older than max_age (ms) count as stale.
+The reference currency itself always converts at 1 and is never stale
+*/
+pub struct CurrencyConverter {
+    pub reference: String,
+    pub rates: HashMap<String, FxRate>,
+    pub max_age: Option<u64>
+}
+
+impl CurrencyConverter {
+    pub fn new(reference: &str, max_age: Option<u64>) -> CurrencyConverter {
+        CurrencyConverter {
+            reference: reference.to_string(),
+            rates: HashMap::new(),
+            max_age
+        }
+    }
+
+    /*
+    Returns false, leaving the previous rate, when rate is non-finite or not positive
+    */
+    pub fn set_rate(&mut self, currency: &str, rate: f64, timestamp: u64) -> bool {
+        if !(rate.is_finite() && rate > 0.0) {
+            return false;
+        }
+        self.rates.insert(currency.to_string(), FxRate { rate, updated: timestamp });
+        true
+    }
+
+    /*
+    Take the rate from the mid of an FX book quoting currency in the reference currency (e.g. EURUSD for EUR into USD).
+    Returns false, leaving the previous rate, when the book has no two-sided mid, is not Live or its
+    mid is not a usable rate
+    */
+    pub fn set_rate_from_book<M>(&mut self, currency: &str, book: &Orderbook<M>) -> bool {
+        let mid_price = match book.summary(Some(1)).mid_price {
+            Some(mid_price) if book.is_live() => mid_price / book.price_factor,
+            _ => return false
+        };
+        self.set_rate(currency, mid_price, book.timestamp)
+    }
+
+    /*
+    Rate into the reference currency and whether it is stale at now. None if no rate is registered
+    */
+    pub fn rate(&self, currency: &str, now: u64) -> Option<(f64, bool)> {
+        if currency == self.reference {
+            return Some((1.0, false));
+        }
+        let rate = self.rates.get(currency)?;
+        let stale = match self.max_age {
+            Some(max_age) => now.saturating_sub(rate.updated) > max_age,
+            None => false
+        };
+        Some((rate.rate, stale))
+    }
+
+    pub fn convert(&self, currency: &str, price: f64, now: u64) -> Option<f64> {
+        self.rate(currency, now).map(|(rate, _)| price * rate)
+    }
+
+    /*
+    Convert the best depth levels per side of each (currency, book) and merge them into one view.
+    Books without a registered rate are skipped. The view is stale if any contributing level is
+    */
+    pub fn consolidate<M>(&self, books: &[(&str, &Orderbook<M>)], depth: usize, now: u64) -> ConvertedView {
+        let mut view = ConvertedView {
+            currency: self.reference.clone(),
+            bids: Vec::new(),
+            asks: Vec::new(),
+            stale: false
+        };
+        for (source, (currency, book)) in books.iter().enumerate() {
+            let (rate, rate_stale) = match self.rate(currency, now) {
+                Some(rate) => rate,
+                None => continue
+            };
+            let stale = rate_stale || !book.is_live();
+            let convert = |(price, quantity): (&i64, &u64)| ConvertedLevel {
+                price: book.unscale_price(*price) * rate,
+                quantity: book.unscale_qty(*quantity),
+                source,
+                stale,
+                synthetic: false
+            };
+            view.bids.extend(book.bids.iter().rev().take(depth).map(convert));
+            view.asks.extend(book.asks.iter().take(depth).map(convert));
+            view.stale |= stale;
+        }
+        view.bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+        view.asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+        view
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unusable_rates_keep_the_previous_one() {
+        let mut converter = CurrencyConverter::new("USD", None);
+        assert!(converter.set_rate("EUR", 1.08, 1));
+        for rate in [f64::NAN, f64::INFINITY, 0.0, -1.08] {
+            assert!(!converter.set_rate("EUR", rate, 2));
+        }
+        assert_eq!(converter.rate("EUR", 2), Some((1.08, false)));
+        assert!(!converter.set_rate("JPY", 0.0, 2));
+        assert_eq!(converter.convert("JPY", 100.0, 2), None);
+    }
+}
@@ -0,0 +1,65 @@
+/*
+Purpose: Cumulative depth resampled onto fixed price grids around mid
+*/
+
+use crate::Orderbook;
+
+/*
+Entry k of each side is the unscaled quantity within (k + 1) * step_bps of mid
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthGrid {
+    pub step_bps: f64,
+    pub bids: Vec<f64>,
+    pub asks: Vec<f64>
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Project cumulative depth onto steps grid points spaced step_bps apart. Distances are taken relative to |mid|.
+    None without a two-sided book or when mid is zero
+    */
+    pub fn depth_grid(&self, step_bps: f64, steps: usize) -> Option<DepthGrid> {
+        let mid_price = self.summary(Some(1)).mid_price?;
+        let reference = mid_price.abs();
+        if reference == 0.0 {
+            return None;
+        }
+        let bids = cumulative_grid(
+            self.bids.iter().rev().map(|(price, quantity)| ((mid_price - *price as f64) / reference * 10_000.0, *quantity)),
+            step_bps,
+            steps,
+            self.quantity_factor
+        );
+        let asks = cumulative_grid(
+            self.asks.iter().map(|(price, quantity)| ((*price as f64 - mid_price) / reference * 10_000.0, *quantity)),
+            step_bps,
+            steps,
+            self.quantity_factor
+        );
+        Some(DepthGrid {
+            step_bps,
+            bids,
+            asks
+        })
+    }
+}
+
+/*
+Levels must arrive best first as (distance from mid in bps, scaled quantity)
+*/
+fn cumulative_grid<I: Iterator<Item = (f64, u64)>>(levels: I, step_bps: f64, steps: usize, quantity_factor: f64) -> Vec<f64> {
+    let mut buckets: Vec<u64> = vec![0; steps];
+    for (offset_bps, quantity) in levels {
+        let index = ((offset_bps.max(0.0) / step_bps).ceil() as usize).saturating_sub(1);
+        if index >= steps {
+            break;
+        }
+        buckets[index] = buckets[index].saturating_add(quantity);
+    }
+    let mut total_quantity: u64 = 0;
+    buckets.iter().map(|quantity| {
+        total_quantity = total_quantity.saturating_add(*quantity);
+        (total_quantity as f64) / quantity_factor
+    }).collect()
+}
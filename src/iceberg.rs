@@ -0,0 +1,146 @@
+/*
+Purpose: Heuristic detection of iceberg orders from executions followed by same-price refills
+*/
+
+use std::collections::HashMap;
+
+use crate::{BookEvent, Projection, Side};
+
+/*
+Probable hidden order resting at price (scaled) on side. hidden_quantity is the scaled quantity seen
+to reappear after executions; confidence is 1 - 0.5^refills
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IcebergEstimate {
+    pub side: Side,
+    pub price: i64,
+    pub refills: u32,
+    pub hidden_quantity: u64,
+    pub last_refill: u64,
+    pub confidence: f64
+}
+
+/*
+Executed quantity at a level not yet matched by a book update
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Execution {
+    quantity: u64,
+    timestamp: u64
+}
+
+/*
+Projection correlating trades with level updates. After a trade at a resting level, an update within
+refill_window ms that leaves more displayed quantity than the previous quantity minus the traded amount
+(including a re-add after the level emptied) counts as a refill of the difference. Trades must be
+recorded before the book update they caused; a level cancelled without a pending execution loses its estimate.
+A rescale of the book's decimals rescales pending executions and estimates
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcebergDetector {
+    pub refill_window: u64,
+    executions: HashMap<(Side, i64), Execution>,
+    estimates: HashMap<(Side, i64), IcebergEstimate>
+}
+
+impl IcebergDetector {
+    pub fn new(refill_window: u64) -> IcebergDetector {
+        IcebergDetector {
+            refill_window,
+            executions: HashMap::new(),
+            estimates: HashMap::new()
+        }
+    }
+
+    /*
+    Record a trade by aggressor side (Side::Bid for buyer-initiated) at a scaled price and quantity.
+    Use Orderbook::projection_mut to reach the detector registered on the book
+    */
+    pub fn record_trade(&mut self, aggressor: Side, price: i64, quantity: u64, timestamp: u64) {
+        let resting = match aggressor {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid
+        };
+        let execution = self.executions.entry((resting, price)).or_insert(Execution { quantity: 0, timestamp });
+        execution.quantity = execution.quantity.saturating_add(quantity);
+        execution.timestamp = timestamp;
+    }
+
+    pub fn estimate(&self, side: Side, price: i64) -> Option<IcebergEstimate> {
+        self.estimates.get(&(side, price)).copied()
+    }
+
+    /*
+    Current estimates, highest confidence first
+    */
+    pub fn estimates(&self) -> Vec<IcebergEstimate> {
+        let mut estimates: Vec<IcebergEstimate> = self.estimates.values().copied().collect();
+        estimates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then(b.hidden_quantity.cmp(&a.hidden_quantity)));
+        estimates
+    }
+
+    /*
+    Pending execution at the level if it is recent enough to explain an update at timestamp
+    */
+    fn take_execution(&mut self, key: (Side, i64), timestamp: u64) -> Option<Execution> {
+        let execution = self.executions.remove(&key)?;
+        match timestamp.saturating_sub(execution.timestamp) <= self.refill_window {
+            true => Some(execution),
+            false => None
+        }
+    }
+
+    fn rescale(&mut self, price_multiplier: i64, quantity_multiplier: u64) {
+        let rekey = |(side, price): (Side, i64)| (side, price.saturating_mul(price_multiplier));
+        self.executions = self.executions.drain().map(|(key, execution)| (rekey(key), Execution {
+            quantity: execution.quantity.saturating_mul(quantity_multiplier),
+            ..execution
+        })).collect();
+        self.estimates = self.estimates.drain().map(|(key, estimate)| (rekey(key), IcebergEstimate {
+            price: estimate.price.saturating_mul(price_multiplier),
+            hidden_quantity: estimate.hidden_quantity.saturating_mul(quantity_multiplier),
+            ..estimate
+        })).collect();
+    }
+
+    fn refill(&mut self, side: Side, price: i64, quantity: u64, timestamp: u64) {
+        let estimate = self.estimates.entry((side, price)).or_insert(IcebergEstimate {
+            side,
+            price,
+            refills: 0,
+            hidden_quantity: 0,
+            last_refill: timestamp,
+            confidence: 0.0
+        });
+        estimate.refills = estimate.refills.saturating_add(1);
+        estimate.hidden_quantity = estimate.hidden_quantity.saturating_add(quantity);
+        estimate.last_refill = timestamp;
+        estimate.confidence = 1.0 - 0.5f64.powi(estimate.refills.min(64) as i32);
+    }
+}
+
+impl Projection for IcebergDetector {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, previous } => {
+                if let Some(execution) = self.take_execution((side, price), timestamp) {
+                    let expected = previous.unwrap_or(0).saturating_sub(execution.quantity);
+                    if quantity > expected {
+                        self.refill(side, price, quantity - expected, timestamp);
+                    }
+                }
+            },
+            BookEvent::LevelRemoved { side, price, .. } => {
+                if !self.executions.contains_key(&(side, price)) {
+                    self.estimates.remove(&(side, price));
+                }
+            },
+            BookEvent::Cleared => {
+                self.executions.clear();
+                self.estimates.clear();
+            },
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => self.rescale(price_multiplier, quantity_multiplier)
+        }
+    }
+}
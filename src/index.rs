@@ -0,0 +1,269 @@
+/*
+Purpose: Index and mark prices from constituent venue books with staleness and outlier exclusion
+*/
+
+use std::collections::BTreeMap;
+
+use crate::Orderbook;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexMethod {
+    WeightedMedian,
+    WeightedMean
+}
+
+/*
+Why a constituent was left out. NotTrading: the venue's book is not Live in continuous trading
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exclusion {
+    NoPrice,
+    Stale,
+    NotTrading,
+    Outlier
+}
+
+/*
+Index value (unscaled) at timestamp with the venues that contributed and those excluded
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexPrice {
+    pub timestamp: u64,
+    pub price: f64,
+    pub venues: Vec<u32>,
+    pub excluded: Vec<(u32, Exclusion)>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Constituent {
+    weight: f64,
+    price: Option<f64>,
+    updated: u64,
+    trading: bool
+}
+
+/*
+Index over venue mids. Constituents not updated within max_age ms are stale; with max_deviation_bps
+set, those further than that from the weighted median of the rest are outliers. The index is the
+weighted median or mean of what remains, None with fewer than min_constituents left. Venue books may
+use different decimals
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexCalculator {
+    pub method: IndexMethod,
+    pub max_age: u64,
+    pub max_deviation_bps: Option<f64>,
+    pub min_constituents: usize,
+    constituents: BTreeMap<u32, Constituent>
+}
+
+impl IndexCalculator {
+    pub fn new(method: IndexMethod, max_age: u64) -> IndexCalculator {
+        IndexCalculator {
+            method,
+            max_age,
+            max_deviation_bps: None,
+            min_constituents: 1,
+            constituents: BTreeMap::new()
+        }
+    }
+
+    pub fn with_outlier_rejection(mut self, max_deviation_bps: f64) -> IndexCalculator {
+        self.max_deviation_bps = Some(max_deviation_bps);
+        self
+    }
+
+    pub fn with_min_constituents(mut self, min_constituents: usize) -> IndexCalculator {
+        self.min_constituents = min_constituents.max(1);
+        self
+    }
+
+    /*
+    Add or reweight a venue. Non-positive or non-finite weights keep the venue out of the index
+    */
+    pub fn set_weight(&mut self, venue: u32, weight: f64) {
+        self.constituents.entry(venue)
+            .and_modify(|constituent| constituent.weight = weight)
+            .or_insert(Constituent { weight, price: None, updated: 0, trading: true });
+    }
+
+    pub fn remove_venue(&mut self, venue: u32) {
+        self.constituents.remove(&venue);
+    }
+
+    /*
+    Record a venue's mid and trading state from its book. Ignored for venues without a weight
+    */
+    pub fn update_venue<M>(&mut self, venue: u32, book: &Orderbook<M>, now: u64) {
+        let price = book.summary(Some(1)).mid_price.map(|mid_price| mid_price / book.price_factor);
+        if let Some(constituent) = self.constituents.get_mut(&venue) {
+            constituent.price = price;
+            constituent.updated = now;
+            constituent.trading = book.accepts_taker_orders();
+        }
+    }
+
+    /*
+    Record a venue's price from another source, e.g. a last trade or a REST ticker (unscaled)
+    */
+    pub fn update_price(&mut self, venue: u32, price: f64, now: u64) {
+        if let Some(constituent) = self.constituents.get_mut(&venue) {
+            constituent.price = Some(price).filter(|price| price.is_finite());
+            constituent.updated = now;
+            constituent.trading = true;
+        }
+    }
+
+    pub fn compute(&self, now: u64) -> Option<IndexPrice> {
+        let mut excluded = Vec::new();
+        let mut prices: Vec<(u32, f64, f64)> = Vec::new();
+        for (venue, constituent) in self.constituents.iter().filter(|(_, constituent)| constituent.weight.is_finite() && constituent.weight > 0.0) {
+            let exclusion = match constituent.price {
+                None => Some(Exclusion::NoPrice),
+                Some(_) if now.saturating_sub(constituent.updated) > self.max_age => Some(Exclusion::Stale),
+                Some(_) if !constituent.trading => Some(Exclusion::NotTrading),
+                Some(price) => {
+                    prices.push((*venue, price, constituent.weight));
+                    None
+                }
+            };
+            if let Some(exclusion) = exclusion {
+                excluded.push((*venue, exclusion));
+            }
+        }
+        if let Some(max_deviation_bps) = self.max_deviation_bps {
+            let median = weighted_median(&prices)?;
+            if median > 0.0 {
+                prices.retain(|(venue, price, _)| match ((price - median) / median * 10_000.0).abs() <= max_deviation_bps {
+                    true => true,
+                    false => {
+                        excluded.push((*venue, Exclusion::Outlier));
+                        false
+                    }
+                });
+            }
+        }
+        if prices.len() < self.min_constituents {
+            return None;
+        }
+        let price = match self.method {
+            IndexMethod::WeightedMedian => weighted_median(&prices)?,
+            IndexMethod::WeightedMean => {
+                let weight: f64 = prices.iter().map(|(_, _, weight)| weight).sum();
+                prices.iter().map(|(_, price, weight)| price * weight).sum::<f64>() / weight
+            }
+        };
+        excluded.sort_by_key(|(venue, _)| *venue);
+        Some(IndexPrice {
+            timestamp: now,
+            price,
+            venues: prices.iter().map(|(venue, _, _)| *venue).collect(),
+            excluded
+        })
+    }
+}
+
+/*
+Mark price for a derivative: the index plus a smoothed basis (contract mid minus index) decaying with
+half_life ms, clamped to max_deviation_bps of the index so a thin contract book cannot drag it far
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkPrice {
+    pub half_life: u64,
+    pub max_deviation_bps: f64,
+    basis: Option<(f64, u64)>
+}
+
+impl MarkPrice {
+    pub fn new(half_life: u64, max_deviation_bps: f64) -> MarkPrice {
+        MarkPrice {
+            half_life,
+            max_deviation_bps,
+            basis: None
+        }
+    }
+
+    /*
+    Update with the latest index and the contract's book. Without a contract mid the basis carries over
+    */
+    pub fn observe<M>(&mut self, index: &IndexPrice, contract: &Orderbook<M>) -> f64 {
+        let now = index.timestamp;
+        if let Some(mid_price) = contract.summary(Some(1)).mid_price {
+            let observed = mid_price / contract.price_factor - index.price;
+            let basis = match self.basis {
+                Some((basis, updated)) if self.half_life > 0 => {
+                    let decay = 0.5f64.powf(now.saturating_sub(updated) as f64 / self.half_life as f64);
+                    basis * decay + observed * (1.0 - decay)
+                },
+                _ => observed
+            };
+            self.basis = Some((basis, now));
+        }
+        self.mark(index.price)
+    }
+
+    pub fn basis(&self) -> Option<f64> {
+        self.basis.map(|(basis, _)| basis)
+    }
+
+    /*
+    Mark at index_price with the current basis. A band that is not finite, from a NaN index_price or
+    max_deviation_bps, leaves index_price unchanged
+    */
+    pub fn mark(&self, index_price: f64) -> f64 {
+        let band = (index_price * self.max_deviation_bps / 10_000.0).abs();
+        match band.is_finite() {
+            true => index_price + self.basis().unwrap_or(0.0).max(-band).min(band),
+            false => index_price
+        }
+    }
+}
+
+/*
+Lower weighted median of (venue, price, weight), averaging the two middle prices when the weight splits
+exactly in half
+*/
+fn weighted_median(prices: &[(u32, f64, f64)]) -> Option<f64> {
+    let mut sorted: Vec<(f64, f64)> = prices.iter().map(|(_, price, weight)| (*price, *weight)).collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let half = sorted.iter().map(|(_, weight)| weight).sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+    for (index, (price, weight)) in sorted.iter().enumerate() {
+        cumulative += weight;
+        if cumulative > half {
+            return Some(*price);
+        }
+        if cumulative == half {
+            return Some(sorted.get(index + 1).map_or(*price, |(next, _)| (price + next) / 2.0));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_tolerates_non_finite_inputs() {
+        let mark = MarkPrice::new(1000, 50.0);
+        assert!(mark.mark(f64::NAN).is_nan());
+        assert_eq!(mark.mark(f64::INFINITY), f64::INFINITY);
+        assert_eq!(mark.mark(100.0), 100.0);
+        for max_deviation_bps in [f64::NAN, f64::INFINITY, -50.0] {
+            let mark = MarkPrice { basis: Some((2.0, 0)), ..MarkPrice::new(1000, max_deviation_bps) };
+            let expected = match max_deviation_bps.is_finite() {
+                true => 100.5,
+                false => 100.0
+            };
+            assert_eq!(mark.mark(100.0), expected);
+        }
+    }
+
+    #[test]
+    fn weighted_median_splits_ties() {
+        assert_eq!(weighted_median(&[(1, 10.0, 1.0), (2, 12.0, 1.0)]), Some(11.0));
+        assert_eq!(weighted_median(&[(1, 10.0, 1.0), (2, 12.0, 3.0)]), Some(12.0));
+        assert_eq!(weighted_median(&[]), None);
+    }
+}
@@ -0,0 +1,204 @@
+/*
+Purpose: Instrument reference data and loaders for exchange metadata
+*/
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{BookConfig, MAX_DECIMALS};
+
+/*
+Static contract details. Tick and lot sizes are unscaled increments of price and quantity.
+Inverse contracts are quoted in the quote currency but settled in the base currency
+*/
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Instrument {
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    pub tick_size: f64,
+    pub lot_size: f64,
+    pub contract_multiplier: f64,
+    pub inverse: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstrumentError {
+    Csv { line: usize, message: String },
+    Json(String),
+    MissingField { symbol: String, field: &'static str }
+}
+
+impl fmt::Display for InstrumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstrumentError::Csv { line, message } => write!(f, "csv line {}: {}", line, message),
+            InstrumentError::Json(message) => write!(f, "json: {}", message),
+            InstrumentError::MissingField { symbol, field } => write!(f, "{}: missing {}", symbol, field)
+        }
+    }
+}
+
+impl std::error::Error for InstrumentError {}
+
+impl Instrument {
+    /*
+    Book configuration with decimals just fine enough to represent the tick and lot sizes
+    */
+    pub fn book_config(&self) -> BookConfig {
+        BookConfig {
+            price_decimals: increment_decimals(self.tick_size),
+            quantity_decimals: increment_decimals(self.lot_size),
+            ..BookConfig::default()
+        }
+    }
+
+    /*
+    Local CSV with header symbol,base,quote,tick_size,lot_size,contract_multiplier,inverse.
+    Blank lines are skipped; inverse accepts true/false
+    */
+    pub fn from_csv(input: &str) -> Result<Vec<Instrument>, InstrumentError> {
+        let mut instruments: Vec<Instrument> = Vec::new();
+        for (index, line) in input.lines().enumerate().skip(1) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let csv_error = |message: String| InstrumentError::Csv { line: index + 1, message };
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() != 7 {
+                return Err(csv_error(format!("expected 7 fields, found {}", fields.len())));
+            }
+            let number = |field: &str| field.parse::<f64>().map_err(|_| csv_error(format!("invalid number {}", field)));
+            instruments.push(Instrument {
+                symbol: fields[0].to_string(),
+                base: fields[1].to_string(),
+                quote: fields[2].to_string(),
+                tick_size: number(fields[3])?,
+                lot_size: number(fields[4])?,
+                contract_multiplier: number(fields[5])?,
+                inverse: fields[6].parse::<bool>().map_err(|_| csv_error(format!("invalid flag {}", fields[6])))?
+            });
+        }
+        Ok(instruments)
+    }
+
+    /*
+    Local JSON: an array of instruments with the struct's field names
+    */
+    #[cfg(feature = "serde")]
+    pub fn from_json(input: &str) -> Result<Vec<Instrument>, InstrumentError> {
+        serde_json::from_str(input).map_err(|error| InstrumentError::Json(error.to_string()))
+    }
+
+    /*
+    Binance exchangeInfo response. Tick and lot sizes come from the PRICE_FILTER and LOT_SIZE filters
+    */
+    #[cfg(feature = "serde")]
+    pub fn from_binance_exchange_info(input: &str) -> Result<Vec<Instrument>, InstrumentError> {
+        let root: serde_json::Value = serde_json::from_str(input).map_err(|error| InstrumentError::Json(error.to_string()))?;
+        let symbols = root["symbols"].as_array().ok_or_else(|| InstrumentError::Json(String::from("missing symbols array")))?;
+        let mut instruments: Vec<Instrument> = Vec::new();
+        for entry in symbols.iter() {
+            let symbol = json_str(entry, "symbol").unwrap_or_default();
+            let filter = |filter_type: &str, field: &'static str| {
+                entry["filters"].as_array()
+                    .and_then(|filters| filters.iter().find(|filter| filter["filterType"] == filter_type))
+                    .and_then(|filter| json_number(filter, field))
+                    .ok_or_else(|| InstrumentError::MissingField { symbol: symbol.clone(), field })
+            };
+            instruments.push(Instrument {
+                base: json_str(entry, "baseAsset").ok_or_else(|| InstrumentError::MissingField { symbol: symbol.clone(), field: "baseAsset" })?,
+                quote: json_str(entry, "quoteAsset").ok_or_else(|| InstrumentError::MissingField { symbol: symbol.clone(), field: "quoteAsset" })?,
+                tick_size: filter("PRICE_FILTER", "tickSize")?,
+                lot_size: filter("LOT_SIZE", "stepSize")?,
+                contract_multiplier: 1.0,
+                inverse: false,
+                symbol
+            });
+        }
+        Ok(instruments)
+    }
+
+    /*
+    Coinbase products response: an array with quote_increment and base_increment per product
+    */
+    #[cfg(feature = "serde")]
+    pub fn from_coinbase_products(input: &str) -> Result<Vec<Instrument>, InstrumentError> {
+        let root: serde_json::Value = serde_json::from_str(input).map_err(|error| InstrumentError::Json(error.to_string()))?;
+        let products = root.as_array().ok_or_else(|| InstrumentError::Json(String::from("expected an array of products")))?;
+        let mut instruments: Vec<Instrument> = Vec::new();
+        for entry in products.iter() {
+            let symbol = json_str(entry, "id").unwrap_or_default();
+            let missing = |field: &'static str| InstrumentError::MissingField { symbol: symbol.clone(), field };
+            instruments.push(Instrument {
+                base: json_str(entry, "base_currency").ok_or_else(|| missing("base_currency"))?,
+                quote: json_str(entry, "quote_currency").ok_or_else(|| missing("quote_currency"))?,
+                tick_size: json_number(entry, "quote_increment").ok_or_else(|| missing("quote_increment"))?,
+                lot_size: json_number(entry, "base_increment").ok_or_else(|| missing("base_increment"))?,
+                contract_multiplier: 1.0,
+                inverse: false,
+                symbol
+            });
+        }
+        Ok(instruments)
+    }
+}
+
+/*
+Fewest decimals that represent increment exactly, 0 for whole increments such as 1 or 5, or None for a
+non-finite or non-positive increment. Increments finer than MAX_DECIMALS are capped there
+*/
+pub fn increment_decimals(increment: f64) -> Option<u8> {
+    if !(increment.is_finite() && increment > 0.0) {
+        return None;
+    }
+    (0..=MAX_DECIMALS).find(|decimals| {
+        let scaled = increment * 10f64.powi(*decimals as i32);
+        // An increment that rounds to 0 at this precision is not represented at all
+        scaled.round() >= 1.0 && (scaled - scaled.round()).abs() < 1e-9 * scaled.max(1.0)
+    }).or(Some(MAX_DECIMALS))
+}
+
+#[cfg(feature = "serde")]
+fn json_str(value: &serde_json::Value, field: &str) -> Option<String> {
+    value[field].as_str().map(String::from)
+}
+
+/*
+Exchanges send increments as strings to preserve precision; accept either form
+*/
+#[cfg(feature = "serde")]
+fn json_number(value: &serde_json::Value, field: &str) -> Option<f64> {
+    match &value[field] {
+        serde_json::Value::String(text) => text.parse::<f64>().ok(),
+        other => other.as_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_increments_need_no_decimals() {
+        let cases = [(0.01, Some(2)), (0.5, Some(1)), (1.0, Some(0)), (5.0, Some(0)), (2.5, Some(1)), (1e-12, Some(MAX_DECIMALS)), (0.0, None), (f64::NAN, None)];
+        for (increment, expected) in cases {
+            assert_eq!(increment_decimals(increment), expected, "increment {}", increment);
+        }
+        let instrument = Instrument {
+            symbol: String::from("NK"),
+            base: String::from("NK"),
+            quote: String::from("JPY"),
+            tick_size: 5.0,
+            lot_size: 1.0,
+            contract_multiplier: 1.0,
+            inverse: false
+        };
+        let config = instrument.book_config();
+        assert_eq!((config.price_decimals, config.quantity_decimals), (Some(0), Some(0)));
+    }
+}
@@ -5,118 +5,627 @@ Purpose: L2 orderbook
 
 use std::collections::BTreeMap;
 
+use crate::banding::PriceBand;
+use crate::config::ConfigError;
+use crate::events::{replay, BookEvent, Projection};
+use crate::lifecycle::{BookState, StateListener, TradingStatus};
+use crate::rate::UpdateRate;
+use crate::precision::AdaptivePrecision;
+use crate::time::nanos_to_millis;
+use crate::window::PriceWindow;
+
 /*
-Bids and asks trees map scaled price to scaled quantity.
-Methods iterate bids in descending order and asks in ascending order of price keys
+Bids and asks trees map scaled price to scaled quantity. Prices are signed so spread and
+commodity instruments can trade at or below zero.
+Methods iterate bids in descending order and asks in ascending order of price keys.
+Order count trees hold the number of orders per level for feeds that publish it.
+When level_ttl is set, update time trees hold the book timestamp at which each level was last updated.
+Meta trees hold an optional user payload per level, kept until the level is removed or a snapshot resets the book.
+Levels removed by prune_policy are tallied in pruned_levels and pruned_quantity (scaled).
+With price_window set, the trees hold only levels near mid and the window parks the rest.
+Every change to the bid and ask trees is emitted as a BookEvent to the registered projections.
+With require_live set, simulations return None unless the book is Live in continuous trading.
+With adaptive_precision set, price_factor and quantity_factor grow when finer values arrive
 */
-pub struct Orderbook {
-    pub bids: BTreeMap<u64, u64>,
-    pub asks: BTreeMap<u64, u64>,
+pub struct Orderbook<M = ()> {
+    pub bids: BTreeMap<i64, u64>,
+    pub asks: BTreeMap<i64, u64>,
+    pub bid_order_counts: BTreeMap<i64, u32>,
+    pub ask_order_counts: BTreeMap<i64, u32>,
+    pub bid_update_times: BTreeMap<i64, u64>,
+    pub ask_update_times: BTreeMap<i64, u64>,
+    pub bid_meta: BTreeMap<i64, M>,
+    pub ask_meta: BTreeMap<i64, M>,
+    pub level_ttl: Option<u64>,
+    pub timestamp: u64,
+    pub prune_policy: Option<PrunePolicy>,
+    pub pruned_levels: u64,
+    pub pruned_quantity: u64,
+    pub stale_after: Option<u64>,
+    pub require_live: bool,
+    pub price_band: Option<PriceBand>,
+    pub price_window: Option<PriceWindow>,
+    pub update_rate: Option<UpdateRate>,
+    pub adaptive_precision: Option<AdaptivePrecision>,
+    pub(crate) state: BookState,
+    pub(crate) synced: bool,
+    pub(crate) state_listener: Option<StateListener>,
+    pub(crate) trading_status: TradingStatus,
+    pub(crate) projections: Vec<Box<dyn Projection>>,
     pub price_factor: f64,
     pub quantity_factor: f64
 }
 
-const MAX_DECIMALS: u8 = 8;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Bid,
+    Ask
+}
+
+/*
+Bounds on which levels the book retains after each update.
+MaxLevels keeps the best N levels per side, MaxDistance keeps levels within a fraction of mid (0.05 = 5%)
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrunePolicy {
+    MaxLevels(usize),
+    MaxDistance(f64)
+}
+
+/*
+Point-in-time statistics produced by Orderbook::summary.
+Prices are scaled like the getters; totals are unscaled quantities. Spread is negative for a crossed book.
+Totals, weighted prices and imbalance cover the levels included by the requested depth
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSummary {
+    pub best_bid: Option<(i64, u64)>,
+    pub best_ask: Option<(i64, u64)>,
+    pub spread: Option<i64>,
+    pub mid_price: Option<f64>,
+    pub microprice: Option<f64>,
+    pub imbalance: Option<f64>,
+    pub total_bid_quantity: f64,
+    pub total_ask_quantity: f64,
+    pub weighted_bid: Option<f64>,
+    pub weighted_ask: Option<f64>
+}
+
+/*
+Largest decimals setting a book accepts
+*/
+pub const MAX_DECIMALS: u8 = 8;
 const DEFAULT_DECIMALS: u8 = 6;
 
+/*
+Scaling factor for a decimals setting, shared by the book types. field names the setting in the error
+*/
+pub(crate) fn checked_decimal_factor(field: &'static str, decimals: Option<u8>) -> Result<f64, ConfigError> {
+    match decimals {
+        Some(decimals) if decimals > MAX_DECIMALS => Err(ConfigError::TooManyDecimals { field, decimals }),
+        Some(decimals) => Ok(f64::powi(10.0, decimals.into())),
+        None => Ok(f64::powi(10.0, DEFAULT_DECIMALS.into()))
+    }
+}
+
+/*
+Infallible constructors panic on invalid decimals; this is the crate's only panic path and has try_ alternatives
+*/
+#[allow(clippy::panic)]
+pub(crate) fn decimal_factor(decimals: Option<u8>) -> f64 {
+    match checked_decimal_factor("decimals", decimals) {
+        Ok(factor) => factor,
+        Err(_) => panic!("Too many decimals")
+    }
+}
+
+/*
+Round to the nearest scaled increment. Quantities saturate negative and NaN input to 0, prices saturate
+NaN to 0, and both saturate overflow to the integer bounds
+*/
+pub(crate) fn scale(value: f64, factor: f64) -> u64 {
+    (value * factor).round() as u64
+}
+
+pub(crate) fn scale_signed(value: f64, factor: f64) -> i64 {
+    (value * factor).round() as i64
+}
+
+/*
+Finite price and finite, non-negative quantity
+*/
+pub(crate) fn is_valid_level(level: &(f64, f64)) -> bool {
+    level.0.is_finite() && level.1.is_finite() && level.1 >= 0.0
+}
+
+pub(crate) fn unscale(value: u64, factor: f64) -> f64 {
+    (value as f64) / factor
+}
+
+pub(crate) fn unscale_signed(value: i64, factor: f64) -> f64 {
+    (value as f64) / factor
+}
+
+// Largest scaled magnitude whose conversion to and from f64 is exact
+const MAX_EXACT_SCALED: u64 = 1 << 53;
+
+pub(crate) fn checked_scale(value: f64, factor: f64) -> Option<u64> {
+    let scaled = (value * factor).round();
+    match scaled.is_finite() && scaled >= 0.0 && scaled <= MAX_EXACT_SCALED as f64 {
+        true => Some(scaled as u64),
+        false => None
+    }
+}
+
+pub(crate) fn checked_scale_signed(value: f64, factor: f64) -> Option<i64> {
+    let scaled = (value * factor).round();
+    match scaled.is_finite() && scaled.abs() <= MAX_EXACT_SCALED as f64 {
+        true => Some(scaled as i64),
+        false => None
+    }
+}
+
 impl Orderbook {
+    /*
+    Panics if either decimals setting exceeds MAX_DECIMALS; see try_new
+    */
     pub fn new(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> Orderbook {
+        Orderbook::with_meta(price_decimals, quantity_decimals)
+    }
+
+    pub fn try_new(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> Result<Orderbook, ConfigError> {
+        Orderbook::try_with_meta(price_decimals, quantity_decimals)
+    }
+
+    /*
+    Construct a book from an initial snapshot using bulk tree construction.
+    Bids and asks should be formatted as (price, quantity)
+    */
+    pub fn from_snapshot(price_decimals: Option<u8>, quantity_decimals: Option<u8>, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> Orderbook {
+        let mut book = Orderbook::new(price_decimals, quantity_decimals);
+        book.load_snapshot(bids, asks);
+        book
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Construct a book whose levels can carry a payload of type M
+    */
+    pub fn with_meta(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> Orderbook<M> {
+        let mut book = Orderbook::empty();
+        book.price_factor = decimal_factor(price_decimals);
+        book.quantity_factor = decimal_factor(quantity_decimals);
+        book
+    }
+
+    pub fn try_with_meta(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> Result<Orderbook<M>, ConfigError> {
+        let mut book = Orderbook::empty();
+        book.price_factor = checked_decimal_factor("price_decimals", price_decimals)?;
+        book.quantity_factor = checked_decimal_factor("quantity_decimals", quantity_decimals)?;
+        Ok(book)
+    }
+
+    fn empty() -> Orderbook<M> {
         Orderbook {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
-            price_factor: f64::powf(
-                10.0, 
-                (
-                    match price_decimals {
-                        Some(x) => {
-                            if x > MAX_DECIMALS {
-                                panic!("Too many decimals");
-                            }
-                            x
-                        },
-                        None => DEFAULT_DECIMALS
-                    }
-                ).into()
-            ),
-            quantity_factor: f64::powf(
-                10.0, 
-                (
-                    match quantity_decimals {
-                        Some(x) => {
-                            if x > MAX_DECIMALS {
-                                panic!("Too many decimals");
-                            }
-                            x
-                        },
-                        None => DEFAULT_DECIMALS
-                    }
-                ).into()
-            ),
+            bid_order_counts: BTreeMap::new(),
+            ask_order_counts: BTreeMap::new(),
+            bid_update_times: BTreeMap::new(),
+            ask_update_times: BTreeMap::new(),
+            bid_meta: BTreeMap::new(),
+            ask_meta: BTreeMap::new(),
+            level_ttl: None,
+            timestamp: 0,
+            prune_policy: None,
+            pruned_levels: 0,
+            pruned_quantity: 0,
+            stale_after: None,
+            require_live: false,
+            price_band: None,
+            price_window: None,
+            update_rate: None,
+            adaptive_precision: None,
+            state: BookState::Initializing,
+            synced: false,
+            state_listener: None,
+            trading_status: TradingStatus::Trading,
+            projections: Vec::new(),
+            price_factor: 1.0,
+            quantity_factor: 1.0
+        }
+    }
+
+    /*
+    Conversions between external values and the book's scaled representation.
+    Scaling rounds to the nearest increment, so a value with at most the configured decimals maps to its exact key
+    and unscaling that key returns the closest f64 to the value. Scaling an unscaled key returns the key for every
+    key with magnitude up to 2^51, and one within a single increment up to 2^53 (exact there too with 0 decimals),
+    since the division and the multiplication each round. The unchecked forms saturate on non-finite or oversized input (and negative
+    quantities), the checked forms return None for input outside the exact range
+    */
+    pub fn scale_price(&self, price: f64) -> i64 {
+        scale_signed(price, self.price_factor)
+    }
+
+    pub fn unscale_price(&self, price: i64) -> f64 {
+        unscale_signed(price, self.price_factor)
+    }
+
+    pub fn scale_qty(&self, quantity: f64) -> u64 {
+        scale(quantity, self.quantity_factor)
+    }
+
+    pub fn unscale_qty(&self, quantity: u64) -> f64 {
+        unscale(quantity, self.quantity_factor)
+    }
+
+    pub fn checked_scale_price(&self, price: f64) -> Option<i64> {
+        checked_scale_signed(price, self.price_factor)
+    }
+
+    pub fn checked_unscale_price(&self, price: i64) -> Option<f64> {
+        match price.unsigned_abs() <= MAX_EXACT_SCALED {
+            true => Some(unscale_signed(price, self.price_factor)),
+            false => None
+        }
+    }
+
+    pub fn checked_scale_qty(&self, quantity: f64) -> Option<u64> {
+        checked_scale(quantity, self.quantity_factor)
+    }
+
+    pub fn checked_unscale_qty(&self, quantity: u64) -> Option<f64> {
+        match quantity <= MAX_EXACT_SCALED {
+            true => Some(unscale(quantity, self.quantity_factor)),
+            false => None
         }
     }
 
     /*
     Process orderbook update. If is_snapshot, resets the bids and asks to empty.
-    Bids and asks should be formatted as (price, quantity)
+    Bids and asks should be formatted as (price, quantity); a quantity that scales to zero removes the level.
+    Levels with a non-finite price or a non-finite or negative quantity are skipped; try_process rejects them
     */
     pub fn process(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, is_snapshot: bool) {
+        self.adapt_precision(&bids, &asks);
         if is_snapshot {
-            self.bids.clear();
-            self.asks.clear();
+            self.clear_levels();
+        }
+        for bid in bids.iter().filter(|bid| is_valid_level(bid)) {
+            let (price, quantity) = (self.scale_price(bid.0), self.scale_qty(bid.1));
+            self.apply_scaled_level(Side::Bid, price, quantity);
+        }
+        for ask in asks.iter().filter(|ask| is_valid_level(ask)) {
+            let (price, quantity) = (self.scale_price(ask.0), self.scale_qty(ask.1));
+            self.apply_scaled_level(Side::Ask, price, quantity);
+        }
+        self.finish_update(is_snapshot);
+    }
+
+    /*
+    Empty both sides and their per-level state ahead of a snapshot's levels
+    */
+    pub(crate) fn clear_levels(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.bid_order_counts.clear();
+        self.ask_order_counts.clear();
+        self.bid_update_times.clear();
+        self.ask_update_times.clear();
+        self.bid_meta.clear();
+        self.ask_meta.clear();
+        self.reset_window();
+        self.emit(BookEvent::Cleared);
+    }
+
+    /*
+    Bookkeeping after an update's levels are applied: rate, window, pruning and state
+    */
+    pub(crate) fn finish_update(&mut self, is_snapshot: bool) {
+        self.record_update();
+        self.shift_window();
+        self.prune();
+        self.on_processed(is_snapshot);
+    }
+
+    /*
+    One level of an update in this book's scale: a zero quantity removes it, otherwise it is set and
+    restamped. The price window may absorb either
+    */
+    pub(crate) fn apply_scaled_level(&mut self, side: Side, price: i64, quantity: u64) {
+        match quantity {
+            _ if self.window_absorbs(side, price, quantity) => (),
+            0 => {
+                self.remove_level(side, price);
+            },
+            quantity => {
+                self.set_level(side, price, quantity);
+                let (order_counts, update_times) = match side {
+                    Side::Bid => (&mut self.bid_order_counts, &mut self.bid_update_times),
+                    Side::Ask => (&mut self.ask_order_counts, &mut self.ask_update_times)
+                };
+                order_counts.remove(&price);
+                if self.level_ttl.is_some() {
+                    update_times.insert(price, self.timestamp);
+                }
+            }
         }
+    }
+
+    /*
+    Replace the book with a snapshot. Equivalent to process with is_snapshot set, but builds each
+    tree in one pass from the sorted levels rather than inserting them one at a time
+    */
+    pub fn load_snapshot(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) {
+        self.adapt_precision(&bids, &asks);
+        let (price_factor, quantity_factor) = (self.price_factor, self.quantity_factor);
+        let scaled = |level: &(f64, f64)| (scale_signed(level.0, price_factor), scale(level.1, quantity_factor));
+        self.load_scaled_snapshot(
+            bids.iter().filter(|bid| is_valid_level(bid)).map(scaled),
+            asks.iter().filter(|ask| is_valid_level(ask)).map(scaled)
+        );
+    }
+
+    /*
+    load_snapshot from levels already in this book's scale, e.g. another book's keys, without the f64
+    round trip that loses precision beyond 2^51. Zero quantities are dropped
+    */
+    pub(crate) fn load_scaled_snapshot(&mut self, bids: impl IntoIterator<Item = (i64, u64)>, asks: impl IntoIterator<Item = (i64, u64)>) {
+        self.bids = bids.into_iter().filter(|(_, quantity)| *quantity > 0).collect();
+        self.asks = asks.into_iter().filter(|(_, quantity)| *quantity > 0).collect();
+        self.bid_order_counts.clear();
+        self.ask_order_counts.clear();
+        self.bid_meta.clear();
+        self.ask_meta.clear();
+        self.reset_window();
+        for projection in self.projections.iter_mut() {
+            replay(projection.as_mut(), &self.bids, &self.asks, self.timestamp);
+        }
+        self.set_level_ttl(self.level_ttl);
+        self.record_update();
+        self.shift_window();
+        self.prune();
+        self.on_processed(true);
+    }
+
+    /*
+    Process orderbook update stamped with timestamp (ms). Advances the book clock, so levels
+    updated by later untimed process calls inherit it, and prunes levels older than level_ttl
+    */
+    pub fn process_at(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, is_snapshot: bool, timestamp: u64) {
+        self.timestamp = timestamp;
+        self.process(bids, asks, is_snapshot);
+        self.expire(timestamp);
+    }
+
+    /*
+    process_at with a UTC nanosecond timestamp, e.g. from parse_timestamp. The book clock keeps ms
+    */
+    pub fn process_at_nanos(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, is_snapshot: bool, nanos: u64) {
+        self.process_at(bids, asks, is_snapshot, nanos_to_millis(nanos));
+    }
+
+    /*
+    Enable or disable level expiry. Levels present before enabling are stamped with the current book time
+    */
+    pub fn set_level_ttl(&mut self, level_ttl: Option<u64>) {
+        self.level_ttl = level_ttl;
+        self.bid_update_times.clear();
+        self.ask_update_times.clear();
+        if level_ttl.is_some() {
+            for price in self.bids.keys() {
+                self.bid_update_times.insert(*price, self.timestamp);
+            }
+            for price in self.asks.keys() {
+                self.ask_update_times.insert(*price, self.timestamp);
+            }
+        }
+    }
+
+    /*
+    Remove levels not updated within level_ttl of now (ms). Returns the number of levels removed
+    */
+    pub fn expire(&mut self, now: u64) -> usize {
+        let ttl = match self.level_ttl {
+            Some(ttl) => ttl,
+            None => return 0
+        };
+        let expired_bids: Vec<i64> = self.bid_update_times.iter()
+            .filter(|(_, time)| now.saturating_sub(**time) > ttl)
+            .map(|(price, _)| *price)
+            .collect();
+        for price in expired_bids.iter() {
+            self.remove_level(Side::Bid, *price);
+        }
+        let expired_asks: Vec<i64> = self.ask_update_times.iter()
+            .filter(|(_, time)| now.saturating_sub(**time) > ttl)
+            .map(|(price, _)| *price)
+            .collect();
+        for price in expired_asks.iter() {
+            self.remove_level(Side::Ask, *price);
+        }
+        expired_bids.len() + expired_asks.len()
+    }
+
+    /*
+    Remove levels outside prune_policy, counting the levels and liquidity dropped
+    */
+    pub fn prune(&mut self) {
+        let (bid_prices, ask_prices): (Vec<i64>, Vec<i64>) = match self.prune_policy {
+            Some(PrunePolicy::MaxLevels(levels)) => (
+                self.bids.keys().rev().skip(levels).copied().collect(),
+                self.asks.keys().skip(levels).copied().collect()
+            ),
+            Some(PrunePolicy::MaxDistance(fraction)) => {
+                let (best_bid, best_ask) = match (self.get_best_bid(), self.get_best_ask()) {
+                    (Some(bid), Some(ask)) => (bid.0, ask.0),
+                    _ => return
+                };
+                let mid_price = ((best_bid as f64) + (best_ask as f64)) / 2.0;
+                let lowest_bid = (mid_price - mid_price.abs() * fraction).ceil() as i64;
+                let highest_ask = (mid_price + mid_price.abs() * fraction).floor() as i64;
+                (
+                    self.bids.range(..lowest_bid).map(|(price, _)| *price).collect(),
+                    self.asks.range(highest_ask.saturating_add(1)..).map(|(price, _)| *price).collect()
+                )
+            },
+            None => return
+        };
+        for price in bid_prices.iter() {
+            if let Some(quantity) = self.remove_level(Side::Bid, *price) {
+                self.pruned_levels += 1;
+                self.pruned_quantity = self.pruned_quantity.saturating_add(quantity);
+            }
+        }
+        for price in ask_prices.iter() {
+            if let Some(quantity) = self.remove_level(Side::Ask, *price) {
+                self.pruned_levels += 1;
+                self.pruned_quantity = self.pruned_quantity.saturating_add(quantity);
+            }
+        }
+    }
+
+    /*
+    Insert or replace a level's quantity
+    */
+    pub(crate) fn set_level(&mut self, side: Side, price: i64, quantity: u64) {
+        let previous = match side {
+            Side::Bid => self.bids.insert(price, quantity),
+            Side::Ask => self.asks.insert(price, quantity)
+        };
+        self.emit(BookEvent::LevelSet { side, price, quantity, previous });
+    }
+
+    /*
+    Release spare capacity in the book's auxiliary buffers. The level trees are BTreeMaps, which
+    allocate per node and carry no spare capacity to reserve or release
+    */
+    pub fn shrink_to_fit(&mut self) {
+        self.projections.shrink_to_fit();
+        if let Some(update_rate) = self.update_rate.as_mut() {
+            update_rate.buckets.shrink_to_fit();
+        }
+    }
+
+    /*
+    Remove a level along with its order count, update time and metadata. Returns the removed quantity
+    */
+    pub(crate) fn remove_level(&mut self, side: Side, price: i64) -> Option<u64> {
+        let removed = match side {
+            Side::Bid => {
+                self.bid_order_counts.remove(&price);
+                self.bid_update_times.remove(&price);
+                self.bid_meta.remove(&price);
+                self.bids.remove(&price)
+            },
+            Side::Ask => {
+                self.ask_order_counts.remove(&price);
+                self.ask_update_times.remove(&price);
+                self.ask_meta.remove(&price);
+                self.asks.remove(&price)
+            }
+        };
+        if let Some(quantity) = removed {
+            self.emit(BookEvent::LevelRemoved { side, price, quantity });
+        }
+        removed
+    }
+
+
+    /*
+    Process orderbook update from a feed that publishes order counts.
+    Bids and asks should be formatted as (price, quantity, order_count)
+    */
+    pub fn process_with_order_counts(&mut self, bids: Vec<(f64, f64, u32)>, asks: Vec<(f64, f64, u32)>, is_snapshot: bool) {
+        self.process(
+            bids.iter().map(|bid| (bid.0, bid.1)).collect(),
+            asks.iter().map(|ask| (ask.0, ask.1)).collect(),
+            is_snapshot
+        );
         for bid in bids.iter() {
-            if bid.1 > 0.0 {
-                let scaled_price = (bid.0 * self.price_factor) as u64;
-                let scaled_quantity = (bid.1 * self.quantity_factor) as u64;
-                self.bids.insert(scaled_price, scaled_quantity);
+            let scaled_price = self.scale_price(bid.0);
+            if is_valid_level(&(bid.0, bid.1)) && bid.1 > 0.0 && self.bids.contains_key(&scaled_price) {
+                self.bid_order_counts.insert(scaled_price, bid.2);
             }
         }
         for ask in asks.iter() {
-            if ask.1 > 0.0 {
-                let scaled_price = (ask.0 * self.price_factor) as u64;
-                let scaled_quantity = (ask.1 * self.quantity_factor) as u64;
-                self.asks.insert(scaled_price, scaled_quantity);
+            let scaled_price = self.scale_price(ask.0);
+            if is_valid_level(&(ask.0, ask.1)) && ask.1 > 0.0 && self.asks.contains_key(&scaled_price) {
+                self.ask_order_counts.insert(scaled_price, ask.2);
             }
         }
     }
 
-    pub fn get_best_bid(&self) -> Option<(u64, u64)> {
-        match self.bids.iter().rev().next() {
-            Some((price, quantity)) => Some((*price, *quantity)),
-            None => None
+    /*
+    Attach a payload to an existing level at scaled price. Returns false if the level does not exist
+    */
+    pub fn set_bid_meta(&mut self, price: i64, meta: M) -> bool {
+        if !self.bids.contains_key(&price) {
+            return false;
         }
+        self.bid_meta.insert(price, meta);
+        true
     }
 
-    pub fn get_best_ask(&self) -> Option<(u64, u64)> {
-        match self.asks.iter().next() {
-            Some((price, quantity)) => Some((*price, *quantity)),
-            None => None
+    pub fn set_ask_meta(&mut self, price: i64, meta: M) -> bool {
+        if !self.asks.contains_key(&price) {
+            return false;
         }
+        self.ask_meta.insert(price, meta);
+        true
+    }
+
+    pub fn get_bid_meta(&self, price: i64) -> Option<&M> {
+        self.bid_meta.get(&price)
+    }
+
+    pub fn get_ask_meta(&self, price: i64) -> Option<&M> {
+        self.ask_meta.get(&price)
+    }
+
+    /*
+    Best depth bid levels as (price, quantity, order_count), best first.
+    order_count is None for levels last updated without a count
+    */
+    pub fn get_bid_levels(&self, depth: usize) -> Vec<(i64, u64, Option<u32>)> {
+        self.bids.iter().rev().take(depth).map(
+            |(price, quantity)| (*price, *quantity, self.bid_order_counts.get(price).copied())
+        ).collect()
+    }
+
+    pub fn get_ask_levels(&self, depth: usize) -> Vec<(i64, u64, Option<u32>)> {
+        self.asks.iter().take(depth).map(
+            |(price, quantity)| (*price, *quantity, self.ask_order_counts.get(price).copied())
+        ).collect()
+    }
+
+    pub fn get_best_bid(&self) -> Option<(i64, u64)> {
+        self.bids.iter().next_back().map(|(price, quantity)| (*price, *quantity))
+    }
+
+    pub fn get_best_ask(&self) -> Option<(i64, u64)> {
+        self.asks.iter().next().map(|(price, quantity)| (*price, *quantity))
     }
 
     pub fn get_weighted_mid_price(&self) -> Option<f64> {
-        let best_bid_option = self.get_best_bid();
-        if best_bid_option.is_none() {
-            return None;
-        }
-        let best_bid = best_bid_option.unwrap();
-        let best_ask_option = self.get_best_ask();
-        if best_ask_option.is_none() {
-            return None;
-        }
-        let best_ask = best_ask_option.unwrap();
-        Some(((best_bid.0 * best_bid.1 + best_ask.0 * best_ask.1) as f64) / ((best_bid.1 + best_ask.1) as f64))
+        let best_bid = self.get_best_bid()?;
+        let best_ask = self.get_best_ask()?;
+        let numerator = ((best_bid.0 as i128) * (best_bid.1 as i128)).saturating_add((best_ask.0 as i128) * (best_ask.1 as i128));
+        Some((numerator as f64) / ((best_bid.1 as f64) + (best_ask.1 as f64)))
     }
 
     pub fn get_weighted_bid(&self) -> Option<f64> {
         if self.bids.is_empty() {
             return None;
         }
-        let mut numerator: u64 = 0;
+        let mut numerator: i128 = 0;
         let mut total_quantity: u64 = 0;
         for (price, quantity) in self.bids.iter() {
-            numerator += price * quantity;
-            total_quantity += quantity;
+            numerator = numerator.saturating_add((*price as i128) * (*quantity as i128));
+            total_quantity = total_quantity.saturating_add(*quantity);
         }
         Some((numerator as f64) / (total_quantity as f64))
     }
@@ -125,11 +634,11 @@ impl Orderbook {
         if self.asks.is_empty() {
             return None;
         }
-        let mut numerator: u64 = 0;
+        let mut numerator: i128 = 0;
         let mut total_quantity: u64 = 0;
         for (price, quantity) in self.asks.iter() {
-            numerator += price * quantity;
-            total_quantity += quantity;
+            numerator = numerator.saturating_add((*price as i128) * (*quantity as i128));
+            total_quantity = total_quantity.saturating_add(*quantity);
         }
         Some((numerator as f64) / (total_quantity as f64))
     }
@@ -137,30 +646,37 @@ impl Orderbook {
     pub fn get_total_bid_quantity(&self) -> f64 {
         let mut total_quantity: u64 = 0;
         for (_, quantity) in self.bids.iter() {
-            total_quantity += quantity;
+            total_quantity = total_quantity.saturating_add(*quantity);
         }
-        (total_quantity as f64) / self.quantity_factor
+        self.unscale_qty(total_quantity)
     }
 
     pub fn get_total_ask_quantity(&self) -> f64 {
         let mut total_quantity: u64 = 0;
         for (_, quantity) in self.asks.iter() {
-            total_quantity += quantity;
+            total_quantity = total_quantity.saturating_add(*quantity);
         }
-        (total_quantity as f64) / self.quantity_factor
+        self.unscale_qty(total_quantity)
     }
 
+    /*
+    Scaled average price for taking quantity (unscaled). None for a non-finite or non-positive quantity,
+    insufficient depth, or a book with require_live set that does not accept taker orders
+    */
     pub fn simulate_taker_buy(&self, quantity: f64) -> Option<f64> {
-        let scaled_quantity = (quantity * self.quantity_factor) as u64;
+        if !(quantity.is_finite() && quantity > 0.0) || (self.require_live && !self.accepts_taker_orders()) {
+            return None;
+        }
+        let scaled_quantity = self.scale_qty(quantity);
         let mut amount_remaining = scaled_quantity;
-        let mut price_numerator: u64 = 0;
+        let mut price_numerator: i128 = 0;
         for (ask_price, ask_quantity) in self.asks.iter() {
             if ask_quantity > &amount_remaining {
-                price_numerator += amount_remaining * ask_price;
+                price_numerator = price_numerator.saturating_add((amount_remaining as i128) * (*ask_price as i128));
                 amount_remaining = 0;
                 break;
             }
-            price_numerator += ask_quantity * ask_price;
+            price_numerator = price_numerator.saturating_add((*ask_quantity as i128) * (*ask_price as i128));
             amount_remaining -= ask_quantity;
         }
         match amount_remaining {
@@ -170,16 +686,19 @@ impl Orderbook {
     }
 
     pub fn simulate_taker_sell(&self, quantity: f64) -> Option<f64> {
-        let scaled_quantity = (quantity * self.quantity_factor) as u64;
+        if !(quantity.is_finite() && quantity > 0.0) || (self.require_live && !self.accepts_taker_orders()) {
+            return None;
+        }
+        let scaled_quantity = self.scale_qty(quantity);
         let mut amount_remaining = scaled_quantity;
-        let mut price_numerator: u64 = 0;
+        let mut price_numerator: i128 = 0;
         for (ask_price, ask_quantity) in self.bids.iter().rev() {
             if ask_quantity > &amount_remaining {
-                price_numerator += amount_remaining * ask_price;
+                price_numerator = price_numerator.saturating_add((amount_remaining as i128) * (*ask_price as i128));
                 amount_remaining = 0;
                 break;
             }
-            price_numerator += ask_quantity * ask_price;
+            price_numerator = price_numerator.saturating_add((*ask_quantity as i128) * (*ask_price as i128));
             amount_remaining -= ask_quantity;
         }
         match amount_remaining {
@@ -187,4 +706,271 @@ impl Orderbook {
             _ => None
         }
     }
-}
\ No newline at end of file
+
+    /*
+    Compute summary statistics in a single pass over each side.
+    depth limits the number of levels per side considered, None covers the full book
+    */
+    pub fn summary(&self, depth: Option<usize>) -> BookSummary {
+        let depth = depth.unwrap_or(usize::MAX);
+        let mut best_bid: Option<(i64, u64)> = None;
+        let mut bid_numerator: i128 = 0;
+        let mut bid_quantity: u64 = 0;
+        for (price, quantity) in self.bids.iter().rev().take(depth) {
+            if best_bid.is_none() {
+                best_bid = Some((*price, *quantity));
+            }
+            bid_numerator = bid_numerator.saturating_add((*price as i128) * (*quantity as i128));
+            bid_quantity = bid_quantity.saturating_add(*quantity);
+        }
+        let mut best_ask: Option<(i64, u64)> = None;
+        let mut ask_numerator: i128 = 0;
+        let mut ask_quantity: u64 = 0;
+        for (price, quantity) in self.asks.iter().take(depth) {
+            if best_ask.is_none() {
+                best_ask = Some((*price, *quantity));
+            }
+            ask_numerator = ask_numerator.saturating_add((*price as i128) * (*quantity as i128));
+            ask_quantity = ask_quantity.saturating_add(*quantity);
+        }
+        let (spread, mid_price, microprice) = match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) => (
+                Some(ask.0.saturating_sub(bid.0)),
+                Some(((bid.0 as f64) + (ask.0 as f64)) / 2.0),
+                Some((((bid.0 as i128) * (ask.1 as i128)).saturating_add((ask.0 as i128) * (bid.1 as i128)) as f64) / ((bid.1 as f64) + (ask.1 as f64)))
+            ),
+            _ => (None, None, None)
+        };
+        let imbalance = match bid_quantity.saturating_add(ask_quantity) {
+            0 => None,
+            total => Some(((bid_quantity as f64) - (ask_quantity as f64)) / (total as f64))
+        };
+        BookSummary {
+            best_bid,
+            best_ask,
+            spread,
+            mid_price,
+            microprice,
+            imbalance,
+            total_bid_quantity: self.unscale_qty(bid_quantity),
+            total_ask_quantity: self.unscale_qty(ask_quantity),
+            weighted_bid: match bid_quantity {
+                0 => None,
+                _ => Some((bid_numerator as f64) / (bid_quantity as f64))
+            },
+            weighted_ask: match ask_quantity {
+                0 => None,
+                _ => Some((ask_numerator as f64) / (ask_quantity as f64))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BandCheck;
+
+    // Scaled magnitude up to which unscale then scale recovers the integer at every decimals setting
+    const EXACT_ROUND_TRIP: u64 = 1 << 51;
+
+    fn factors() -> impl Iterator<Item = (u8, f64)> {
+        (0..=MAX_DECIMALS).map(|decimals| (decimals, decimal_factor(Some(decimals))))
+    }
+
+    /*
+    Deterministic xorshift so the sampled magnitudes are the same on every run
+    */
+    fn samples(count: usize, below: u64) -> impl Iterator<Item = u64> {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        (0..count).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % below
+        })
+    }
+
+    #[test]
+    fn scaled_round_trip_is_exact_below_2_pow_51() {
+        for (_, factor) in factors() {
+            let edges = (0..64).flat_map(|offset| [offset, EXACT_ROUND_TRIP - offset]);
+            for scaled in edges.chain(samples(10_000, EXACT_ROUND_TRIP)) {
+                assert_eq!(scale(unscale(scaled, factor), factor), scaled);
+                let signed = scaled as i64;
+                assert_eq!(scale_signed(unscale_signed(signed, factor), factor), signed);
+                assert_eq!(scale_signed(unscale_signed(-signed, factor), factor), -signed);
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_round_trip_near_2_pow_53_is_within_one_increment() {
+        for (decimals, factor) in factors() {
+            let edges = (0..256).map(|offset| MAX_EXACT_SCALED - offset);
+            for scaled in edges.chain(samples(10_000, MAX_EXACT_SCALED - EXACT_ROUND_TRIP).map(|sample| sample + EXACT_ROUND_TRIP)) {
+                let back = scale(unscale(scaled, factor), factor);
+                match decimals {
+                    0 => assert_eq!(back, scaled),
+                    _ => assert!(back.abs_diff(scaled) <= 1, "{scaled} came back as {back} at {decimals} decimals")
+                }
+                let signed = -(scaled as i64);
+                assert!(scale_signed(unscale_signed(signed, factor), factor).abs_diff(signed) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn unscaled_round_trip_returns_the_same_f64() {
+        for (_, factor) in factors() {
+            for scaled in samples(10_000, EXACT_ROUND_TRIP) {
+                let value = unscale(scaled, factor);
+                assert_eq!(unscale(scale(value, factor), factor), value);
+                assert_eq!(unscale_signed(scale_signed(-value, factor), factor), -value);
+            }
+        }
+        assert_eq!(scale(1234.5678, 1e4), 12_345_678);
+        assert_eq!(unscale(12_345_678, 1e4), 1234.5678);
+        assert_eq!(scale(0.1, 1e8), 10_000_000);
+        assert_eq!(scale_signed(-0.3, 1e2), -30);
+    }
+
+    #[test]
+    fn scale_rounds_half_away_from_zero_and_saturates() {
+        assert_eq!(scale(0.5, 1.0), 1);
+        assert_eq!(scale(2.5, 1.0), 3);
+        assert_eq!(scale(0.25, 10.0), 3);
+        assert_eq!(scale(0.049_999, 10.0), 0);
+        assert_eq!(scale_signed(-0.5, 1.0), -1);
+        assert_eq!(scale_signed(-0.25, 10.0), -3);
+        // 0.00015 is stored just below the half, so it rounds down
+        assert_eq!(scale(0.000_15, 1e4), 1);
+        assert_eq!(scale(-0.5, 1.0), 0);
+        assert_eq!(scale(f64::NAN, 1e6), 0);
+        assert_eq!(scale(f64::INFINITY, 1e6), u64::MAX);
+        assert_eq!(scale_signed(f64::NEG_INFINITY, 1e6), i64::MIN);
+        assert_eq!(scale_signed(f64::NAN, 1e6), 0);
+    }
+
+    #[test]
+    fn checked_scale_accepts_up_to_2_pow_53() {
+        for (_, factor) in factors() {
+            let limit = unscale(MAX_EXACT_SCALED, factor);
+            assert_eq!(checked_scale(limit, factor), Some(MAX_EXACT_SCALED));
+            assert_eq!(checked_scale_signed(-limit, factor), Some(-(MAX_EXACT_SCALED as i64)));
+            assert_eq!(checked_scale(unscale(MAX_EXACT_SCALED + 2, factor), factor), None);
+            assert_eq!(checked_scale_signed(-unscale(MAX_EXACT_SCALED + 2, factor), factor), None);
+        }
+        assert_eq!(checked_scale(-1.0, 1e6), None);
+        assert_eq!(checked_scale(f64::NAN, 1e6), None);
+        assert_eq!(checked_scale_signed(f64::INFINITY, 1e6), None);
+    }
+
+    #[test]
+    fn decimal_factor_bounds() {
+        assert_eq!(decimal_factor(None), 1e6);
+        assert_eq!(decimal_factor(Some(0)), 1.0);
+        assert_eq!(decimal_factor(Some(MAX_DECIMALS)), 1e8);
+        assert_eq!(
+            checked_decimal_factor("price_decimals", Some(MAX_DECIMALS + 1)),
+            Err(ConfigError::TooManyDecimals { field: "price_decimals", decimals: MAX_DECIMALS + 1 })
+        );
+    }
+
+    fn negative_book() -> Orderbook {
+        let mut book = Orderbook::new(Some(2), Some(2));
+        book.process(vec![(-10.5, 1.0), (-11.0, 2.0)], vec![(-10.0, 1.0), (-9.5, 3.0)], true);
+        book
+    }
+
+    #[test]
+    fn negative_levels_order_best_first() {
+        let book = negative_book();
+        assert_eq!(book.get_best_bid(), Some((-1050, 100)));
+        assert_eq!(book.get_best_ask(), Some((-1000, 100)));
+        assert_eq!(book.get_bid_levels(10).iter().map(|level| level.0).collect::<Vec<i64>>(), vec![-1050, -1100]);
+        assert_eq!(book.get_ask_levels(10).iter().map(|level| level.0).collect::<Vec<i64>>(), vec![-1000, -950]);
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.bids(), &[(-1050, 100), (-1100, 200)]);
+        assert_eq!(snapshot.asks(), &[(-1000, 100), (-950, 300)]);
+        assert_eq!(book.unscale_price(-1050), -10.5);
+    }
+
+    #[test]
+    fn negative_and_mixed_sign_summary() {
+        let book = negative_book();
+        let summary = book.summary(None);
+        assert_eq!(summary.spread, Some(50));
+        assert_eq!(summary.mid_price, Some(-1025.0));
+        assert_eq!(summary.microprice, Some(-1025.0));
+        assert_eq!(summary.weighted_bid, Some(-325_000.0 / 300.0));
+        assert_eq!(summary.weighted_ask, Some(-385_000.0 / 400.0));
+        assert_eq!(book.snapshot().get_spread(), Some(50));
+        assert_eq!(book.snapshot().get_mid_price(), Some(-1025.0));
+
+        let mut mixed = Orderbook::new(Some(2), Some(2));
+        mixed.process(vec![(-0.5, 1.0), (-1.0, 1.0)], vec![(0.5, 1.0), (1.0, 1.0)], true);
+        let summary = mixed.summary(None);
+        assert_eq!(summary.spread, Some(100));
+        assert_eq!(summary.mid_price, Some(0.0));
+        assert_eq!(summary.weighted_bid, Some(-75.0));
+        assert_eq!(summary.weighted_ask, Some(75.0));
+
+        mixed.process(vec![(0.75, 1.0)], Vec::new(), false);
+        assert_eq!(mixed.summary(None).spread, Some(-25));
+        assert_eq!(mixed.snapshot().get_spread(), Some(-25));
+    }
+
+    #[test]
+    fn negative_taker_simulation() {
+        let book = negative_book();
+        assert_eq!(book.simulate_taker_buy(2.0), Some(-975.0));
+        assert_eq!(book.simulate_taker_sell(3.0), Some(-325_000.0 / 300.0));
+        assert_eq!(book.simulate_taker_buy(5.0), None);
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.simulate_taker_buy(2.0), Some(-975.0));
+        assert_eq!(snapshot.simulate_taker_sell(3.0), Some(-325_000.0 / 300.0));
+
+        let mut mixed = Orderbook::new(Some(2), Some(2));
+        mixed.process(Vec::new(), vec![(-0.5, 1.0), (0.5, 1.0)], true);
+        assert_eq!(mixed.simulate_taker_buy(2.0), Some(0.0));
+        assert_eq!(mixed.simulate_taker_buy(1.0), Some(-50.0));
+    }
+
+    #[test]
+    fn max_distance_prune_measures_from_negative_mid() {
+        let mut book = negative_book();
+        book.prune_policy = Some(PrunePolicy::MaxDistance(0.05));
+        book.prune();
+        assert_eq!(book.bids.keys().copied().collect::<Vec<i64>>(), vec![-1050]);
+        assert_eq!(book.asks.keys().copied().collect::<Vec<i64>>(), vec![-1000]);
+
+        let mut book = negative_book();
+        book.prune_policy = Some(PrunePolicy::MaxDistance(0.1));
+        book.prune();
+        assert_eq!(book.bids.len() + book.asks.len(), 4);
+    }
+
+    #[test]
+    fn band_around_negative_reference() {
+        let mut book = negative_book();
+        book.set_reference_price(-10.0, 0.05);
+        assert_eq!(book.price_band.map(|band| (band.lower, band.upper)), Some((-1050, -950)));
+        assert_eq!(book.check_price(-10.2), BandCheck::Within);
+        assert_eq!(book.check_price(-11.0), BandCheck::BelowBand);
+        assert_eq!(book.check_price(-9.0), BandCheck::AboveBand);
+        assert!(!book.is_limit_state());
+        book.set_reference_price(-9.6, 0.04);
+        assert_eq!(book.price_band.map(|band| (band.lower, band.upper)), Some((-998, -922)));
+        assert!(book.is_limit_state());
+    }
+
+    #[test]
+    fn zero_quantity_delta_removes_level() {
+        let mut book = Orderbook::new(Some(2), Some(2));
+        book.process(vec![(99.0, 1.0), (98.0, 1.0)], vec![(101.0, 1.0)], true);
+        book.process(vec![(99.0, 0.0)], vec![(101.0, 0.0), (102.0, 0.0)], false);
+        assert_eq!(book.bids.keys().copied().collect::<Vec<i64>>(), vec![9800]);
+        assert!(book.asks.is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+/*
+Purpose: Text ladder diff of two snapshots for debugging divergences
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+use crate::BookSnapshot;
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_ADDED: &str = "\x1b[32m";
+const ANSI_REMOVED: &str = "\x1b[31m";
+const ANSI_CHANGED: &str = "\x1b[33m";
+
+/*
+One row per price present in either snapshot within depth levels per side (None for all), asks above
+bids, highest price first, with the before and after quantities side by side. Rows are marked + added,
+- removed, ~ changed and left blank when equal; ansi also colors them green, red and yellow.
+Both snapshots must use the same price and quantity decimals
+*/
+pub fn diff_ladder(before: &BookSnapshot, after: &BookSnapshot, depth: Option<usize>, ansi: bool) -> String {
+    let depth = depth.unwrap_or(usize::MAX);
+    let price_decimals = decimals(after.price_factor());
+    let quantity_decimals = decimals(after.quantity_factor());
+    let mut output = format!("  {:<4} {:>16} {:>16} {:>16}\n", "side", "price", "before", "after");
+    for (label, before_levels, after_levels) in [("ask", before.asks(), after.asks()), ("bid", before.bids(), after.bids())] {
+        let before_levels: BTreeMap<i64, u64> = before_levels.iter().take(depth).copied().collect();
+        let after_levels: BTreeMap<i64, u64> = after_levels.iter().take(depth).copied().collect();
+        let prices: BTreeSet<i64> = before_levels.keys().chain(after_levels.keys()).copied().collect();
+        for price in prices.into_iter().rev() {
+            let before_quantity = before_levels.get(&price).copied();
+            let after_quantity = after_levels.get(&price).copied();
+            let (marker, color) = match (before_quantity, after_quantity) {
+                (None, Some(_)) => ('+', ANSI_ADDED),
+                (Some(_), None) => ('-', ANSI_REMOVED),
+                (Some(before_quantity), Some(after_quantity)) if before_quantity != after_quantity => ('~', ANSI_CHANGED),
+                _ => (' ', "")
+            };
+            let quantity = |quantity: Option<u64>| quantity.map_or(String::new(), |quantity| format!("{:.*}", quantity_decimals, after.unscale_qty(quantity)));
+            let row = format!(
+                "{} {:<4} {:>16.*} {:>16} {:>16}",
+                marker,
+                label,
+                price_decimals,
+                after.unscale_price(price),
+                quantity(before_quantity),
+                quantity(after_quantity)
+            );
+            let _ = match (ansi, color.is_empty()) {
+                (true, false) => writeln!(output, "{}{}{}", color, row, ANSI_RESET),
+                _ => writeln!(output, "{}", row)
+            };
+        }
+    }
+    output
+}
+
+fn decimals(factor: f64) -> usize {
+    factor.log10().round().max(0.0) as usize
+}
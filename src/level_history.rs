@@ -0,0 +1,193 @@
+/*
+Purpose: Quantity-at-price time series for watched price levels
+*/
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{BookEvent, Orderbook, Projection, Side};
+
+/*
+Displayed quantity (scaled) at a watched price from timestamp (book clock, ms) on. side is None and
+quantity 0 while no level rests at the price
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelObservation {
+    pub timestamp: u64,
+    pub side: Option<Side>,
+    pub quantity: u64
+}
+
+/*
+Projection keeping a ring buffer of the most recent capacity observations per watched scaled price,
+on either side. Only changes are recorded: a snapshot that re-lists a watched level unchanged adds nothing,
+one that drops it records its removal. A rescale of the book's decimals moves each watched price and its
+observations to the new scale. Prices passed to new are seeded by add_projection; use
+Orderbook::watch_level to add one to a registered history with its current quantity
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelHistory {
+    pub capacity: usize,
+    levels: HashMap<i64, VecDeque<LevelObservation>>,
+    cleared: Vec<i64>,
+    cleared_at: u64
+}
+
+impl LevelHistory {
+    pub fn new(capacity: usize, prices: &[i64]) -> LevelHistory {
+        LevelHistory {
+            capacity: capacity.max(1),
+            levels: prices.iter().map(|price| (*price, VecDeque::new())).collect(),
+            cleared: Vec::new(),
+            cleared_at: 0
+        }
+    }
+
+    /*
+    Start watching price with no history. Returns false if it was already watched
+    */
+    pub fn watch(&mut self, price: i64) -> bool {
+        match self.levels.contains_key(&price) {
+            true => false,
+            false => {
+                self.levels.insert(price, VecDeque::new());
+                true
+            }
+        }
+    }
+
+    pub fn unwatch(&mut self, price: i64) -> Option<VecDeque<LevelObservation>> {
+        self.levels.remove(&price)
+    }
+
+    pub fn is_watched(&self, price: i64) -> bool {
+        self.levels.contains_key(&price)
+    }
+
+    /*
+    Observations at price, oldest first
+    */
+    pub fn history(&self, price: i64) -> Option<&VecDeque<LevelObservation>> {
+        self.levels.get(&price)
+    }
+
+    pub fn latest(&self, price: i64) -> Option<LevelObservation> {
+        self.levels.get(&price)?.back().copied()
+    }
+
+    /*
+    Observations at price within [from, to), preceded by the one in effect at from if retained
+    */
+    pub fn range(&self, price: i64, from: u64, to: u64) -> Vec<LevelObservation> {
+        let history = match self.levels.get(&price) {
+            Some(history) => history,
+            None => return Vec::new()
+        };
+        let start = history.iter().rposition(|observation| observation.timestamp <= from).unwrap_or(0);
+        history.iter().skip(start).take_while(|observation| observation.timestamp < to).copied().collect()
+    }
+
+    fn record(&mut self, price: i64, side: Option<Side>, quantity: u64, timestamp: u64) {
+        if let Some(history) = self.levels.get_mut(&price) {
+            let observation = LevelObservation { timestamp, side, quantity };
+            if history.back().is_some_and(|last| last.side == side && last.quantity == quantity) {
+                return;
+            }
+            // capacity is pub and may have been lowered or zeroed since the last observation
+            while history.len() >= self.capacity.max(1) {
+                history.pop_front();
+            }
+            history.push_back(observation);
+        }
+    }
+
+    fn rescale(&mut self, price_multiplier: i64, quantity_multiplier: u64) {
+        self.levels = self.levels.drain().map(|(price, mut history)| {
+            for observation in history.iter_mut() {
+                observation.quantity = observation.quantity.saturating_mul(quantity_multiplier);
+            }
+            (price.saturating_mul(price_multiplier), history)
+        }).collect();
+        for price in self.cleared.iter_mut() {
+            *price = price.saturating_mul(price_multiplier);
+        }
+    }
+
+    /*
+    Watched levels that a snapshot did not re-list by the time of a later event are recorded as removed
+    at the snapshot's time
+    */
+    fn settle_cleared(&mut self) {
+        for price in std::mem::take(&mut self.cleared) {
+            self.record(price, None, 0, self.cleared_at);
+        }
+    }
+}
+
+impl Projection for LevelHistory {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, previous } => {
+                match previous.is_none() && timestamp == self.cleared_at {
+                    true => self.cleared.retain(|cleared| *cleared != price),
+                    false => self.settle_cleared()
+                }
+                self.record(price, Some(side), quantity, timestamp);
+            },
+            BookEvent::LevelRemoved { price, .. } => {
+                self.settle_cleared();
+                self.record(price, None, 0, timestamp);
+            },
+            BookEvent::Cleared => {
+                self.settle_cleared();
+                self.cleared = self.levels.keys().copied().collect();
+                self.cleared_at = timestamp;
+            },
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => self.rescale(price_multiplier, quantity_multiplier)
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Watch price on the registered LevelHistory, seeded with the level resting there now. False without
+    a registered LevelHistory or if price is already watched
+    */
+    pub fn watch_level(&mut self, price: i64) -> bool {
+        let current = match (self.bids.get(&price), self.asks.get(&price)) {
+            (Some(quantity), _) => (Some(Side::Bid), *quantity),
+            (None, Some(quantity)) => (Some(Side::Ask), *quantity),
+            (None, None) => (None, 0)
+        };
+        let timestamp = self.timestamp;
+        let history = match self.projection_mut::<LevelHistory>() {
+            Some(history) => history,
+            None => return false
+        };
+        let watched = history.watch(price);
+        if watched {
+            history.record(price, current.0, current.1, timestamp);
+        }
+        watched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowered_capacity_trims_each_history() {
+        let mut history = LevelHistory::new(8, &[100]);
+        for quantity in 1..=8 {
+            history.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity, previous: None }, quantity);
+        }
+        history.capacity = 3;
+        history.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity: 9, previous: Some(8) }, 9);
+        let quantities = |history: &LevelHistory| history.history(100).map(|observations| observations.iter().map(|observation| observation.quantity).collect::<Vec<_>>());
+        assert_eq!(quantities(&history), Some(vec![7, 8, 9]));
+        history.capacity = 0;
+        history.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity: 10, previous: Some(9) }, 10);
+        assert_eq!(quantities(&history), Some(vec![10]));
+    }
+}
@@ -3,7 +3,153 @@ Author: Jake Mathai
 Purpose: Module file
 */
 
+/*
+The crate contains no unsafe code; the pipeline queue is built on std Mutex and Condvar
+*/
+#![forbid(unsafe_code)]
+/*
+Library code must not bring down the host process: fallible paths return Option or a typed error,
+and the only panic is the documented one in the infallible constructors
+*/
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
 mod l2;
 pub use l2::*;
+mod quote_book;
+pub use quote_book::*;
+mod verify;
+pub use verify::*;
+mod features;
+pub use features::*;
+mod predict;
+pub use predict::*;
+mod publisher;
+pub use publisher::*;
+mod pipeline;
+pub use pipeline::*;
+mod lifecycle;
+pub use lifecycle::*;
+mod banding;
+pub use banding::*;
+mod nbbo;
+pub use nbbo::*;
+mod stress;
+pub use stress::*;
+mod grid;
+pub use grid::*;
+mod markout;
+pub use markout::*;
+mod config;
+pub use config::*;
+mod instruments;
+pub use instruments::*;
+mod fx;
+pub use fx::*;
+mod arb;
+pub use arb::*;
+mod reconcile;
+pub use reconcile::*;
+mod events;
+pub use events::*;
+mod rate;
+pub use rate::*;
+mod lifetime;
+pub use lifetime::*;
+mod snapshot;
+pub use snapshot::*;
+mod twa;
+pub use twa::*;
+mod vpin;
+pub use vpin::*;
+mod validate;
+pub use validate::*;
+mod changes;
+pub use changes::*;
+mod stats;
+pub use stats::*;
+mod shard;
+pub use shard::*;
+mod rollup;
+pub use rollup::*;
+mod crashdump;
+pub use crashdump::*;
+mod ladder;
+pub use ladder::*;
+mod cost;
+pub use cost::*;
+mod iceberg;
+pub use iceberg::*;
+mod alerts;
+pub use alerts::*;
+mod synthetic;
+pub use synthetic::*;
+mod basket;
+pub use basket::*;
+mod spread;
+pub use spread::*;
+mod liquidation;
+pub use liquidation::*;
+mod volatility;
+pub use volatility::*;
+mod codec;
+pub use codec::*;
+mod flat;
+pub use flat::*;
+mod calendar;
+pub use calendar::*;
+mod time;
+pub use time::*;
+mod divergence;
+pub use divergence::*;
+mod queue;
+pub use queue::*;
+mod precision;
+pub use precision::*;
+mod level_history;
+pub use level_history::*;
+mod round_numbers;
+pub use round_numbers::*;
+mod sweep;
+pub use sweep::*;
+mod audit;
+pub use audit::*;
+mod throttle;
+pub use throttle::*;
+mod warmup;
+pub use warmup::*;
+mod participation;
+pub use participation::*;
+mod fill_model;
+pub use fill_model::*;
+mod archive;
+pub use archive::*;
+mod checksum;
+pub use checksum::*;
+mod index;
+pub use index::*;
+mod funding;
+pub use funding::*;
+mod mirror;
+pub use mirror::*;
+mod window;
+pub use window::*;
+mod poller;
+pub use poller::*;
+#[cfg(feature = "sqlite")]
+mod storage;
+#[cfg(feature = "sqlite")]
+pub use storage::*;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "redis")]
+pub use redis::*;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "otel")]
+pub use otel::*;
+#[cfg(feature = "tui")]
+mod dom;
+#[cfg(feature = "tui")]
+pub use dom::*;
 // mod l3;
 // pub use l3::*;
\ No newline at end of file
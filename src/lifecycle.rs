@@ -0,0 +1,161 @@
+/*
+Purpose: Book lifecycle state machine
+*/
+
+use crate::{validate_levels, BookEvent, Orderbook, TradingCalendar, ValidationError};
+
+/*
+Initializing: no snapshot applied yet.
+Syncing: a sequence gap was reported, deltas are unreliable until the next snapshot.
+Live: the book reflects the venue.
+Stale: no update within stale_after of the last one.
+Halted: the venue halted the instrument; snapshots are still applied but the book stays Halted until resumed
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookState {
+    Initializing,
+    Syncing,
+    Live,
+    Stale,
+    Halted
+}
+
+/*
+Instrument trading phase as published by the venue
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradingStatus {
+    Trading,
+    Auction,
+    PostOnly,
+    Halted,
+    Closed
+}
+
+pub type StateListener = Box<dyn FnMut(BookState, BookState) + Send>;
+
+impl<M> Orderbook<M> {
+    pub fn state(&self) -> BookState {
+        self.state
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.state == BookState::Live
+    }
+
+    pub fn trading_status(&self) -> TradingStatus {
+        self.trading_status
+    }
+
+    /*
+    Apply a venue status event. Halted and Closed halt the book, any other status resumes it
+    */
+    pub fn set_trading_status(&mut self, status: TradingStatus) {
+        self.trading_status = status;
+        match status {
+            TradingStatus::Halted | TradingStatus::Closed => self.halt(),
+            TradingStatus::Trading | TradingStatus::Auction | TradingStatus::PostOnly => self.resume()
+        }
+    }
+
+    /*
+    True when the book is Live in continuous trading, where taking liquidity at displayed prices is possible
+    */
+    pub fn accepts_taker_orders(&self) -> bool {
+        self.is_live() && self.trading_status == TradingStatus::Trading
+    }
+
+    /*
+    Register a callback invoked with (previous, current) on every state transition
+    */
+    pub fn on_state_change(&mut self, listener: StateListener) {
+        self.state_listener = Some(listener);
+    }
+
+    /*
+    Report a gap in the update sequence. The book waits in Syncing for a snapshot
+    */
+    pub fn mark_gap(&mut self) {
+        match self.state {
+            BookState::Halted => self.synced = false,
+            _ => self.transition(BookState::Syncing)
+        }
+    }
+
+    /*
+    Mark a Live book Stale if the book clock has not advanced within stale_after of now (ms)
+    */
+    pub fn check_staleness(&mut self, now: u64) {
+        if let Some(stale_after) = self.stale_after {
+            if self.state == BookState::Live && now.saturating_sub(self.timestamp) > stale_after {
+                self.transition(BookState::Stale);
+            }
+        }
+    }
+
+    /*
+    check_staleness counting only open trading time in calendar toward stale_after, so a quiet book over
+    a close, break or holiday is not mistaken for a dead feed
+    */
+    pub fn check_staleness_in(&mut self, now: u64, calendar: &TradingCalendar) {
+        if let Some(stale_after) = self.stale_after {
+            if self.state == BookState::Live && calendar.open_ms(self.timestamp, now) > stale_after {
+                self.transition(BookState::Stale);
+            }
+        }
+    }
+
+    pub fn halt(&mut self) {
+        self.transition(BookState::Halted);
+    }
+
+    /*
+    Leave Halted. The book is Live again only if it was synced before the halt
+    */
+    pub fn resume(&mut self) {
+        if self.state == BookState::Halted {
+            let state = match self.synced {
+                true => BookState::Live,
+                false => BookState::Syncing
+            };
+            self.transition(state);
+        }
+    }
+
+    /*
+    Swap in a fresh snapshot on a running book, e.g. after a resync. The state listener, projections,
+    update-rate tracking and configuration are kept; projections receive Reset before the new levels.
+    The snapshot is validated first and the book is left untouched on error
+    */
+    pub fn reload_snapshot(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> Result<(), ValidationError> {
+        validate_levels(&bids, &asks)?;
+        self.emit(BookEvent::Reset);
+        self.load_snapshot(bids, asks);
+        Ok(())
+    }
+
+    pub(crate) fn on_processed(&mut self, is_snapshot: bool) {
+        if is_snapshot {
+            self.synced = true;
+        }
+        match self.state {
+            BookState::Initializing | BookState::Syncing if is_snapshot => self.transition(BookState::Live),
+            BookState::Stale => self.transition(BookState::Live),
+            _ => ()
+        }
+    }
+
+    pub(crate) fn transition(&mut self, state: BookState) {
+        if state == BookState::Syncing {
+            self.synced = false;
+        }
+        if state == self.state {
+            return;
+        }
+        let previous = self.state;
+        self.state = state;
+        if let Some(listener) = self.state_listener.as_mut() {
+            listener(previous, state);
+        }
+    }
+}
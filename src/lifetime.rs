@@ -0,0 +1,101 @@
+/*
+Purpose: Price level age and lifetime analytics
+*/
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::events::rescale_tree;
+use crate::{BookEvent, Projection, Side};
+
+/*
+Projection recording when each level was inserted (book clock, ms) and the lifetimes of the
+most recent capacity removed levels. Quantity changes keep a level's insertion time.
+A snapshot restarts every level's age and does not record lifetimes for the levels it replaces
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelLifetimes {
+    pub capacity: usize,
+    pub bid_inserted: BTreeMap<i64, u64>,
+    pub ask_inserted: BTreeMap<i64, u64>,
+    pub lifetimes: VecDeque<u64>
+}
+
+impl LevelLifetimes {
+    pub fn new(capacity: usize) -> LevelLifetimes {
+        LevelLifetimes {
+            capacity,
+            bid_inserted: BTreeMap::new(),
+            ask_inserted: BTreeMap::new(),
+            lifetimes: VecDeque::new()
+        }
+    }
+
+    /*
+    Time since the current best bid level was inserted
+    */
+    pub fn best_bid_age(&self, now: u64) -> Option<u64> {
+        self.bid_inserted.iter().next_back().map(|(_, inserted)| now.saturating_sub(*inserted))
+    }
+
+    pub fn best_ask_age(&self, now: u64) -> Option<u64> {
+        self.ask_inserted.iter().next().map(|(_, inserted)| now.saturating_sub(*inserted))
+    }
+
+    pub fn mean_lifetime(&self) -> Option<f64> {
+        match self.lifetimes.is_empty() {
+            true => None,
+            false => Some(self.lifetimes.iter().map(|lifetime| *lifetime as f64).sum::<f64>() / (self.lifetimes.len() as f64))
+        }
+    }
+
+    /*
+    Lifetime at quantile (0.5 = median) of the recorded lifetimes, nearest rank
+    */
+    pub fn lifetime_quantile(&self, quantile: f64) -> Option<u64> {
+        if self.lifetimes.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<u64> = self.lifetimes.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (quantile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+        Some(sorted[rank])
+    }
+}
+
+impl Projection for LevelLifetimes {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, price, previous: None, .. } => {
+                let inserted = match side {
+                    Side::Bid => &mut self.bid_inserted,
+                    Side::Ask => &mut self.ask_inserted
+                };
+                inserted.insert(price, timestamp);
+            },
+            BookEvent::LevelSet { .. } => (),
+            BookEvent::LevelRemoved { side, price, .. } => {
+                let inserted = match side {
+                    Side::Bid => self.bid_inserted.remove(&price),
+                    Side::Ask => self.ask_inserted.remove(&price)
+                };
+                if let Some(inserted) = inserted {
+                    if self.capacity > 0 {
+                        if self.lifetimes.len() == self.capacity {
+                            self.lifetimes.pop_front();
+                        }
+                        self.lifetimes.push_back(timestamp.saturating_sub(inserted));
+                    }
+                }
+            },
+            BookEvent::Cleared => {
+                self.bid_inserted.clear();
+                self.ask_inserted.clear();
+            },
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { price_multiplier, .. } => {
+                rescale_tree(&mut self.bid_inserted, price_multiplier, 1);
+                rescale_tree(&mut self.ask_inserted, price_multiplier, 1);
+            }
+        }
+    }
+}
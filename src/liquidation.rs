@@ -0,0 +1,85 @@
+/*
+Purpose: Liquidation price and unwind proceeds of a leveraged position estimated from current depth
+*/
+
+use crate::{Orderbook, Side};
+
+/*
+collateral is margin posted in quote currency, maintenance_margin the required equity as a fraction
+of position notional at mark, fee_rate the taker fee charged on the unwind notional
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginParams {
+    pub collateral: f64,
+    pub maintenance_margin: f64,
+    pub fee_rate: f64
+}
+
+/*
+Unscaled values in quote currency. side is the position's side (Side::Bid long), proceeds the cash
+the unwind receives net of fees (negative when buying back a short), slippage its adverse fraction of mid.
+equity_after is collateral plus the realized PnL of unwinding now. liquidation_price is the mark where equity
+falls to maintenance margin; effective_liquidation_price also charges the unwind's slippage and fees at
+that mark, assuming the book keeps its current shape relative to mid. Either is None when the position
+cannot be liquidated at a positive price
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiquidationEstimate {
+    pub side: Side,
+    pub size: f64,
+    pub mark_price: f64,
+    pub unwind_price: f64,
+    pub slippage: f64,
+    pub fees: f64,
+    pub proceeds: f64,
+    pub realized_pnl: f64,
+    pub equity_after: f64,
+    pub liquidation_price: Option<f64>,
+    pub effective_liquidation_price: Option<f64>
+}
+
+impl<M> Orderbook<M> {
+    /*
+    size is the signed position in base units (positive long, negative short) opened at entry_price.
+    A long unwinds into the bids, a short into the asks. None without a two-sided book, with a zero or
+    non-finite size, or when the book cannot absorb the unwind
+    */
+    pub fn estimate_liquidation(&self, size: f64, entry_price: f64, margin: &MarginParams) -> Option<LiquidationEstimate> {
+        if !(size.is_finite() && size != 0.0) {
+            return None;
+        }
+        let mark_price = self.summary(Some(1)).mid_price? / self.price_factor;
+        if mark_price <= 0.0 {
+            return None;
+        }
+        let quantity = size.abs();
+        let (side, unwind_price, direction) = match size > 0.0 {
+            true => (Side::Bid, self.simulate_taker_sell(quantity)? / self.price_factor, 1.0),
+            false => (Side::Ask, self.simulate_taker_buy(quantity)? / self.price_factor, -1.0)
+        };
+        let slippage = direction * (mark_price - unwind_price) / mark_price;
+        let fees = unwind_price * quantity * margin.fee_rate;
+        let realized_pnl = size * (unwind_price - entry_price) - fees;
+        let liquidation_at = |adverse: f64| {
+            let denominator = size * (1.0 - direction * (adverse + margin.maintenance_margin));
+            let price = (size * entry_price - margin.collateral) / denominator;
+            match denominator != 0.0 && price.is_finite() && price > 0.0 {
+                true => Some(price),
+                false => None
+            }
+        };
+        Some(LiquidationEstimate {
+            side,
+            size,
+            mark_price,
+            unwind_price,
+            slippage,
+            fees,
+            proceeds: direction * unwind_price * quantity - fees,
+            realized_pnl,
+            equity_after: margin.collateral + realized_pnl,
+            liquidation_price: liquidation_at(0.0),
+            effective_liquidation_price: liquidation_at(slippage.max(0.0) + margin.fee_rate)
+        })
+    }
+}
@@ -0,0 +1,129 @@
+/*
+Purpose: Markout analysis of realized fills against later mids and simulated taker prices
+*/
+
+use crate::{Orderbook, Side};
+
+/*
+An executed fill with unscaled price and quantity. Side::Bid is a buy, Side::Ask a sell
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fill {
+    pub timestamp: u64,
+    pub side: Side,
+    pub price: f64,
+    pub quantity: f64
+}
+
+/*
+simulated_price is the average price simulate_taker_* predicted from the book at fill time.
+slippage is how much worse the fill was than that prediction (negative when better).
+markouts[i] is the mid move in the fill's favour at horizons[i], None until resolved.
+All values are unscaled prices
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkoutRecord {
+    pub fill: Fill,
+    pub simulated_price: Option<f64>,
+    pub slippage: Option<f64>,
+    pub markouts: Vec<Option<f64>>
+}
+
+/*
+Fed fills and book observations in time order, as during a replay. Each markout resolves against
+the mid at the first observation at least the horizon (ms) after the fill
+*/
+pub struct MarkoutAnalyzer {
+    pub horizons: Vec<u64>,
+    pub completed: Vec<MarkoutRecord>,
+    pending: Vec<MarkoutRecord>
+}
+
+impl MarkoutAnalyzer {
+    pub fn new(horizons: Vec<u64>) -> MarkoutAnalyzer {
+        MarkoutAnalyzer {
+            horizons,
+            completed: Vec::new(),
+            pending: Vec::new()
+        }
+    }
+
+    /*
+    Record a fill against the book as it stood when the fill happened
+    */
+    pub fn record_fill<M>(&mut self, fill: Fill, book: &Orderbook<M>) {
+        let simulated_price = match fill.side {
+            Side::Bid => book.simulate_taker_buy(fill.quantity),
+            Side::Ask => book.simulate_taker_sell(fill.quantity)
+        }.map(|price| price / book.price_factor);
+        let slippage = simulated_price.map(|simulated_price| match fill.side {
+            Side::Bid => fill.price - simulated_price,
+            Side::Ask => simulated_price - fill.price
+        });
+        self.pending.push(MarkoutRecord {
+            fill,
+            simulated_price,
+            slippage,
+            markouts: vec![None; self.horizons.len()]
+        });
+    }
+
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) {
+        let mid_price = match book.summary(Some(1)).mid_price {
+            Some(mid_price) => mid_price / book.price_factor,
+            None => return
+        };
+        for record in self.pending.iter_mut() {
+            for (horizon, markout) in self.horizons.iter().zip(record.markouts.iter_mut()) {
+                if markout.is_none() && timestamp >= record.fill.timestamp.saturating_add(*horizon) {
+                    *markout = Some(match record.fill.side {
+                        Side::Bid => mid_price - record.fill.price,
+                        Side::Ask => record.fill.price - mid_price
+                    });
+                }
+            }
+        }
+        let (completed, pending): (Vec<MarkoutRecord>, Vec<MarkoutRecord>) = self.pending.drain(..)
+            .partition(|record| record.markouts.iter().all(|markout| markout.is_some()));
+        self.completed.extend(completed);
+        self.pending = pending;
+    }
+
+    /*
+    Quantity-weighted average markout per horizon over completed records
+    */
+    pub fn average_markouts(&self) -> Vec<Option<f64>> {
+        (0..self.horizons.len()).map(|index| {
+            let mut weighted_total: f64 = 0.0;
+            let mut total_quantity: f64 = 0.0;
+            for record in self.completed.iter() {
+                if let Some(markout) = record.markouts.get(index).copied().flatten() {
+                    weighted_total += markout * record.fill.quantity;
+                    total_quantity += record.fill.quantity;
+                }
+            }
+            match total_quantity > 0.0 {
+                true => Some(weighted_total / total_quantity),
+                false => None
+            }
+        }).collect()
+    }
+
+    /*
+    Quantity-weighted average slippage versus simulation over completed records with a simulated price
+    */
+    pub fn average_slippage(&self) -> Option<f64> {
+        let mut weighted_total: f64 = 0.0;
+        let mut total_quantity: f64 = 0.0;
+        for record in self.completed.iter() {
+            if let Some(slippage) = record.slippage {
+                weighted_total += slippage * record.fill.quantity;
+                total_quantity += record.fill.quantity;
+            }
+        }
+        match total_quantity > 0.0 {
+            true => Some(weighted_total / total_quantity),
+            false => None
+        }
+    }
+}
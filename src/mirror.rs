@@ -0,0 +1,412 @@
+/*
+Purpose: Hot-standby book mirroring over a byte stream with state hash verification
+*/
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::flat::{state_code, state_from_code};
+use crate::{BookEvent, BookState, DecodeError, DeltaDecoder, DeltaEncoder, FlatError, FlatSnapshot, Orderbook, Projection, WireDelta};
+
+const RECORD_SYNC: u8 = b'S';
+const RECORD_ACK: u8 = b'A';
+const RECORD_UPDATE: u8 = b'U';
+const RECORD_CHECK: u8 = b'C';
+
+#[derive(Debug)]
+pub enum MirrorError {
+    Io(io::Error),
+    InvalidRecord(u8),
+    InvalidState(u8),
+    Flat(FlatError),
+    Decode(DecodeError),
+    NotSynced,
+    Sequence { expected: u64, found: u64 },
+    HashMismatch { sequence: u64, expected: u32, found: u32 }
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirrorError::Io(error) => write!(f, "mirror stream failed: {}", error),
+            MirrorError::InvalidRecord(kind) => write!(f, "invalid mirror record type {:#04x}", kind),
+            MirrorError::InvalidState(code) => write!(f, "invalid book state code {}", code),
+            MirrorError::Flat(error) => write!(f, "invalid sync snapshot: {}", error),
+            MirrorError::Decode(error) => write!(f, "invalid update frame: {}", error),
+            MirrorError::NotSynced => write!(f, "update received before a sync"),
+            MirrorError::Sequence { expected, found } => write!(f, "mirror sequence {} received, expected {}", found, expected),
+            MirrorError::HashMismatch { sequence, expected, found } =>
+                write!(f, "state hash {:#010x} at sequence {}, primary has {:#010x}", found, sequence, expected)
+        }
+    }
+}
+
+impl std::error::Error for MirrorError {}
+
+impl From<io::Error> for MirrorError {
+    fn from(error: io::Error) -> MirrorError {
+        MirrorError::Io(error)
+    }
+}
+
+/*
+What a standby record did to the mirrored book
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorEvent {
+    Synced { sequence: u64, hash: u32 },
+    Updated { sequence: u64, deltas: usize },
+    Verified { sequence: u64, hash: u32 }
+}
+
+type Pending = Arc<Mutex<Vec<WireDelta>>>;
+
+/*
+Projection collecting the primary book's changes between publications. Added by MirrorPrimary::attach
+*/
+pub struct MirrorTap {
+    pending: Pending
+}
+
+impl Projection for MirrorTap {
+    fn apply(&mut self, event: &BookEvent, _timestamp: u64) {
+        if let Some(delta) = WireDelta::from_event(event) {
+            self.pending.lock().unwrap_or_else(PoisonError::into_inner).push(delta);
+        }
+    }
+}
+
+/*
+Streams a book's applied changes to a standby. Records, with little-endian integers:
+'S' sequence u64, length u32, flat snapshot (primary to standby);
+'A' sequence u64, state hash u32 (standby to primary, answering 'S');
+'U' sequence u64, timestamp u64, state u8, length u32, one DeltaEncoder frame;
+'C' sequence u64, state hash u32, sent every verify_interval updates (0 disables).
+Sequences number every 'S' and 'U' from 1. Call handshake once attached and whenever a standby connects,
+then publish after each update. A change of the book's decimals triggers a new handshake
+*/
+pub struct MirrorPrimary<S: Read + Write> {
+    pub verify_interval: u64,
+    stream: S,
+    encoder: DeltaEncoder,
+    pending: Pending,
+    sequence: u64,
+    synced: Option<(f64, f64, BookState)>
+}
+
+impl<S: Read + Write> MirrorPrimary<S> {
+    pub fn new(stream: S, verify_interval: u64) -> MirrorPrimary<S> {
+        MirrorPrimary {
+            verify_interval,
+            stream,
+            encoder: DeltaEncoder::new(),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            sequence: 0,
+            synced: None
+        }
+    }
+
+    /*
+    Start capturing book's changes. Attach exactly one book per primary
+    */
+    pub fn attach<M>(&self, book: &mut Orderbook<M>) {
+        book.add_projection(MirrorTap { pending: self.pending.clone() });
+    }
+
+    /*
+    Send the full book and wait for the standby's state hash. On a mismatch the standby is not in sync
+    and the handshake should be retried
+    */
+    pub fn handshake<M>(&mut self, book: &Orderbook<M>) -> Result<u32, MirrorError> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        self.encoder = DeltaEncoder::new();
+        self.sequence += 1;
+        let flat = book.snapshot().to_flat();
+        let mut record = vec![RECORD_SYNC];
+        record.extend_from_slice(&self.sequence.to_le_bytes());
+        record.extend_from_slice(&(flat.len() as u32).to_le_bytes());
+        record.extend_from_slice(&flat);
+        self.stream.write_all(&record)?;
+        self.stream.flush()?;
+        self.synced = None;
+        let kind = read_array::<_, 1>(&mut self.stream)?[0];
+        if kind != RECORD_ACK {
+            return Err(MirrorError::InvalidRecord(kind));
+        }
+        let sequence = u64::from_le_bytes(read_array(&mut self.stream)?);
+        let found = u32::from_le_bytes(read_array(&mut self.stream)?);
+        if sequence != self.sequence {
+            return Err(MirrorError::Sequence { expected: self.sequence, found: sequence });
+        }
+        let expected = book.state_hash();
+        if found != expected {
+            return Err(MirrorError::HashMismatch { sequence, expected, found });
+        }
+        self.synced = Some((book.price_factor, book.quantity_factor, book.state()));
+        Ok(expected)
+    }
+
+    /*
+    Send the changes since the previous call, or a state change alone. Returns whether a record was sent
+    */
+    pub fn publish<M>(&mut self, book: &Orderbook<M>) -> Result<bool, MirrorError> {
+        let state = match self.synced {
+            None => return Err(MirrorError::NotSynced),
+            Some((price_factor, quantity_factor, _)) if price_factor != book.price_factor || quantity_factor != book.quantity_factor => {
+                self.handshake(book)?;
+                return Ok(true);
+            },
+            Some((_, _, state)) => state
+        };
+        let deltas = std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        if deltas.is_empty() && state == book.state() {
+            return Ok(false);
+        }
+        self.sequence += 1;
+        let mut frame = Vec::new();
+        self.encoder.encode(&deltas, &mut frame);
+        let mut record = vec![RECORD_UPDATE];
+        record.extend_from_slice(&self.sequence.to_le_bytes());
+        record.extend_from_slice(&book.timestamp.to_le_bytes());
+        record.push(state_code(book.state()));
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(&frame);
+        if self.verify_interval > 0 && self.sequence.is_multiple_of(self.verify_interval) {
+            record.push(RECORD_CHECK);
+            record.extend_from_slice(&self.sequence.to_le_bytes());
+            record.extend_from_slice(&book.state_hash().to_le_bytes());
+        }
+        self.stream.write_all(&record)?;
+        self.stream.flush()?;
+        self.synced = Some((book.price_factor, book.quantity_factor, book.state()));
+        Ok(true)
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/*
+Maintains a copy of the primary's book from its stream. Call receive in a loop; an Io error (end of
+stream, or a read timeout set on the socket) means the primary is gone, and take_over then yields the
+book in the primary's last published state. After a Sequence or HashMismatch error the book is marked
+Syncing until the primary handshakes again. The standby book should not have adaptive precision, a
+level TTL or pruning configured, as the primary's own pruning already arrives as changes
+*/
+pub struct MirrorStandby<S: Read + Write, M = ()> {
+    stream: S,
+    book: Orderbook<M>,
+    decoder: DeltaDecoder,
+    sequence: Option<u64>
+}
+
+impl<S: Read + Write, M> MirrorStandby<S, M> {
+    pub fn new(stream: S, book: Orderbook<M>) -> MirrorStandby<S, M> {
+        MirrorStandby {
+            stream,
+            book,
+            decoder: DeltaDecoder::new(),
+            sequence: None
+        }
+    }
+
+    /*
+    Read and apply one record, blocking until it arrives
+    */
+    pub fn receive(&mut self) -> Result<MirrorEvent, MirrorError> {
+        let kind = read_array::<_, 1>(&mut self.stream)?[0];
+        let sequence = u64::from_le_bytes(read_array(&mut self.stream)?);
+        match kind {
+            RECORD_SYNC => {
+                let length = u32::from_le_bytes(read_array(&mut self.stream)?) as usize;
+                let mut flat = vec![0; length];
+                self.stream.read_exact(&mut flat)?;
+                let snapshot = FlatSnapshot::new(&flat).map_err(MirrorError::Flat)?;
+                self.book.load_mirror(&snapshot);
+                self.decoder = DeltaDecoder::new();
+                self.sequence = Some(sequence);
+                let hash = self.book.state_hash();
+                let mut record = vec![RECORD_ACK];
+                record.extend_from_slice(&sequence.to_le_bytes());
+                record.extend_from_slice(&hash.to_le_bytes());
+                self.stream.write_all(&record)?;
+                self.stream.flush()?;
+                Ok(MirrorEvent::Synced { sequence, hash })
+            },
+            RECORD_UPDATE => {
+                let timestamp = u64::from_le_bytes(read_array(&mut self.stream)?);
+                let code = read_array::<_, 1>(&mut self.stream)?[0];
+                let length = u32::from_le_bytes(read_array(&mut self.stream)?) as usize;
+                let mut frame = vec![0; length];
+                self.stream.read_exact(&mut frame)?;
+                let state = state_from_code(code).ok_or(MirrorError::InvalidState(code))?;
+                let expected = self.sequence.ok_or(MirrorError::NotSynced)? + 1;
+                if sequence != expected {
+                    return Err(self.desync(MirrorError::Sequence { expected, found: sequence }));
+                }
+                let (deltas, _) = self.decoder.decode(&frame).map_err(|error| self.desync(MirrorError::Decode(error)))?;
+                self.book.timestamp = timestamp;
+                self.book.apply_wire_deltas(&deltas);
+                self.book.transition(state);
+                self.sequence = Some(sequence);
+                Ok(MirrorEvent::Updated { sequence, deltas: deltas.len() })
+            },
+            RECORD_CHECK => {
+                let expected = u32::from_le_bytes(read_array(&mut self.stream)?);
+                let hash = self.book.state_hash();
+                match self.sequence == Some(sequence) && hash == expected {
+                    true => Ok(MirrorEvent::Verified { sequence, hash }),
+                    false => Err(self.desync(MirrorError::HashMismatch { sequence, expected, found: hash }))
+                }
+            },
+            _ => Err(MirrorError::InvalidRecord(kind))
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook<M> {
+        &self.book
+    }
+
+    /*
+    Last applied sequence. None before the first sync or after losing sync
+    */
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /*
+    Promote the standby: stop mirroring and return the book to be fed directly
+    */
+    pub fn take_over(self) -> Orderbook<M> {
+        self.book
+    }
+
+    fn desync(&mut self, error: MirrorError) -> MirrorError {
+        self.sequence = None;
+        self.book.mark_gap();
+        error
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    CRC32 of the flat snapshot: levels, decimals, book clock and state. Equal books hash equally
+    */
+    pub fn state_hash(&self) -> u32 {
+        crate::crc32(&self.snapshot().to_flat())
+    }
+
+    /*
+    Replace the book with a primary's snapshot, adopting its decimals, clock and state. Levels are
+    loaded as scaled keys, so the mirror matches beyond the range f64 represents exactly
+    */
+    fn load_mirror(&mut self, snapshot: &FlatSnapshot) {
+        self.emit(BookEvent::Reset);
+        self.price_factor = snapshot.price_factor();
+        self.quantity_factor = snapshot.quantity_factor();
+        self.timestamp = snapshot.timestamp();
+        self.load_scaled_snapshot(snapshot.bids(), snapshot.asks());
+        self.transition(snapshot.state());
+    }
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Side;
+
+    #[test]
+    fn sync_keeps_scaled_levels_beyond_f64_precision() {
+        let mut primary = Orderbook::new(Some(2), Some(8));
+        primary.process(vec![(99.5, 1.0)], vec![(100.5, 2.0)], true);
+        primary.bids.insert((1 << 55) + 1, (1 << 54) + 1);
+        primary.asks.insert((1 << 55) + 3, 7);
+        primary.asks.insert(-(1 << 55) - 1, u64::MAX);
+        primary.timestamp = 42;
+        let flat = primary.snapshot().to_flat();
+        let snapshot = FlatSnapshot::new(&flat).map_err(MirrorError::Flat);
+        assert!(snapshot.is_ok());
+
+        let mut standby: Orderbook = Orderbook::new(Some(0), Some(0));
+        if let Ok(snapshot) = snapshot {
+            standby.load_mirror(&snapshot);
+        }
+        assert_eq!(standby.bids, primary.bids);
+        assert_eq!(standby.asks, primary.asks);
+        assert_eq!((standby.price_factor, standby.quantity_factor, standby.timestamp), (100.0, 1e8, 42));
+        assert_eq!(standby.state(), primary.state());
+        assert_eq!(standby.state_hash(), primary.state_hash());
+    }
+
+    // Replays recorded primary bytes and keeps what the standby writes back
+    struct Recorded {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>
+    }
+
+    impl Read for Recorded {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buffer)
+        }
+    }
+
+    impl Write for Recorded {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            self.output.write(buffer)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn updates_keep_scaled_levels_beyond_f64_precision() {
+        let mut primary = Orderbook::new(Some(2), Some(8));
+        primary.process(vec![(99.5, 1.0)], vec![(100.5, 2.0)], true);
+        let flat = primary.snapshot().to_flat();
+        let deltas = [
+            WireDelta::Set { side: Side::Bid, price: (1 << 55) + 1, quantity: (1 << 54) + 1 },
+            WireDelta::Set { side: Side::Ask, price: (1 << 55) + 3, quantity: u64::MAX }
+        ];
+        primary.bids.insert((1 << 55) + 1, (1 << 54) + 1);
+        primary.asks.insert((1 << 55) + 3, u64::MAX);
+        let mut frame = Vec::new();
+        DeltaEncoder::new().encode(&deltas, &mut frame);
+
+        let mut input = vec![RECORD_SYNC];
+        input.extend_from_slice(&1u64.to_le_bytes());
+        input.extend_from_slice(&(flat.len() as u32).to_le_bytes());
+        input.extend_from_slice(&flat);
+        input.push(RECORD_UPDATE);
+        input.extend_from_slice(&2u64.to_le_bytes());
+        input.extend_from_slice(&primary.timestamp.to_le_bytes());
+        input.push(state_code(primary.state()));
+        input.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        input.extend_from_slice(&frame);
+        input.push(RECORD_CHECK);
+        input.extend_from_slice(&2u64.to_le_bytes());
+        input.extend_from_slice(&primary.state_hash().to_le_bytes());
+
+        let stream = Recorded { input: io::Cursor::new(input), output: Vec::new() };
+        let mut standby = MirrorStandby::new(stream, Orderbook::new(Some(0), Some(0)));
+        for _ in 0..2 {
+            assert!(standby.receive().is_ok());
+        }
+        assert_eq!(standby.book().bids, primary.bids);
+        assert_eq!(standby.book().asks, primary.asks);
+        assert!(matches!(standby.receive(), Ok(MirrorEvent::Verified { sequence: 2, .. })));
+    }
+}
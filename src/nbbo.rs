@@ -0,0 +1,136 @@
+/*
+Purpose: National best bid and offer across venues with protected quote and flicker handling
+*/
+
+use std::collections::BTreeMap;
+
+use crate::Orderbook;
+
+/*
+Best prices with quantity aggregated over the protected venues quoting them, and those venue IDs
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nbbo {
+    pub bid: Option<(i64, u64)>,
+    pub ask: Option<(i64, u64)>,
+    pub bid_venues: Vec<u32>,
+    pub ask_venues: Vec<u32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VenueQuote {
+    pub bid: Option<(i64, u64)>,
+    pub ask: Option<(i64, u64)>,
+    pub protected: bool
+}
+
+/*
+Consolidates venue tops of book into an NBBO. Only protected quotes contribute.
+A changed NBBO is published once it has held for flicker_window (ms), so quotes that flicker
+away and back within the window never produce change events. Venue books must share price decimals
+*/
+pub struct NbboCalculator {
+    pub venues: BTreeMap<u32, VenueQuote>,
+    pub flicker_window: u64,
+    current: Option<Nbbo>,
+    pending: Option<(Nbbo, u64)>
+}
+
+impl NbboCalculator {
+    pub fn new(flicker_window: u64) -> NbboCalculator {
+        NbboCalculator {
+            venues: BTreeMap::new(),
+            flicker_window,
+            current: None,
+            pending: None
+        }
+    }
+
+    /*
+    Record a venue's top of book. Returns the new NBBO if this publishes a change
+    */
+    pub fn update_venue<M>(&mut self, venue: u32, book: &Orderbook<M>, protected: bool, now: u64) -> Option<Nbbo> {
+        self.venues.insert(venue, VenueQuote {
+            bid: book.get_best_bid(),
+            ask: book.get_best_ask(),
+            protected
+        });
+        self.evaluate(now)
+    }
+
+    pub fn remove_venue(&mut self, venue: u32, now: u64) -> Option<Nbbo> {
+        self.venues.remove(&venue);
+        self.evaluate(now)
+    }
+
+    /*
+    Publish a pending change whose flicker window has elapsed without further venue updates
+    */
+    pub fn poll(&mut self, now: u64) -> Option<Nbbo> {
+        self.evaluate(now)
+    }
+
+    /*
+    Last published NBBO
+    */
+    pub fn nbbo(&self) -> Option<&Nbbo> {
+        self.current.as_ref()
+    }
+
+    fn evaluate(&mut self, now: u64) -> Option<Nbbo> {
+        let candidate = self.compute();
+        if self.current.as_ref() == Some(&candidate) {
+            self.pending = None;
+            return None;
+        }
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == candidate => *since,
+            _ => now
+        };
+        if now.saturating_sub(since) < self.flicker_window {
+            self.pending = Some((candidate, since));
+            return None;
+        }
+        self.pending = None;
+        self.current = Some(candidate.clone());
+        Some(candidate)
+    }
+
+    fn compute(&self) -> Nbbo {
+        let mut nbbo = Nbbo {
+            bid: None,
+            ask: None,
+            bid_venues: Vec::new(),
+            ask_venues: Vec::new()
+        };
+        for (venue, quote) in self.venues.iter().filter(|(_, quote)| quote.protected) {
+            if let Some((price, quantity)) = quote.bid {
+                match nbbo.bid {
+                    Some((best, total)) if price == best => {
+                        nbbo.bid = Some((best, total.saturating_add(quantity)));
+                        nbbo.bid_venues.push(*venue);
+                    },
+                    Some((best, _)) if price < best => (),
+                    _ => {
+                        nbbo.bid = Some((price, quantity));
+                        nbbo.bid_venues = vec![*venue];
+                    }
+                }
+            }
+            if let Some((price, quantity)) = quote.ask {
+                match nbbo.ask {
+                    Some((best, total)) if price == best => {
+                        nbbo.ask = Some((best, total.saturating_add(quantity)));
+                        nbbo.ask_venues.push(*venue);
+                    },
+                    Some((best, _)) if price > best => (),
+                    _ => {
+                        nbbo.ask = Some((price, quantity));
+                        nbbo.ask_venues = vec![*venue];
+                    }
+                }
+            }
+        }
+        nbbo
+    }
+}
@@ -0,0 +1,329 @@
+/*
+Purpose: Notable book occurrences as OpenTelemetry spans, exported as OTLP/HTTP JSON traces
+*/
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use serde::Serialize;
+
+use crate::{millis_to_nanos, BookState, Orderbook};
+
+/*
+Resync: from a reported gap until the book is back in sync. Halt: while the venue halt lasts. Stale:
+while no updates arrive. Crossed: while best bid is at or above best ask. Burst: the update-rate bucket
+that reached the burst threshold
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BookIncident {
+    Resync,
+    Halt,
+    Stale,
+    Crossed,
+    Burst
+}
+
+impl BookIncident {
+    pub fn span_name(&self) -> &'static str {
+        match self {
+            BookIncident::Resync => "orderbook.resync",
+            BookIncident::Halt => "orderbook.halt",
+            BookIncident::Stale => "orderbook.stale",
+            BookIncident::Crossed => "orderbook.crossed",
+            BookIncident::Burst => "orderbook.burst"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpanValue {
+    Str(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool)
+}
+
+/*
+A finished incident between start and end on the book clock (ms since the Unix epoch)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncidentSpan {
+    pub incident: BookIncident,
+    pub start: u64,
+    pub end: u64,
+    pub attributes: Vec<(&'static str, SpanValue)>
+}
+
+/*
+Turns book state into incident spans by polling: call observe after each update, or on a timer for
+staleness, with the book clock. Incidents still open are reported once they end; bursts need update
+rate tracking on the book
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookTelemetry {
+    pub symbol: String,
+    state: BookState,
+    bursts: u64,
+    open: HashMap<BookIncident, IncidentSpan>,
+    finished: Vec<IncidentSpan>
+}
+
+impl BookTelemetry {
+    pub fn new(symbol: &str) -> BookTelemetry {
+        BookTelemetry {
+            symbol: symbol.to_string(),
+            state: BookState::Initializing,
+            bursts: 0,
+            open: HashMap::new(),
+            finished: Vec::new()
+        }
+    }
+
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) {
+        let state = book.state();
+        if state != self.state {
+            let previous = std::mem::replace(&mut self.state, state);
+            for (incident, from) in [(BookIncident::Resync, BookState::Syncing), (BookIncident::Halt, BookState::Halted), (BookIncident::Stale, BookState::Stale)] {
+                if previous == from {
+                    self.close(incident, timestamp, vec![("orderbook.state_after", SpanValue::Str(format!("{:?}", state)))]);
+                }
+                if state == from {
+                    self.start(incident, timestamp, vec![("orderbook.state_before", SpanValue::Str(format!("{:?}", previous)))]);
+                }
+            }
+        }
+        match (book.bids.last_key_value(), book.asks.first_key_value()) {
+            (Some((bid, _)), Some((ask, _))) if bid >= ask => {
+                if !self.open.contains_key(&BookIncident::Crossed) {
+                    self.start(BookIncident::Crossed, timestamp, vec![
+                        ("orderbook.best_bid", SpanValue::Double(book.unscale_price(*bid))),
+                        ("orderbook.best_ask", SpanValue::Double(book.unscale_price(*ask)))
+                    ]);
+                }
+            },
+            _ => self.close(BookIncident::Crossed, timestamp, Vec::new())
+        }
+        if let Some(update_rate) = book.update_rate.as_ref() {
+            if update_rate.bursts > self.bursts {
+                let (bucket, updates) = update_rate.buckets.back().copied().unwrap_or((timestamp, 0));
+                self.finished.push(IncidentSpan {
+                    incident: BookIncident::Burst,
+                    start: bucket,
+                    end: bucket.saturating_add(update_rate.bucket_ms),
+                    attributes: vec![
+                        ("orderbook.symbol", SpanValue::Str(self.symbol.clone())),
+                        ("orderbook.updates", SpanValue::Int(updates.min(i64::MAX as u64) as i64)),
+                        ("orderbook.bursts", SpanValue::Int((update_rate.bursts - self.bursts).min(i64::MAX as u64) as i64))
+                    ]
+                });
+            }
+            self.bursts = update_rate.bursts;
+        }
+    }
+
+    pub fn is_open(&self, incident: BookIncident) -> bool {
+        self.open.contains_key(&incident)
+    }
+
+    /*
+    Incidents finished since the last drain, in the order they ended
+    */
+    pub fn drain_spans(&mut self) -> Vec<IncidentSpan> {
+        std::mem::take(&mut self.finished)
+    }
+
+    fn start(&mut self, incident: BookIncident, timestamp: u64, mut attributes: Vec<(&'static str, SpanValue)>) {
+        attributes.insert(0, ("orderbook.symbol", SpanValue::Str(self.symbol.clone())));
+        self.open.insert(incident, IncidentSpan { incident, start: timestamp, end: timestamp, attributes });
+    }
+
+    fn close(&mut self, incident: BookIncident, timestamp: u64, attributes: Vec<(&'static str, SpanValue)>) {
+        if let Some(mut span) = self.open.remove(&incident) {
+            span.end = timestamp.max(span.start);
+            span.attributes.extend(attributes);
+            self.finished.push(span);
+        }
+    }
+}
+
+/*
+OTLP/HTTP JSON export of incident spans to a collector's /v1/traces (port 4318 by default). Each span
+gets its own trace unless a parent is set, in which case spans join that trace as its children, e.g. to
+hang them under the application's feed-handler span
+*/
+pub struct OtlpExporter {
+    pub host: String,
+    pub service_name: String,
+    pub parent: Option<(u128, u64)>,
+    address: Vec<std::net::SocketAddr>,
+    ids: RandomState,
+    sequence: u64
+}
+
+impl OtlpExporter {
+    pub fn new<A: ToSocketAddrs>(address: A, service_name: &str) -> io::Result<OtlpExporter> {
+        let address: Vec<std::net::SocketAddr> = address.to_socket_addrs()?.collect();
+        let host = match address.first() {
+            Some(first) => first.to_string(),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no collector address"))
+        };
+        Ok(OtlpExporter {
+            host,
+            service_name: service_name.to_string(),
+            parent: None,
+            address,
+            ids: RandomState::new(),
+            sequence: 0
+        })
+    }
+
+    /*
+    Export spans as children of span_id in trace_id
+    */
+    pub fn with_parent(mut self, trace_id: u128, span_id: u64) -> OtlpExporter {
+        self.parent = Some((trace_id, span_id));
+        self
+    }
+
+    /*
+    POST spans in one request. A non-2xx response becomes an io error
+    */
+    pub fn export(&mut self, spans: &[IncidentSpan]) -> io::Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        let body = serde_json::to_string(&self.request(spans))?;
+        let mut stream = TcpStream::connect(&self.address[..])?;
+        let head = format!(
+            "POST /v1/traces HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.host,
+            body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!("otlp export failed: {}", status.trim_end())))
+        }
+    }
+
+    fn request(&mut self, spans: &[IncidentSpan]) -> OtlpRequest {
+        let spans = spans.iter().map(|span| {
+            let (trace_id, parent_span_id) = match self.parent {
+                Some((trace_id, span_id)) => (trace_id, Some(format!("{:016x}", span_id))),
+                None => ((self.next_id() as u128) << 64 | self.next_id() as u128, None)
+            };
+            OtlpSpan {
+                trace_id: format!("{:032x}", trace_id),
+                span_id: format!("{:016x}", self.next_id()),
+                parent_span_id,
+                name: span.incident.span_name(),
+                kind: 1,
+                start_time_unix_nano: millis_to_nanos(span.start).to_string(),
+                end_time_unix_nano: millis_to_nanos(span.end).to_string(),
+                attributes: span.attributes.iter().map(|(key, value)| attribute(key, value)).collect()
+            }
+        }).collect();
+        OtlpRequest {
+            resource_spans: vec![OtlpResourceSpans {
+                resource: OtlpResource { attributes: vec![attribute("service.name", &SpanValue::Str(self.service_name.clone()))] },
+                scope_spans: vec![OtlpScopeSpans {
+                    scope: OtlpScope { name: "orderbook", version: env!("CARGO_PKG_VERSION") },
+                    spans
+                }]
+            }]
+        }
+    }
+
+    /*
+    Random non-zero id; ids of zero are invalid in OTLP
+    */
+    fn next_id(&mut self) -> u64 {
+        self.sequence += 1;
+        let mut hasher = self.ids.build_hasher();
+        hasher.write_u64(self.sequence);
+        hasher.finish().max(1)
+    }
+}
+
+fn attribute(key: &str, value: &SpanValue) -> OtlpAttribute {
+    OtlpAttribute {
+        key: key.to_string(),
+        value: match value {
+            SpanValue::Str(value) => OtlpValue::String(value.clone()),
+            SpanValue::Int(value) => OtlpValue::Int(value.to_string()),
+            SpanValue::Double(value) => OtlpValue::Double(*value),
+            SpanValue::Bool(value) => OtlpValue::Bool(*value)
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpRequest {
+    resource_spans: Vec<OtlpResourceSpans>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpResourceSpans {
+    resource: OtlpResource,
+    scope_spans: Vec<OtlpScopeSpans>
+}
+
+#[derive(Serialize)]
+struct OtlpResource {
+    attributes: Vec<OtlpAttribute>
+}
+
+#[derive(Serialize)]
+struct OtlpScopeSpans {
+    scope: OtlpScope,
+    spans: Vec<OtlpSpan>
+}
+
+#[derive(Serialize)]
+struct OtlpScope {
+    name: &'static str,
+    version: &'static str
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: &'static str,
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<OtlpAttribute>
+}
+
+#[derive(Serialize)]
+struct OtlpAttribute {
+    key: String,
+    value: OtlpValue
+}
+
+#[derive(Serialize)]
+enum OtlpValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    #[serde(rename = "intValue")]
+    Int(String),
+    #[serde(rename = "doubleValue")]
+    Double(f64),
+    #[serde(rename = "boolValue")]
+    Bool(bool)
+}
@@ -0,0 +1,116 @@
+/*
+Purpose: Taker simulation capped at a fraction of each level's displayed size
+*/
+
+use crate::{Orderbook, Side};
+
+/*
+Outcome of taking up to requested (unscaled) while consuming at most participation of each level.
+filled is the unscaled quantity obtainable, average_price its scaled average price and worst_price the
+scaled price of the last level touched (None when nothing fills); levels is the number of levels touched
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticipationFill {
+    pub side: Side,
+    pub requested: f64,
+    pub participation: f64,
+    pub filled: f64,
+    pub average_price: Option<f64>,
+    pub worst_price: Option<i64>,
+    pub levels: usize
+}
+
+impl ParticipationFill {
+    pub fn is_complete(&self) -> bool {
+        self.filled >= self.requested
+    }
+
+    /*
+    Share of the requested quantity the constraint allows
+    */
+    pub fn fill_ratio(&self) -> f64 {
+        match self.requested > 0.0 {
+            true => (self.filled / self.requested).min(1.0),
+            false => 0.0
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    simulate_taker_buy (Side::Bid) or simulate_taker_sell (Side::Ask) taking at most participation, in
+    (0, 1], of each level's displayed quantity. Unlike those, a partial fill is reported rather than None.
+    None for a non-finite or non-positive quantity, a participation outside (0, 1], or a book with
+    require_live set that does not accept taker orders
+    */
+    pub fn simulate_taker_capped(&self, side: Side, quantity: f64, participation: f64) -> Option<ParticipationFill> {
+        if !(quantity.is_finite() && quantity > 0.0 && participation > 0.0 && participation <= 1.0) {
+            return None;
+        }
+        if self.require_live && !self.accepts_taker_orders() {
+            return None;
+        }
+        let levels: Box<dyn Iterator<Item = (&i64, &u64)>> = match side {
+            Side::Bid => Box::new(self.asks.iter()),
+            Side::Ask => Box::new(self.bids.iter().rev())
+        };
+        let mut remaining = self.scale_qty(quantity);
+        let mut filled: u64 = 0;
+        let mut price_numerator: i128 = 0;
+        let mut worst_price = None;
+        let mut touched = 0;
+        for (price, level_quantity) in levels {
+            if remaining == 0 {
+                break;
+            }
+            let taken = participation_cap(*level_quantity, participation).min(remaining);
+            if taken == 0 {
+                continue;
+            }
+            price_numerator = price_numerator.saturating_add((taken as i128) * (*price as i128));
+            filled += taken;
+            remaining -= taken;
+            worst_price = Some(*price);
+            touched += 1;
+        }
+        Some(ParticipationFill {
+            side,
+            requested: quantity,
+            participation,
+            filled: self.unscale_qty(filled),
+            average_price: match filled {
+                0 => None,
+                filled => Some(price_numerator as f64 / filled as f64)
+            },
+            worst_price,
+            levels: touched
+        })
+    }
+
+    /*
+    Largest unscaled quantity takeable on side (Side::Bid buys from the asks) at participation of every
+    level, optionally only from levels priced no worse than limit_price (scaled)
+    */
+    pub fn achievable_quantity(&self, side: Side, participation: f64, limit_price: Option<i64>) -> f64 {
+        if !(participation > 0.0 && participation <= 1.0) {
+            return 0.0;
+        }
+        let total = match side {
+            Side::Bid => self.asks.range(..=limit_price.unwrap_or(i64::MAX))
+                .fold(0u64, |total, (_, quantity)| total.saturating_add(participation_cap(*quantity, participation))),
+            Side::Ask => self.bids.range(limit_price.unwrap_or(i64::MIN)..)
+                .fold(0u64, |total, (_, quantity)| total.saturating_add(participation_cap(*quantity, participation)))
+        };
+        self.unscale_qty(total)
+    }
+}
+
+/*
+Scaled quantity takeable from a level, rounded down so the cap is never exceeded
+*/
+fn participation_cap(quantity: u64, participation: f64) -> u64 {
+    match participation >= 1.0 {
+        true => quantity,
+        false => (quantity as f64 * participation).floor() as u64
+    }
+}
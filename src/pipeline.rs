@@ -0,0 +1,271 @@
+/*
+Purpose: Bounded feed to book thread pipeline with explicit overflow semantics
+*/
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use crate::{BookReader, Orderbook};
+
+/*
+One exchange message worth of levels, applied with Orderbook::process
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookUpdate {
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+    pub is_snapshot: bool
+}
+
+/*
+What the feed side does when the queue is full.
+Block waits for the book thread to make room.
+DropOldest discards the oldest queued update and requires a resync: the book thread marks a gap, so the
+book is Syncing, and skips deltas until the next snapshot, and FeedSender::resync_required tells the feed
+to fetch one.
+Conflate merges the update into the newest queued one, which is equivalent to applying both in order
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Block,
+    DropOldest,
+    Conflate
+}
+
+#[derive(Debug, Default)]
+pub struct PipelineMetrics {
+    pub processed: AtomicU64,
+    pub dropped: AtomicU64,
+    pub conflated: AtomicU64,
+    pub skipped: AtomicU64
+}
+
+struct QueueState {
+    updates: VecDeque<BookUpdate>,
+    resync_required: bool,
+    closed: bool
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    metrics: Arc<PipelineMetrics>
+}
+
+#[derive(Clone)]
+pub struct FeedSender {
+    shared: Arc<Shared>
+}
+
+impl FeedSender {
+    /*
+    Queue an update for the book thread. Returns false once the pipeline has been closed
+    */
+    pub fn send(&self, update: BookUpdate) -> bool {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.closed {
+            return false;
+        }
+        if state.updates.len() >= shared.capacity {
+            match shared.policy {
+                OverflowPolicy::Block => {
+                    while state.updates.len() >= shared.capacity && !state.closed {
+                        state = shared.not_full.wait(state).unwrap_or_else(PoisonError::into_inner);
+                    }
+                    if state.closed {
+                        return false;
+                    }
+                },
+                OverflowPolicy::DropOldest => {
+                    state.updates.pop_front();
+                    state.resync_required = true;
+                    shared.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                },
+                OverflowPolicy::Conflate => {
+                    if let Some(newest) = state.updates.back_mut() {
+                        if update.is_snapshot {
+                            *newest = update;
+                        } else {
+                            newest.bids.extend(update.bids);
+                            newest.asks.extend(update.asks);
+                        }
+                        shared.metrics.conflated.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                }
+            }
+        }
+        state.updates.push_back(update);
+        shared.not_empty.notify_one();
+        true
+    }
+
+    /*
+    True after DropOldest discarded an update and until the book thread has applied a snapshot
+    */
+    pub fn resync_required(&self) -> bool {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).resync_required
+    }
+
+    pub fn metrics(&self) -> Arc<PipelineMetrics> {
+        self.shared.metrics.clone()
+    }
+}
+
+/*
+Owns a book on a dedicated thread fed through a bounded queue. The book thread takes everything queued
+as one batch and applies it message by message. on_update runs on the book thread after each applied
+update; readers see the book only between batches
+*/
+pub struct Pipeline<M> {
+    sender: FeedSender,
+    reader: BookReader,
+    handle: JoinHandle<Orderbook<M>>
+}
+
+impl<M: Send + 'static> Pipeline<M> {
+    pub fn spawn<F>(mut book: Orderbook<M>, capacity: usize, policy: OverflowPolicy, mut on_update: F) -> Pipeline<M>
+    where F: FnMut(&Orderbook<M>) + Send + 'static {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                updates: VecDeque::with_capacity(capacity),
+                resync_required: false,
+                closed: false
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+            metrics: Arc::new(PipelineMetrics::default())
+        });
+        let worker_shared = shared.clone();
+        let reader = BookReader::new(&book);
+        let worker_reader = reader.clone();
+        let handle = thread::spawn(move || {
+            loop {
+                let (gap, batch) = {
+                    let mut state = worker_shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+                    while state.updates.is_empty() && !state.closed {
+                        state = worker_shared.not_empty.wait(state).unwrap_or_else(PoisonError::into_inner);
+                    }
+                    if state.updates.is_empty() {
+                        break;
+                    }
+                    let gap = state.resync_required;
+                    let queued: Vec<BookUpdate> = state.updates.drain(..).collect();
+                    worker_shared.not_full.notify_all();
+                    let mut batch = Vec::with_capacity(queued.len());
+                    for update in queued {
+                        if state.resync_required {
+                            if !update.is_snapshot {
+                                worker_shared.metrics.skipped.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            state.resync_required = false;
+                        }
+                        batch.push(update);
+                    }
+                    (gap, batch)
+                };
+                if gap {
+                    book.mark_gap();
+                } else if batch.is_empty() {
+                    continue;
+                }
+                for update in batch {
+                    book.process(update.bids, update.asks, update.is_snapshot);
+                    worker_shared.metrics.processed.fetch_add(1, Ordering::Relaxed);
+                    on_update(&book);
+                }
+                worker_reader.publish(&book);
+            }
+            book
+        });
+        Pipeline {
+            sender: FeedSender { shared },
+            reader,
+            handle
+        }
+    }
+
+    pub fn sender(&self) -> FeedSender {
+        self.sender.clone()
+    }
+
+    /*
+    Reader of the book's latest snapshot, published after each batch of queued updates
+    */
+    pub fn reader(&self) -> BookReader {
+        self.reader.clone()
+    }
+
+    /*
+    Close the queue, let the book thread drain what is already queued, and return the book
+    */
+    pub fn join(self) -> thread::Result<Orderbook<M>> {
+        {
+            let mut state = self.sender.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.closed = true;
+        }
+        self.sender.shared.not_empty.notify_all();
+        self.sender.shared.not_full.notify_all();
+        self.handle.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use crate::BookState;
+
+    use super::*;
+
+    fn update(bid: f64, is_snapshot: bool) -> BookUpdate {
+        BookUpdate { bids: vec![(bid, 1.0)], asks: vec![(bid + 1.0, 1.0)], is_snapshot }
+    }
+
+    fn wait_for_version(reader: &BookReader, version: u64) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reader.version() < version && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn drop_oldest_marks_a_gap_until_the_next_snapshot() {
+        let (entered, applying) = mpsc::channel();
+        let (release, gate) = mpsc::channel::<()>();
+        let pipeline = Pipeline::spawn(Orderbook::<()>::new(Some(2), Some(2)), 1, OverflowPolicy::DropOldest, move |_| {
+            let _ = entered.send(());
+            let _ = gate.recv();
+        });
+        let sender = pipeline.sender();
+        let reader = pipeline.reader();
+        assert!(sender.send(update(100.0, true)));
+        assert!(applying.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert!(sender.send(update(101.0, false)));
+        assert!(sender.send(update(102.0, false)));
+        assert!(sender.resync_required());
+        assert!(release.send(()).is_ok());
+        wait_for_version(&reader, 2);
+        assert_eq!(reader.load().state(), BookState::Syncing);
+        assert_eq!(reader.load().get_best_bid(), Some((10_000, 100)));
+        assert_eq!(sender.metrics().skipped.load(Ordering::Relaxed), 1);
+
+        assert!(release.send(()).is_ok());
+        assert!(sender.send(update(103.0, true)));
+        wait_for_version(&reader, 3);
+        assert_eq!(reader.load().state(), BookState::Live);
+        assert_eq!(reader.load().get_best_bid(), Some((10_300, 100)));
+        assert!(!sender.resync_required());
+        assert!(pipeline.join().is_ok());
+    }
+}
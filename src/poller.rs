@@ -0,0 +1,183 @@
+/*
+Purpose: Polling connector turning successive REST depth snapshots into deltas for the pipeline
+*/
+
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::{diff_snapshots, BookSnapshot, BookState, BookUpdate, ConfigError, FeedSender, Side, WireDelta};
+use crate::l2::{checked_decimal_factor, decimal_factor, is_valid_level, scale, scale_signed, unscale, unscale_signed};
+
+/*
+Unscaled (price, quantity) bids and asks of one fetch
+*/
+pub type FetchedDepth = (Vec<(f64, f64)>, Vec<(f64, f64)>);
+
+/*
+A venue's full depth on request, e.g. a REST depth endpoint. Any closure returning it is a source
+*/
+pub trait SnapshotSource {
+    fn fetch(&mut self) -> io::Result<FetchedDepth>;
+}
+
+impl<F> SnapshotSource for F
+where
+    F: FnMut() -> io::Result<FetchedDepth>
+{
+    fn fetch(&mut self) -> io::Result<FetchedDepth> {
+        self()
+    }
+}
+
+/*
+Fetches from source at most every interval ms and diffs each result against the previous one with the
+book's decimals, so a venue without streaming depth produces the same snapshot-then-deltas updates as
+one with it. The first poll, and the first after request_snapshot, yields a snapshot. A failed fetch is
+returned and the next poll diffs against the last good one. Levels the venue omits beyond its depth limit
+show up as removals
+*/
+pub struct SnapshotPoller<S: SnapshotSource> {
+    pub interval: u64,
+    source: S,
+    price_factor: f64,
+    quantity_factor: f64,
+    previous: Option<BookSnapshot>,
+    next_poll: u64,
+    polls: u64,
+    failures: u64
+}
+
+impl<S: SnapshotSource> SnapshotPoller<S> {
+    /*
+    Panics if either decimals setting exceeds MAX_DECIMALS; see try_new
+    */
+    pub fn new(source: S, price_decimals: Option<u8>, quantity_decimals: Option<u8>, interval: u64) -> SnapshotPoller<S> {
+        SnapshotPoller {
+            interval,
+            source,
+            price_factor: decimal_factor(price_decimals),
+            quantity_factor: decimal_factor(quantity_decimals),
+            previous: None,
+            next_poll: 0,
+            polls: 0,
+            failures: 0
+        }
+    }
+
+    pub fn try_new(source: S, price_decimals: Option<u8>, quantity_decimals: Option<u8>, interval: u64) -> Result<SnapshotPoller<S>, ConfigError> {
+        Ok(SnapshotPoller {
+            interval,
+            source,
+            price_factor: checked_decimal_factor("price_decimals", price_decimals)?,
+            quantity_factor: checked_decimal_factor("quantity_decimals", quantity_decimals)?,
+            previous: None,
+            next_poll: 0,
+            polls: 0,
+            failures: 0
+        })
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        now >= self.next_poll
+    }
+
+    /*
+    Book clock time (ms) of the next poll
+    */
+    pub fn next_poll(&self) -> u64 {
+        self.next_poll
+    }
+
+    /*
+    Make the next poll yield a full snapshot, e.g. when the pipeline requires a resync
+    */
+    pub fn request_snapshot(&mut self) {
+        self.previous = None;
+    }
+
+    /*
+    Fetch if due. None when not due or when nothing changed since the previous poll
+    */
+    pub fn poll(&mut self, now: u64) -> io::Result<Option<BookUpdate>> {
+        if !self.is_due(now) {
+            return Ok(None);
+        }
+        self.next_poll = now.saturating_add(self.interval);
+        self.polls += 1;
+        let (bids, asks) = self.source.fetch().inspect_err(|_| self.failures += 1)?;
+        let snapshot = self.scale(bids, asks, now);
+        let update = match self.previous.as_ref() {
+            None => Some(BookUpdate {
+                bids: snapshot.bids().iter().map(|level| self.unscale(*level)).collect(),
+                asks: snapshot.asks().iter().map(|level| self.unscale(*level)).collect(),
+                is_snapshot: true
+            }),
+            Some(previous) => {
+                let mut update = BookUpdate { bids: Vec::new(), asks: Vec::new(), is_snapshot: false };
+                for delta in diff_snapshots(previous, &snapshot) {
+                    let (side, level) = match delta {
+                        WireDelta::Set { side, price, quantity } => (side, self.unscale((price, quantity))),
+                        WireDelta::Remove { side, price } => (side, self.unscale((price, 0))),
+                        WireDelta::Clear => continue
+                    };
+                    match side {
+                        Side::Bid => update.bids.push(level),
+                        Side::Ask => update.asks.push(level)
+                    }
+                }
+                Some(update).filter(|update| !update.bids.is_empty() || !update.asks.is_empty())
+            }
+        };
+        self.previous = Some(snapshot);
+        Ok(update)
+    }
+
+    /*
+    poll and queue the result on sender, switching to a snapshot while the pipeline requires a resync.
+    Returns whether an update was queued
+    */
+    pub fn poll_into(&mut self, sender: &FeedSender, now: u64) -> io::Result<bool> {
+        if sender.resync_required() && self.is_due(now) {
+            self.request_snapshot();
+        }
+        match self.poll(now)? {
+            Some(update) => Ok(sender.send(update)),
+            None => Ok(false)
+        }
+    }
+
+    /*
+    Polls attempted and how many of them failed to fetch
+    */
+    pub fn polls(&self) -> (u64, u64) {
+        (self.polls, self.failures)
+    }
+
+    /*
+    Fetched levels scaled and merged per price, zero quantities and invalid levels dropped
+    */
+    fn scale(&self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, now: u64) -> BookSnapshot {
+        let levels = |levels: Vec<(f64, f64)>| {
+            let mut scaled = BTreeMap::new();
+            for (price, quantity) in levels.into_iter().filter(is_valid_level) {
+                let quantity = scale(quantity, self.quantity_factor);
+                if quantity > 0 {
+                    scaled.insert(scale_signed(price, self.price_factor), quantity);
+                }
+            }
+            scaled
+        };
+        BookSnapshot::from_parts(
+            levels(bids).into_iter().rev().collect(),
+            levels(asks).into_iter().collect(),
+            now,
+            BookState::Live,
+            self.price_factor,
+            self.quantity_factor
+        )
+    }
+
+    fn unscale(&self, (price, quantity): (i64, u64)) -> (f64, f64) {
+        (unscale_signed(price, self.price_factor), unscale(quantity, self.quantity_factor))
+    }
+}
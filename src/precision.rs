@@ -0,0 +1,162 @@
+/*
+Purpose: Adaptive price and quantity precision inferred from feed values, with in-place rescaling
+*/
+
+use crate::events::rescale_tree;
+use crate::l2::checked_decimal_factor;
+use crate::{BookEvent, ConfigError, Orderbook, MAX_DECIMALS};
+
+/*
+Fewest decimals that represent value up to float noise, MAX_DECIMALS for anything finer
+*/
+pub fn value_decimals(value: f64) -> u8 {
+    (0..=MAX_DECIMALS).find(|decimals| fits(value, 10f64.powi(*decimals as i32))).unwrap_or(MAX_DECIMALS)
+}
+
+/*
+Adaptive mode state. The book starts at its configured decimals and refines them whenever an incoming
+price or quantity needs more, rescaling the levels already held. Decimals never shrink. Refinements
+after the first sample_size levels are counted in late_rescales: they cost a full rescale of the book
+and usually mean the configured or reference decimals were too coarse
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptivePrecision {
+    pub sample_size: usize,
+    pub observed: usize,
+    pub late_rescales: u64
+}
+
+impl Orderbook {
+    /*
+    Adaptive book starting from whole prices and quantities; see AdaptivePrecision
+    */
+    pub fn adaptive(sample_size: usize) -> Orderbook {
+        let mut book = Orderbook::new(Some(0), Some(0));
+        book.enable_adaptive_precision(sample_size);
+        book
+    }
+}
+
+impl<M> Orderbook<M> {
+    pub fn enable_adaptive_precision(&mut self, sample_size: usize) {
+        self.adaptive_precision = Some(AdaptivePrecision { sample_size, observed: 0, late_rescales: 0 });
+    }
+
+    pub fn price_decimals(&self) -> u8 {
+        self.price_factor.log10().round() as u8
+    }
+
+    pub fn quantity_decimals(&self) -> u8 {
+        self.quantity_factor.log10().round() as u8
+    }
+
+    /*
+    Raise the book's decimals to at least the given ones, e.g. from late-arriving reference data, rescaling
+    existing levels, order counts, update times, payloads, the price band, parked window levels and pruned_quantity. Projections
+    receive Rescaled with the multipliers and rescale their own state. Returns whether anything changed
+    */
+    pub fn refine_precision(&mut self, price_decimals: u8, quantity_decimals: u8) -> Result<bool, ConfigError> {
+        let price_decimals = price_decimals.max(self.price_decimals());
+        let quantity_decimals = quantity_decimals.max(self.quantity_decimals());
+        let price_factor = checked_decimal_factor("price_decimals", Some(price_decimals))?;
+        let quantity_factor = checked_decimal_factor("quantity_decimals", Some(quantity_decimals))?;
+        let price_multiplier = 10i64.pow((price_decimals - self.price_decimals()) as u32);
+        let quantity_multiplier = 10u64.pow((quantity_decimals - self.quantity_decimals()) as u32);
+        if price_multiplier == 1 && quantity_multiplier == 1 {
+            return Ok(false);
+        }
+        let price = |price: i64| price.saturating_mul(price_multiplier);
+        rescale_tree(&mut self.bids, price_multiplier, quantity_multiplier);
+        rescale_tree(&mut self.asks, price_multiplier, quantity_multiplier);
+        rescale_tree(&mut self.bid_update_times, price_multiplier, 1);
+        rescale_tree(&mut self.ask_update_times, price_multiplier, 1);
+        for counts in [&mut self.bid_order_counts, &mut self.ask_order_counts] {
+            *counts = std::mem::take(counts).into_iter().map(|(key, count)| (price(key), count)).collect();
+        }
+        for meta in [&mut self.bid_meta, &mut self.ask_meta] {
+            *meta = std::mem::take(meta).into_iter().map(|(key, payload)| (price(key), payload)).collect();
+        }
+        if let Some(band) = self.price_band.as_mut() {
+            band.reference_price = price(band.reference_price);
+            band.lower = price(band.lower);
+            band.upper = price(band.upper);
+        }
+        if let Some(window) = self.price_window.as_mut() {
+            rescale_tree(&mut window.bids, price_multiplier, quantity_multiplier);
+            rescale_tree(&mut window.asks, price_multiplier, quantity_multiplier);
+            window.bounds = window.bounds.map(|(lowest, highest)| (price(lowest), price(highest)));
+            window.outer = window.outer.map(|(lowest, highest)| (price(lowest), price(highest)));
+        }
+        self.pruned_quantity = self.pruned_quantity.saturating_mul(quantity_multiplier);
+        self.price_factor = price_factor;
+        self.quantity_factor = quantity_factor;
+        self.emit(BookEvent::Rescaled { price_multiplier, quantity_multiplier });
+        Ok(true)
+    }
+
+    /*
+    In adaptive mode, refine precision for incoming levels before they are scaled
+    */
+    pub(crate) fn adapt_precision(&mut self, bids: &[(f64, f64)], asks: &[(f64, f64)]) {
+        let mut adaptive = match self.adaptive_precision {
+            Some(adaptive) => adaptive,
+            None => return
+        };
+        let (mut price_decimals, mut quantity_decimals) = (self.price_decimals(), self.quantity_decimals());
+        for (price, quantity) in bids.iter().chain(asks.iter()).filter(|level| level.0.is_finite() && level.1.is_finite()) {
+            if !fits(*price, self.price_factor) {
+                price_decimals = price_decimals.max(value_decimals(*price));
+            }
+            if !fits(*quantity, self.quantity_factor) {
+                quantity_decimals = quantity_decimals.max(value_decimals(*quantity));
+            }
+        }
+        let settled = adaptive.observed >= adaptive.sample_size;
+        adaptive.observed = adaptive.observed.saturating_add(bids.len() + asks.len());
+        if self.refine_precision(price_decimals, quantity_decimals).unwrap_or(false) && settled {
+            adaptive.late_rescales += 1;
+        }
+        self.adaptive_precision = Some(adaptive);
+    }
+}
+
+/*
+value lands on a scaled increment at factor up to float noise, without a non-zero value rounding to zero
+*/
+fn fits(value: f64, factor: f64) -> bool {
+    let scaled = value * factor;
+    (scaled - scaled.round()).abs() < 1e-9 * scaled.abs().max(1.0) && (scaled.round() != 0.0 || value == 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancelAssumption, FlowKind, LevelHistory, LevelObservation, QueueInference, Side};
+
+    #[test]
+    fn rescale_carries_price_keyed_projections_over() {
+        let mut book = Orderbook::adaptive(0);
+        book.process(vec![(99.0, 2.0)], vec![(101.0, 1.0)], true);
+        book.add_projection(QueueInference::new(1000, 16, CancelAssumption::FromBack));
+        book.add_projection(LevelHistory::new(8, &[99]));
+        let order = book.projection_mut::<QueueInference>().map(|queue| {
+            queue.drain_flows();
+            queue.track_order(Side::Bid, 99, 1)
+        });
+
+        book.process(vec![(99.5, 0.5)], Vec::new(), false);
+        assert_eq!((book.price_decimals(), book.quantity_decimals()), (1, 1));
+        let queue = book.projection_mut::<QueueInference>();
+        let flows = queue.map(|queue| queue.drain_flows().iter().map(|flow| (flow.price, flow.kind, flow.quantity)).collect::<Vec<_>>());
+        assert_eq!(flows, Some(vec![(995, FlowKind::Add, 5)]));
+        let position = order.and_then(|order| book.projection::<QueueInference>()?.position(order));
+        assert_eq!(position.map(|position| (position.price, position.quantity, position.ahead)), Some((990, 10, 20)));
+
+        let history = book.projection::<LevelHistory>();
+        assert_eq!(history.map(|history| history.is_watched(99)), Some(false));
+        let observations = history.and_then(|history| history.history(990)).map(|history| history.iter().copied().collect::<Vec<_>>());
+        assert_eq!(observations, Some(vec![LevelObservation { timestamp: 0, side: Some(Side::Bid), quantity: 20 }]));
+        book.process(vec![(99.0, 1.0)], Vec::new(), false);
+        assert_eq!(book.projection::<LevelHistory>().and_then(|history| history.latest(990)).map(|latest| latest.quantity), Some(10));
+    }
+}
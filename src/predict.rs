@@ -0,0 +1,95 @@
+/*
+Purpose: Harness for evaluating pluggable mid-price predictors against realized book moves
+*/
+
+use std::collections::VecDeque;
+
+use crate::Orderbook;
+
+/*
+Produces a prediction of the unscaled mid price change over the harness horizon after observing the book.
+Return None to skip a prediction for this observation
+*/
+pub trait Predictor<M = ()> {
+    fn predict(&mut self, book: &Orderbook<M>, timestamp: u64) -> Option<f64>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PredictionScores {
+    pub evaluated: u64,
+    pub hits: u64,
+    pub squared_error: f64
+}
+
+impl PredictionScores {
+    /*
+    Fraction of predictions whose direction matched the realized move, flat moves matching flat predictions
+    */
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.evaluated {
+            0 => None,
+            evaluated => Some((self.hits as f64) / (evaluated as f64))
+        }
+    }
+
+    pub fn mean_squared_error(&self) -> Option<f64> {
+        match self.evaluated {
+            0 => None,
+            evaluated => Some(self.squared_error / (evaluated as f64))
+        }
+    }
+}
+
+/*
+Feeds each observed book to the predictor and scores every prediction against the mid price change
+seen at the first observation at least horizon (ms) later. Observations without a two-sided book are skipped
+*/
+pub struct PredictorHarness<P> {
+    pub predictor: P,
+    pub horizon: u64,
+    pub scores: PredictionScores,
+    pending: VecDeque<(u64, f64, f64)>
+}
+
+impl<P> PredictorHarness<P> {
+    pub fn new(predictor: P, horizon: u64) -> PredictorHarness<P> {
+        PredictorHarness {
+            predictor,
+            horizon,
+            scores: PredictionScores::default(),
+            pending: VecDeque::new()
+        }
+    }
+
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) where P: Predictor<M> {
+        let mid_price = match book.summary(Some(1)).mid_price {
+            Some(mid_price) => mid_price / book.price_factor,
+            None => return
+        };
+        while let Some((due, prediction, start_mid_price)) = self.pending.front().copied() {
+            if due > timestamp {
+                break;
+            }
+            self.pending.pop_front();
+            let realized = mid_price - start_mid_price;
+            let hit = (prediction > 0.0 && realized > 0.0)
+                || (prediction < 0.0 && realized < 0.0)
+                || (prediction == 0.0 && realized == 0.0);
+            self.scores.evaluated += 1;
+            if hit {
+                self.scores.hits += 1;
+            }
+            self.scores.squared_error += (prediction - realized).powi(2);
+        }
+        if let Some(prediction) = self.predictor.predict(book, timestamp) {
+            self.pending.push_back((timestamp.saturating_add(self.horizon), prediction, mid_price));
+        }
+    }
+
+    /*
+    Number of predictions still waiting for their horizon to elapse
+    */
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
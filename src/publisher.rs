@@ -0,0 +1,321 @@
+/*
+Purpose: Downstream publication helpers for book views
+*/
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::{BookSnapshot, Orderbook, Side};
+
+/*
+Best depth levels per side, bids best first and asks best first, as scaled (price, quantity)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthView {
+    pub depth: usize,
+    pub bids: Vec<(i64, u64)>,
+    pub asks: Vec<(i64, u64)>
+}
+
+impl DepthView {
+    pub fn from_book<M>(book: &Orderbook<M>, depth: usize) -> DepthView {
+        DepthView {
+            depth,
+            bids: book.bids.iter().rev().take(depth).map(|(p, q)| (*p, *q)).collect(),
+            asks: book.asks.iter().take(depth).map(|(p, q)| (*p, *q)).collect()
+        }
+    }
+
+    fn matches<M>(&self, book: &Orderbook<M>) -> bool {
+        self.bids.iter().copied().eq(book.bids.iter().rev().take(self.depth).map(|(p, q)| (*p, *q)))
+            && self.asks.iter().copied().eq(book.asks.iter().take(self.depth).map(|(p, q)| (*p, *q)))
+    }
+}
+
+/*
+Maintains one view per resolution (e.g. L1, L5, L25) and only yields a view when the levels
+within that resolution changed since it was last published
+*/
+pub struct DepthPublisher {
+    pub resolutions: Vec<usize>,
+    last_views: Vec<Option<DepthView>>
+}
+
+impl DepthPublisher {
+    pub fn new(resolutions: Vec<usize>) -> DepthPublisher {
+        let last_views = vec![None; resolutions.len()];
+        DepthPublisher {
+            resolutions,
+            last_views
+        }
+    }
+
+    /*
+    Views whose contents changed since the previous update, in resolution order
+    */
+    pub fn update<M>(&mut self, book: &Orderbook<M>) -> Vec<DepthView> {
+        let mut changed: Vec<DepthView> = Vec::new();
+        for (depth, last_view) in self.resolutions.iter().zip(self.last_views.iter_mut()) {
+            if let Some(view) = last_view {
+                if view.matches(book) {
+                    continue;
+                }
+            }
+            let view = DepthView::from_book(book, *depth);
+            changed.push(view.clone());
+            *last_view = Some(view);
+        }
+        changed
+    }
+}
+
+impl Default for DepthPublisher {
+    fn default() -> DepthPublisher {
+        DepthPublisher::new(vec![1, 5, 25])
+    }
+}
+
+/*
+Coalesces values offered faster than a consumer reads them. Only the latest value is kept, and poll
+delivers it at most once per interval (ms). conflated counts values replaced before delivery
+*/
+pub struct Conflator<T> {
+    pub interval: u64,
+    pub conflated: u64,
+    latest: Option<T>,
+    last_delivery: Option<u64>
+}
+
+impl<T> Conflator<T> {
+    pub fn new(interval: u64) -> Conflator<T> {
+        Conflator {
+            interval,
+            conflated: 0,
+            latest: None,
+            last_delivery: None
+        }
+    }
+
+    pub fn offer(&mut self, value: T) {
+        if self.latest.replace(value).is_some() {
+            self.conflated += 1;
+        }
+    }
+
+    pub fn poll(&mut self, now: u64) -> Option<T> {
+        if let Some(last_delivery) = self.last_delivery {
+            if now.saturating_sub(last_delivery) < self.interval {
+                return None;
+            }
+        }
+        let value = self.latest.take()?;
+        self.last_delivery = Some(now);
+        Some(value)
+    }
+}
+
+/*
+Fans published values out to consumers with independent conflation intervals, sharing one allocation per value
+*/
+pub struct ConflationHub<T> {
+    pub consumers: Vec<Conflator<Arc<T>>>
+}
+
+impl<T> ConflationHub<T> {
+    pub fn new() -> ConflationHub<T> {
+        ConflationHub {
+            consumers: Vec::new()
+        }
+    }
+
+    /*
+    Register a consumer and return its index for polling
+    */
+    pub fn add_consumer(&mut self, interval: u64) -> usize {
+        self.consumers.push(Conflator::new(interval));
+        self.consumers.len() - 1
+    }
+
+    pub fn publish(&mut self, value: T) {
+        let value = Arc::new(value);
+        for consumer in self.consumers.iter_mut() {
+            consumer.offer(value.clone());
+        }
+    }
+
+    pub fn poll(&mut self, consumer: usize, now: u64) -> Option<Arc<T>> {
+        self.consumers.get_mut(consumer)?.poll(now)
+    }
+}
+
+impl<T> Default for ConflationHub<T> {
+    fn default() -> ConflationHub<T> {
+        ConflationHub::new()
+    }
+}
+
+/*
+Row edits for a ladder of the best levels, indexed from the best price.
+Applying a batch in order to the previous rows yields the current rows
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowChange {
+    Inserted { side: Side, index: usize, price: i64, quantity: u64 },
+    Removed { side: Side, index: usize, price: i64 },
+    Updated { side: Side, index: usize, price: i64, quantity: u64 }
+}
+
+/*
+Tracks the best depth rows per side and reports what changed since the previous update
+*/
+pub struct TopNTracker {
+    pub depth: usize,
+    bids: Vec<(i64, u64)>,
+    asks: Vec<(i64, u64)>
+}
+
+impl TopNTracker {
+    pub fn new(depth: usize) -> TopNTracker {
+        TopNTracker {
+            depth,
+            bids: Vec::new(),
+            asks: Vec::new()
+        }
+    }
+
+    pub fn update<M>(&mut self, book: &Orderbook<M>) -> Vec<RowChange> {
+        let bids: Vec<(i64, u64)> = book.bids.iter().rev().take(self.depth).map(|(p, q)| (*p, *q)).collect();
+        let asks: Vec<(i64, u64)> = book.asks.iter().take(self.depth).map(|(p, q)| (*p, *q)).collect();
+        let mut changes: Vec<RowChange> = Vec::new();
+        diff_rows(&mut changes, Side::Bid, &self.bids, &bids);
+        diff_rows(&mut changes, Side::Ask, &self.asks, &asks);
+        self.bids = bids;
+        self.asks = asks;
+        changes
+    }
+}
+
+fn diff_rows(changes: &mut Vec<RowChange>, side: Side, previous: &[(i64, u64)], current: &[(i64, u64)]) {
+    let is_better = |a: i64, b: i64| match side {
+        Side::Bid => a > b,
+        Side::Ask => a < b
+    };
+    let mut index: usize = 0;
+    let mut old_rows = previous.iter().peekable();
+    let mut new_rows = current.iter().peekable();
+    loop {
+        match (old_rows.peek(), new_rows.peek()) {
+            (Some((old_price, old_quantity)), Some((new_price, new_quantity))) => {
+                if old_price == new_price {
+                    if old_quantity != new_quantity {
+                        changes.push(RowChange::Updated { side, index, price: *new_price, quantity: *new_quantity });
+                    }
+                    old_rows.next();
+                    new_rows.next();
+                    index += 1;
+                } else if is_better(*old_price, *new_price) {
+                    changes.push(RowChange::Removed { side, index, price: *old_price });
+                    old_rows.next();
+                } else {
+                    changes.push(RowChange::Inserted { side, index, price: *new_price, quantity: *new_quantity });
+                    new_rows.next();
+                    index += 1;
+                }
+            },
+            (Some((old_price, _)), None) => {
+                changes.push(RowChange::Removed { side, index, price: *old_price });
+                old_rows.next();
+            },
+            (None, Some((new_price, new_quantity))) => {
+                changes.push(RowChange::Inserted { side, index, price: *new_price, quantity: *new_quantity });
+                new_rows.next();
+                index += 1;
+            },
+            (None, None) => break
+        }
+    }
+}
+
+/*
+Top rows changed since the previous publication, with the top rows after the change
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthDelta {
+    pub changes: Vec<RowChange>,
+    pub top: BookSnapshot
+}
+
+/*
+A transport backend. Sends may buffer; flush is called once per PublishFanout::publish
+*/
+pub trait Publisher {
+    fn send_snapshot(&mut self, symbol: &str, snapshot: &BookSnapshot) -> io::Result<()>;
+    fn send_delta(&mut self, symbol: &str, delta: &DepthDelta) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/*
+Change detection and snapshot cadence shared by all transports. For each symbol, whenever its best
+depth rows change, every transport receives the delta, and a top-depth snapshot at most every
+snapshot_interval ms of book clock (always with the first publication) so consumers can resync
+*/
+pub struct PublishFanout {
+    pub depth: usize,
+    pub snapshot_interval: u64,
+    transports: Vec<Box<dyn Publisher + Send>>,
+    symbols: HashMap<String, (TopNTracker, Option<u64>)>
+}
+
+impl PublishFanout {
+    pub fn new(depth: usize, snapshot_interval: u64) -> PublishFanout {
+        PublishFanout {
+            depth,
+            snapshot_interval,
+            transports: Vec::new(),
+            symbols: HashMap::new()
+        }
+    }
+
+    pub fn add_transport(&mut self, transport: Box<dyn Publisher + Send>) {
+        self.transports.push(transport);
+    }
+
+    /*
+    Publish symbol's changes since the previous call. Returns whether anything was sent.
+    Every transport is attempted; the first error is returned
+    */
+    pub fn publish<M>(&mut self, symbol: &str, book: &Orderbook<M>) -> io::Result<bool> {
+        let depth = self.depth;
+        let (tracker, last_snapshot) = self.symbols.entry(symbol.to_string()).or_insert_with(|| (TopNTracker::new(depth), None));
+        let changes = tracker.update(book);
+        if changes.is_empty() && last_snapshot.is_some() {
+            return Ok(false);
+        }
+        let top = book.snapshot_depth(depth);
+        let snapshot_due = last_snapshot.is_none_or(|last| book.timestamp.saturating_sub(last) >= self.snapshot_interval);
+        if snapshot_due {
+            *last_snapshot = Some(book.timestamp);
+        }
+        let delta = match changes.is_empty() {
+            true => None,
+            false => Some(DepthDelta { changes, top: top.clone() })
+        };
+        let mut result = Ok(true);
+        for transport in self.transports.iter_mut() {
+            let sent = match &delta {
+                Some(delta) => transport.send_delta(symbol, delta),
+                None => Ok(())
+            }
+            .and_then(|_| match snapshot_due {
+                true => transport.send_snapshot(symbol, &top),
+                false => Ok(())
+            })
+            .and_then(|_| transport.flush());
+            if let (Err(error), Ok(_)) = (sent, &result) {
+                result = Err(error);
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,337 @@
+/*
+Purpose: Inferred level-3 queue flows from L2 updates and trades, and queue-position estimates built on them
+*/
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{BookEvent, Projection, Side};
+
+/*
+Add: quantity joined the back of the level. Cancel: quantity left without trading. Trade: quantity
+executed at the front of the level
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowKind {
+    Add,
+    Cancel,
+    Trade
+}
+
+/*
+One inferred change at a level, in scaled price and quantity. level_before is the displayed quantity
+before the change; one update can produce several flows, applied in order
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelFlow {
+    pub side: Side,
+    pub price: i64,
+    pub kind: FlowKind,
+    pub quantity: u64,
+    pub level_before: u64,
+    pub timestamp: u64
+}
+
+/*
+Scaled quantity per flow kind since the inference was created
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowTotals {
+    pub added: u64,
+    pub cancelled: u64,
+    pub traded: u64
+}
+
+/*
+How cancels that are not ours are spread over the queue. ProRata assumes they are uniform over the
+displayed quantity ahead of and behind the order; FromBack assumes they all come from behind it, the
+conservative choice that only trades advance the order
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelAssumption {
+    ProRata,
+    FromBack
+}
+
+/*
+An order's estimated place in its level: ahead is the scaled quantity in front of it, filled the scaled
+quantity of it executed so far
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePosition {
+    pub side: Side,
+    pub price: i64,
+    pub quantity: u64,
+    pub ahead: u64,
+    pub filled: u64
+}
+
+impl QueuePosition {
+    pub fn remaining(&self) -> u64 {
+        self.quantity.saturating_sub(self.filled)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Execution {
+    quantity: u64,
+    timestamp: u64
+}
+
+/*
+Projection decomposing L2 size changes into add, cancel and trade flows, with a queue-position
+estimator fed by them. Assumptions, since L2 shows only the total at each price:
+- trades are recorded with record_trade before the book update they caused, at the resting level's
+  price; executions not explained by an update within trade_window ms are discarded
+- a decrease is attributed to pending trades at the level first and to cancels for the remainder
+- an increase is an add at the back of the queue; an increase despite a pending trade is a trade
+  followed by an add (e.g. an iceberg refill)
+- levels re-listed at the same book time as a Cleared (a snapshot) are diffed against their quantity
+  before it, so a resync does not show up as flow
+- trades consume the queue strictly in time priority; cancels follow the CancelAssumption
+A rescale of the book's decimals rescales levels, pending executions, tracked orders, retained flows and
+totals alike. Flows are kept in a ring of the most recent capacity for drain_flows. Registering on a non-empty book
+reports its levels as adds, counted in totals; drain_flows after add_projection to discard them
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueInference {
+    pub trade_window: u64,
+    pub capacity: usize,
+    pub cancel_assumption: CancelAssumption,
+    flows: VecDeque<LevelFlow>,
+    bid_totals: FlowTotals,
+    ask_totals: FlowTotals,
+    levels: HashMap<(Side, i64), u64>,
+    executions: HashMap<(Side, i64), Execution>,
+    cleared_at: Option<u64>,
+    cleared_levels: HashMap<(Side, i64), u64>,
+    orders: HashMap<u64, QueuePosition>,
+    next_order: u64
+}
+
+impl QueueInference {
+    pub fn new(trade_window: u64, capacity: usize, cancel_assumption: CancelAssumption) -> QueueInference {
+        QueueInference {
+            trade_window,
+            capacity: capacity.max(1),
+            cancel_assumption,
+            flows: VecDeque::with_capacity(capacity.max(1)),
+            bid_totals: FlowTotals::default(),
+            ask_totals: FlowTotals::default(),
+            levels: HashMap::new(),
+            executions: HashMap::new(),
+            cleared_at: None,
+            cleared_levels: HashMap::new(),
+            orders: HashMap::new(),
+            next_order: 0
+        }
+    }
+
+    /*
+    Record a trade by aggressor side (Side::Bid for buyer-initiated) at a scaled price and quantity.
+    Use Orderbook::projection_mut to reach the inference registered on the book
+    */
+    pub fn record_trade(&mut self, aggressor: Side, price: i64, quantity: u64, timestamp: u64) {
+        let resting = match aggressor {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid
+        };
+        let execution = self.executions.entry((resting, price)).or_insert(Execution { quantity: 0, timestamp });
+        execution.quantity = execution.quantity.saturating_add(quantity);
+        execution.timestamp = timestamp;
+    }
+
+    /*
+    Flows inferred since the last drain, oldest first
+    */
+    pub fn drain_flows(&mut self) -> Vec<LevelFlow> {
+        self.flows.drain(..).collect()
+    }
+
+    pub fn totals(&self, side: Side) -> FlowTotals {
+        match side {
+            Side::Bid => self.bid_totals,
+            Side::Ask => self.ask_totals
+        }
+    }
+
+    /*
+    Start tracking an order of scaled quantity joining the back of the level at price. Call when the
+    order is acknowledged, before the book update that displays it. Returns an id for position
+    */
+    pub fn track_order(&mut self, side: Side, price: i64, quantity: u64) -> u64 {
+        let id = self.next_order;
+        self.next_order += 1;
+        let ahead = self.levels.get(&(side, price)).copied().unwrap_or(0);
+        self.orders.insert(id, QueuePosition { side, price, quantity, ahead, filled: 0 });
+        id
+    }
+
+    pub fn position(&self, id: u64) -> Option<QueuePosition> {
+        self.orders.get(&id).copied()
+    }
+
+    /*
+    Stop tracking an order, e.g. once cancelled or fully filled
+    */
+    pub fn untrack_order(&mut self, id: u64) -> Option<QueuePosition> {
+        self.orders.remove(&id)
+    }
+
+    /*
+    Executed quantity at the level recent enough to explain an update at timestamp, with its time
+    */
+    fn pending_execution(&mut self, key: (Side, i64), timestamp: u64) -> (u64, u64) {
+        match self.executions.get(&key).copied() {
+            Some(execution) if timestamp.saturating_sub(execution.timestamp) <= self.trade_window => (execution.quantity, execution.timestamp),
+            Some(_) => {
+                self.executions.remove(&key);
+                (0, timestamp)
+            },
+            None => (0, timestamp)
+        }
+    }
+
+    /*
+    Explain a change at a level from before to after
+    */
+    fn infer(&mut self, side: Side, price: i64, before: u64, after: u64, timestamp: u64) {
+        let key = (side, price);
+        let (executed, executed_at) = self.pending_execution(key, timestamp);
+        let mut level = before;
+        let traded = match after < before {
+            true => executed.min(before - after),
+            false => executed.min(before)
+        };
+        if traded > 0 {
+            self.emit(side, price, FlowKind::Trade, traded, level, timestamp);
+            level -= traded;
+            match executed - traded {
+                0 => self.executions.remove(&key),
+                rest => self.executions.insert(key, Execution { quantity: rest, timestamp: executed_at })
+            };
+        }
+        match after.cmp(&level) {
+            std::cmp::Ordering::Less => self.emit(side, price, FlowKind::Cancel, level - after, level, timestamp),
+            std::cmp::Ordering::Greater => self.emit(side, price, FlowKind::Add, after - level, level, timestamp),
+            std::cmp::Ordering::Equal => ()
+        }
+        match after {
+            0 => self.levels.remove(&key),
+            _ => self.levels.insert(key, after)
+        };
+    }
+
+    fn rescale(&mut self, price_multiplier: i64, quantity_multiplier: u64) {
+        let rekey = |(side, price): (Side, i64)| (side, price.saturating_mul(price_multiplier));
+        let quantity = |quantity: u64| quantity.saturating_mul(quantity_multiplier);
+        self.levels = self.levels.drain().map(|(key, level)| (rekey(key), quantity(level))).collect();
+        self.cleared_levels = self.cleared_levels.drain().map(|(key, level)| (rekey(key), quantity(level))).collect();
+        self.executions = self.executions.drain().map(|(key, execution)| (rekey(key), Execution { quantity: quantity(execution.quantity), ..execution })).collect();
+        for order in self.orders.values_mut() {
+            order.price = order.price.saturating_mul(price_multiplier);
+            order.quantity = quantity(order.quantity);
+            order.ahead = quantity(order.ahead);
+            order.filled = quantity(order.filled);
+        }
+        for flow in self.flows.iter_mut() {
+            flow.price = flow.price.saturating_mul(price_multiplier);
+            flow.quantity = quantity(flow.quantity);
+            flow.level_before = quantity(flow.level_before);
+        }
+        for totals in [&mut self.bid_totals, &mut self.ask_totals] {
+            *totals = FlowTotals { added: quantity(totals.added), cancelled: quantity(totals.cancelled), traded: quantity(totals.traded) };
+        }
+    }
+
+    fn emit(&mut self, side: Side, price: i64, kind: FlowKind, quantity: u64, level_before: u64, timestamp: u64) {
+        let totals = match side {
+            Side::Bid => &mut self.bid_totals,
+            Side::Ask => &mut self.ask_totals
+        };
+        match kind {
+            FlowKind::Add => totals.added = totals.added.saturating_add(quantity),
+            FlowKind::Cancel => totals.cancelled = totals.cancelled.saturating_add(quantity),
+            FlowKind::Trade => totals.traded = totals.traded.saturating_add(quantity)
+        }
+        let flow = LevelFlow { side, price, kind, quantity, level_before, timestamp };
+        let cancel_assumption = self.cancel_assumption;
+        for order in self.orders.values_mut().filter(|order| order.side == side && order.price == price) {
+            advance(order, &flow, cancel_assumption);
+        }
+        // capacity is pub and may have been lowered or zeroed since the last flow
+        while self.flows.len() >= self.capacity.max(1) {
+            self.flows.pop_front();
+        }
+        self.flows.push_back(flow);
+    }
+}
+
+/*
+Move an order through its queue for one flow at its level. Adds join behind it
+*/
+fn advance(order: &mut QueuePosition, flow: &LevelFlow, cancel_assumption: CancelAssumption) {
+    match flow.kind {
+        FlowKind::Add => (),
+        FlowKind::Trade => {
+            let from_ahead = flow.quantity.min(order.ahead);
+            order.ahead -= from_ahead;
+            order.filled = order.filled.saturating_add((flow.quantity - from_ahead).min(order.remaining()));
+        },
+        FlowKind::Cancel => {
+            if flow.quantity >= flow.level_before {
+                order.ahead = 0;
+                return;
+            }
+            if cancel_assumption == CancelAssumption::ProRata {
+                let behind = flow.level_before.saturating_sub(order.ahead.saturating_add(order.remaining()));
+                let others = order.ahead.saturating_add(behind);
+                if others > 0 {
+                    let from_ahead = (flow.quantity as u128 * order.ahead as u128 / others as u128) as u64;
+                    order.ahead = order.ahead.saturating_sub(from_ahead);
+                }
+            }
+        }
+    }
+}
+
+impl Projection for QueueInference {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, previous } => {
+                let before = match previous {
+                    Some(previous) => previous,
+                    None if self.cleared_at == Some(timestamp) => self.cleared_levels.remove(&(side, price)).unwrap_or(0),
+                    None => 0
+                };
+                self.infer(side, price, before, quantity, timestamp);
+            },
+            BookEvent::LevelRemoved { side, price, quantity } => self.infer(side, price, quantity, 0, timestamp),
+            BookEvent::Cleared => {
+                self.cleared_at = Some(timestamp);
+                self.cleared_levels = std::mem::take(&mut self.levels);
+            },
+            BookEvent::Reset => (),
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => self.rescale(price_multiplier, quantity_multiplier)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowered_capacity_trims_retained_flows() {
+        let mut inference = QueueInference::new(0, 8, CancelAssumption::ProRata);
+        for quantity in 1..=8 {
+            inference.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity, previous: None }, quantity);
+        }
+        inference.capacity = 3;
+        inference.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity: 9, previous: Some(8) }, 9);
+        assert_eq!(inference.drain_flows().iter().map(|flow| flow.timestamp).collect::<Vec<_>>(), vec![7, 8, 9]);
+        inference.capacity = 0;
+        inference.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity: 10, previous: Some(9) }, 10);
+        inference.apply(&BookEvent::LevelSet { side: Side::Bid, price: 100, quantity: 11, previous: Some(10) }, 11);
+        assert_eq!(inference.drain_flows().len(), 1);
+    }
+}
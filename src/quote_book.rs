@@ -0,0 +1,261 @@
+/*
+Purpose: Dealer quote aggregation book with per-provider attribution
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::ConfigError;
+use crate::l2::{checked_decimal_factor, decimal_factor, is_valid_level, scale, scale_signed};
+
+/*
+Bids and asks trees map scaled price to the scaled quantity quoted by each provider at that price.
+Provider quote sets track which prices each provider currently quotes so they can be replaced as a unit
+*/
+pub struct QuoteBook {
+    pub bids: BTreeMap<i64, BTreeMap<u32, u64>>,
+    pub asks: BTreeMap<i64, BTreeMap<u32, u64>>,
+    pub provider_quotes: HashMap<u32, (Vec<i64>, Vec<i64>)>,
+    pub price_factor: f64,
+    pub quantity_factor: f64
+}
+
+impl QuoteBook {
+    /*
+    Panics if either decimals setting exceeds MAX_DECIMALS; see try_new
+    */
+    pub fn new(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> QuoteBook {
+        QuoteBook {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            provider_quotes: HashMap::new(),
+            price_factor: decimal_factor(price_decimals),
+            quantity_factor: decimal_factor(quantity_decimals)
+        }
+    }
+
+    pub fn try_new(price_decimals: Option<u8>, quantity_decimals: Option<u8>) -> Result<QuoteBook, ConfigError> {
+        Ok(QuoteBook {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            provider_quotes: HashMap::new(),
+            price_factor: checked_decimal_factor("price_decimals", price_decimals)?,
+            quantity_factor: checked_decimal_factor("quantity_decimals", quantity_decimals)?
+        })
+    }
+
+    /*
+    Construct with room for providers quote sets so adding them does not reallocate
+    */
+    pub fn with_capacity(price_decimals: Option<u8>, quantity_decimals: Option<u8>, providers: usize) -> QuoteBook {
+        let mut book = QuoteBook::new(price_decimals, quantity_decimals);
+        book.reserve(providers);
+        book
+    }
+
+    pub fn reserve(&mut self, providers: usize) {
+        self.provider_quotes.reserve(providers);
+    }
+
+    /*
+    Release spare capacity in the provider index. Price trees are BTreeMaps and hold none
+    */
+    pub fn shrink_to_fit(&mut self) {
+        self.provider_quotes.shrink_to_fit();
+        for (bid_prices, ask_prices) in self.provider_quotes.values_mut() {
+            bid_prices.shrink_to_fit();
+            ask_prices.shrink_to_fit();
+        }
+    }
+
+    /*
+    Replace every quote from provider with the given set in one call.
+    Bids and asks should be formatted as (price, quantity); quotes whose quantity scales to zero are dropped
+    */
+    pub fn replace_quotes(&mut self, provider: u32, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) {
+        self.remove_provider(provider);
+        let mut bid_prices: Vec<i64> = Vec::new();
+        let mut ask_prices: Vec<i64> = Vec::new();
+        for bid in bids.iter().filter(|bid| is_valid_level(bid)) {
+            let scaled_price = scale_signed(bid.0, self.price_factor);
+            match scale(bid.1, self.quantity_factor) {
+                0 => (),
+                scaled_quantity => {
+                    self.bids.entry(scaled_price).or_default().insert(provider, scaled_quantity);
+                    bid_prices.push(scaled_price);
+                }
+            }
+        }
+        for ask in asks.iter().filter(|ask| is_valid_level(ask)) {
+            let scaled_price = scale_signed(ask.0, self.price_factor);
+            match scale(ask.1, self.quantity_factor) {
+                0 => (),
+                scaled_quantity => {
+                    self.asks.entry(scaled_price).or_default().insert(provider, scaled_quantity);
+                    ask_prices.push(scaled_price);
+                }
+            }
+        }
+        if !bid_prices.is_empty() || !ask_prices.is_empty() {
+            self.provider_quotes.insert(provider, (bid_prices, ask_prices));
+        }
+    }
+
+    pub fn remove_provider(&mut self, provider: u32) {
+        let (bid_prices, ask_prices) = match self.provider_quotes.remove(&provider) {
+            Some(quotes) => quotes,
+            None => return
+        };
+        for price in bid_prices.iter() {
+            if let Some(level) = self.bids.get_mut(price) {
+                level.remove(&provider);
+                if level.is_empty() {
+                    self.bids.remove(price);
+                }
+            }
+        }
+        for price in ask_prices.iter() {
+            if let Some(level) = self.asks.get_mut(price) {
+                level.remove(&provider);
+                if level.is_empty() {
+                    self.asks.remove(price);
+                }
+            }
+        }
+    }
+
+    /*
+    Best bid as (price, quantity) aggregated over providers not in excluded
+    */
+    pub fn get_best_bid(&self, excluded: &[u32]) -> Option<(i64, u64)> {
+        self.bids.iter().rev().find_map(|(price, level)| Self::aggregate(level, excluded).map(|quantity| (*price, quantity)))
+    }
+
+    pub fn get_best_ask(&self, excluded: &[u32]) -> Option<(i64, u64)> {
+        self.asks.iter().find_map(|(price, level)| Self::aggregate(level, excluded).map(|quantity| (*price, quantity)))
+    }
+
+    /*
+    Providers quoting at a price as (provider, quantity)
+    */
+    pub fn get_bid_providers(&self, price: i64) -> Vec<(u32, u64)> {
+        match self.bids.get(&price) {
+            Some(level) => level.iter().map(|(provider, quantity)| (*provider, *quantity)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    pub fn get_ask_providers(&self, price: i64) -> Vec<(u32, u64)> {
+        match self.asks.get(&price) {
+            Some(level) => level.iter().map(|(provider, quantity)| (*provider, *quantity)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    fn aggregate(level: &BTreeMap<u32, u64>, excluded: &[u32]) -> Option<u64> {
+        let mut total_quantity: u64 = 0;
+        let mut included = false;
+        for (provider, quantity) in level.iter() {
+            if !excluded.contains(provider) {
+                total_quantity = total_quantity.saturating_add(*quantity);
+                included = true;
+            }
+        }
+        match included {
+            true => Some(total_quantity),
+            false => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::l2::unscale_signed;
+
+    // Fixed xorshift seed, as in benches/, so every run drives the same sequence
+    fn xorshift(seed: u64) -> impl FnMut() -> u64 {
+        let mut state = seed;
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    fn quotes(next: &mut impl FnMut() -> u64) -> Vec<(f64, f64)> {
+        let awkward = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0, 0.0, 1e-9, 0.004, 1e300];
+        (0..next() % 5).map(|_| {
+            let price = match next() % 8 {
+                0 => awkward[(next() % 8) as usize],
+                _ => 95.0 + (next() % 1000) as f64 / 100.0
+            };
+            let quantity = match next() % 4 {
+                0 => awkward[(next() % 8) as usize],
+                _ => (next() % 500) as f64 / 100.0
+            };
+            (price, quantity)
+        }).collect()
+    }
+
+    fn check_invariants(book: &QuoteBook) {
+        for tree in [&book.bids, &book.asks] {
+            for (price, level) in tree.iter() {
+                assert!(!level.is_empty(), "empty level at {}", price);
+                assert!(level.values().all(|quantity| *quantity > 0), "zero quantity at {}", price);
+                assert!(unscale_signed(*price, book.price_factor).is_finite());
+                for provider in level.keys() {
+                    assert!(book.provider_quotes.contains_key(provider), "untracked provider {} at {}", provider, price);
+                }
+            }
+        }
+        for (provider, (bid_prices, ask_prices)) in book.provider_quotes.iter() {
+            assert!(!bid_prices.is_empty() || !ask_prices.is_empty(), "provider {} tracked with no quotes", provider);
+            assert!(bid_prices.iter().all(|price| book.bids.get(price).is_some_and(|level| level.contains_key(provider))));
+            assert!(ask_prices.iter().all(|price| book.asks.get(price).is_some_and(|level| level.contains_key(provider))));
+        }
+        for excluded in [&[][..], &[0][..], &[1, 2][..]] {
+            let expected_bid = book.bids.iter().rev().find_map(|(price, level)| {
+                let included: Vec<u64> = level.iter().filter(|(provider, _)| !excluded.contains(provider)).map(|(_, quantity)| *quantity).collect();
+                match included.is_empty() {
+                    true => None,
+                    false => Some((*price, included.iter().fold(0u64, |total, quantity| total.saturating_add(*quantity))))
+                }
+            });
+            assert_eq!(book.get_best_bid(excluded), expected_bid);
+            if let Some((_, quantity)) = book.get_best_ask(excluded) {
+                assert!(quantity > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn random_quote_sequences_keep_the_book_consistent() {
+        for seed in [0x9e3779b97f4a7c15, 0xdeadbeefcafef00d, 42] {
+            let mut next = xorshift(seed);
+            let mut book = QuoteBook::new(Some(2), Some(2));
+            for _ in 0..2000 {
+                let provider = (next() % 4) as u32;
+                match next() % 5 {
+                    0 => book.remove_provider(provider),
+                    _ => {
+                        let (bids, asks) = (quotes(&mut next), quotes(&mut next));
+                        book.replace_quotes(provider, bids, asks);
+                    }
+                }
+                check_invariants(&book);
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_scaling_to_zero_are_dropped() {
+        let mut book = QuoteBook::new(Some(2), Some(2));
+        book.replace_quotes(1, vec![(99.0, 0.004)], vec![(101.0, 0.004), (102.0, 1.0)]);
+        assert!(book.bids.is_empty());
+        assert_eq!(book.asks.keys().collect::<Vec<_>>(), vec![&10200]);
+        assert_eq!(book.provider_quotes.get(&1), Some(&(Vec::new(), vec![10200])));
+        book.replace_quotes(1, vec![(99.0, 0.004)], Vec::new());
+        assert!(book.asks.is_empty() && book.provider_quotes.is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+/*
+Purpose: Bucketed update-rate statistics and burst detection
+*/
+
+use std::collections::VecDeque;
+
+use crate::Orderbook;
+
+/*
+Called with (bucket start, updates in the bucket) when a bucket first reaches the burst threshold
+*/
+pub type BurstListener = Box<dyn FnMut(u64, u64) + Send>;
+
+/*
+Counts book updates in bucket_ms buckets on the book clock, keeping the last window buckets.
+A bucket with at least burst_threshold updates is a burst. peak_burst is the largest bucket count seen
+*/
+pub struct UpdateRate {
+    pub bucket_ms: u64,
+    pub window: usize,
+    pub burst_threshold: u64,
+    pub buckets: VecDeque<(u64, u64)>,
+    pub total_updates: u64,
+    pub peak_burst: u64,
+    pub bursts: u64,
+    listener: Option<BurstListener>
+}
+
+impl UpdateRate {
+    pub fn new(bucket_ms: u64, window: usize, burst_threshold: u64) -> UpdateRate {
+        UpdateRate {
+            bucket_ms: bucket_ms.max(1),
+            window: window.max(1),
+            burst_threshold,
+            buckets: VecDeque::new(),
+            total_updates: 0,
+            peak_burst: 0,
+            bursts: 0,
+            listener: None
+        }
+    }
+
+    pub fn record(&mut self, timestamp: u64) {
+        let start = timestamp - timestamp % self.bucket_ms.max(1);
+        match self.buckets.back_mut() {
+            Some((bucket, count)) if *bucket == start => *count += 1,
+            _ => {
+                self.buckets.push_back((start, 1));
+                while self.buckets.len() > self.window {
+                    self.buckets.pop_front();
+                }
+            }
+        }
+        self.total_updates += 1;
+        let count = self.buckets.back().map_or(0, |(_, count)| *count);
+        self.peak_burst = self.peak_burst.max(count);
+        if count == self.burst_threshold {
+            self.bursts += 1;
+            if let Some(listener) = self.listener.as_mut() {
+                listener(start, count);
+            }
+        }
+    }
+
+    /*
+    Updates per second over the window ending at now (ms)
+    */
+    pub fn rate(&self, now: u64) -> f64 {
+        let span = self.bucket_ms.max(1).saturating_mul(self.window.max(1) as u64);
+        let since = now.saturating_sub(span);
+        let count: u64 = self.buckets.iter()
+            .filter(|(bucket, _)| bucket.saturating_add(self.bucket_ms) > since && *bucket <= now)
+            .map(|(_, count)| count)
+            .sum();
+        (count as f64) * 1000.0 / (span as f64)
+    }
+
+    /*
+    True when the bucket containing now has reached the burst threshold
+    */
+    pub fn is_bursting(&self, now: u64) -> bool {
+        match self.buckets.back() {
+            Some((bucket, count)) => now - now % self.bucket_ms.max(1) == *bucket && *count >= self.burst_threshold,
+            None => false
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Start counting updates applied by process and load_snapshot, stamped with the book clock.
+    Untimed updates count toward the bucket of the last process_at timestamp
+    */
+    pub fn track_update_rate(&mut self, bucket_ms: u64, window: usize, burst_threshold: u64) {
+        self.update_rate = Some(UpdateRate::new(bucket_ms, window, burst_threshold));
+    }
+
+    /*
+    Register a callback for burst detection. Has no effect until update rate tracking is enabled
+    */
+    pub fn on_burst(&mut self, listener: BurstListener) {
+        if let Some(update_rate) = self.update_rate.as_mut() {
+            update_rate.listener = Some(listener);
+        }
+    }
+
+    pub(crate) fn record_update(&mut self) {
+        if let Some(update_rate) = self.update_rate.as_mut() {
+            update_rate.record(self.timestamp);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+/*
+Purpose: Periodic reconciliation of a delta-maintained book against venue REST snapshots
+*/
+
+use crate::{verify, Orderbook, ValidationError, VerifyReport};
+
+/*
+Schedules and runs comparisons of a local book against reference snapshots fetched by the caller.
+depth bounds the comparison to the levels a REST snapshot covers; tolerance is in unscaled quantity.
+With auto_resync set, a divergent book is replaced by the snapshot through Orderbook::reload_snapshot.
+A snapshot that fails validation leaves the book as it was and is counted in resync_failures with the
+error kept in last_resync_error
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconciler {
+    pub interval: u64,
+    pub depth: Option<usize>,
+    pub tolerance: f64,
+    pub auto_resync: bool,
+    pub last_check: Option<u64>,
+    pub checks: u64,
+    pub divergences: u64,
+    pub resyncs: u64,
+    pub resync_failures: u64,
+    pub last_resync_error: Option<ValidationError>,
+    pub last_report: Option<VerifyReport>
+}
+
+impl Reconciler {
+    pub fn new(interval: u64, depth: Option<usize>, tolerance: f64, auto_resync: bool) -> Reconciler {
+        Reconciler {
+            interval,
+            depth,
+            tolerance,
+            auto_resync,
+            last_check: None,
+            checks: 0,
+            divergences: 0,
+            resyncs: 0,
+            resync_failures: 0,
+            last_resync_error: None,
+            last_report: None
+        }
+    }
+
+    /*
+    True when a snapshot should be fetched: never checked, or interval (ms) has passed since the last check
+    */
+    pub fn is_due(&self, now: u64) -> bool {
+        match self.last_check {
+            Some(last_check) => now.saturating_sub(last_check) >= self.interval,
+            None => true
+        }
+    }
+
+    /*
+    Compare book against a snapshot formatted as (price, quantity), taken at now.
+    The snapshot must reflect the same point in the feed as the book, e.g. applied after buffered deltas catch up
+    */
+    pub fn reconcile<M>(&mut self, book: &mut Orderbook<M>, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, now: u64) -> &VerifyReport {
+        let mut reference = Orderbook::new(None, None);
+        reference.price_factor = book.price_factor;
+        reference.quantity_factor = book.quantity_factor;
+        reference.load_snapshot(bids.clone(), asks.clone());
+        let report = verify(book, &reference, self.depth, self.tolerance);
+        self.last_check = Some(now);
+        self.checks += 1;
+        if !report.is_consistent() {
+            self.divergences += 1;
+            if self.auto_resync {
+                match book.reload_snapshot(bids, asks) {
+                    Ok(()) => self.resyncs += 1,
+                    Err(error) => {
+                        self.resync_failures += 1;
+                        self.last_resync_error = Some(error);
+                    }
+                }
+            }
+        }
+        self.last_report.insert(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BookEvent, Projection, Side};
+
+    #[derive(Default)]
+    struct Resets(usize);
+
+    impl Projection for Resets {
+        fn apply(&mut self, event: &BookEvent, _timestamp: u64) {
+            if matches!(event, BookEvent::Reset) {
+                self.0 += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn auto_resync_reloads_and_reports_invalid_snapshots() {
+        let mut book = Orderbook::from_snapshot(Some(2), Some(2), vec![(99.0, 1.0)], vec![(101.0, 1.0)]);
+        book.add_projection(Resets::default());
+        let mut reconciler = Reconciler::new(1000, None, 0.0, true);
+
+        reconciler.reconcile(&mut book, vec![(99.0, 2.0)], vec![(101.0, 1.0)], 1);
+        assert_eq!((reconciler.divergences, reconciler.resyncs, reconciler.resync_failures), (1, 1, 0));
+        assert_eq!(book.get_best_bid(), Some((9900, 200)));
+        assert_eq!(book.projection::<Resets>().map(|resets| resets.0), Some(1));
+
+        reconciler.reconcile(&mut book, vec![(99.0, 3.0), (f64::NAN, 1.0)], vec![(101.0, 1.0)], 2);
+        assert_eq!((reconciler.divergences, reconciler.resyncs, reconciler.resync_failures), (2, 1, 1));
+        assert!(matches!(reconciler.last_resync_error, Some(ValidationError::NonFinitePrice { side: Side::Bid, .. })));
+        assert_eq!(book.get_best_bid(), Some((9900, 200)));
+        assert_eq!(book.projection::<Resets>().map(|resets| resets.0), Some(1));
+    }
+}
@@ -0,0 +1,176 @@
+/*
+Purpose: Redis transport for top-N snapshots and deltas with a latest-snapshot key per symbol
+*/
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use serde::Serialize;
+
+use crate::{BookSnapshot, DepthDelta, Publisher, RowChange, Side};
+
+/*
+Unscaled best depth levels per side, best first
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedisSnapshot {
+    pub symbol: String,
+    pub timestamp: u64,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>
+}
+
+/*
+A TopNTracker row change, unscaled. action is insert, remove or update; quantity is absent for remove
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedisRowChange {
+    pub side: &'static str,
+    pub action: &'static str,
+    pub index: usize,
+    pub price: f64,
+    pub quantity: Option<f64>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedisDelta {
+    pub symbol: String,
+    pub timestamp: u64,
+    pub changes: Vec<RedisRowChange>
+}
+
+/*
+Publisher transport over a plain RESP connection, sending JSON. A delta PUBLISHes a RedisDelta on
+<prefix>:<symbol>:deltas; a snapshot PUBLISHes a RedisSnapshot on <prefix>:<symbol>:snapshots.
+Both SET <prefix>:<symbol>:snapshot to the latest top rows. Commands are pipelined until flush
+*/
+pub struct RedisPublisher {
+    pub prefix: String,
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+    commands: Vec<Vec<String>>
+}
+
+impl RedisPublisher {
+    pub fn connect<A: ToSocketAddrs>(address: A, prefix: &str) -> io::Result<RedisPublisher> {
+        let writer = TcpStream::connect(address)?;
+        writer.set_nodelay(true)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(RedisPublisher {
+            prefix: prefix.to_string(),
+            writer,
+            reader,
+            commands: Vec::new()
+        })
+    }
+
+    fn set_latest(&mut self, symbol: &str, snapshot: &str) {
+        self.commands.push(vec![String::from("SET"), format!("{}:{}:snapshot", self.prefix, symbol), snapshot.to_string()]);
+    }
+
+    fn send_commands(&mut self) -> io::Result<()> {
+        let commands = std::mem::take(&mut self.commands);
+        let mut buffer: Vec<u8> = Vec::new();
+        for command in commands.iter() {
+            buffer.extend_from_slice(format!("*{}\r\n", command.len()).as_bytes());
+            for argument in command.iter() {
+                buffer.extend_from_slice(format!("${}\r\n", argument.len()).as_bytes());
+                buffer.extend_from_slice(argument.as_bytes());
+                buffer.extend_from_slice(b"\r\n");
+            }
+        }
+        self.writer.write_all(&buffer)?;
+        for _ in commands.iter() {
+            self.read_reply()?;
+        }
+        Ok(())
+    }
+
+    fn read_reply(&mut self) -> io::Result<()> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "redis connection closed"));
+        }
+        let line = line.trim_end();
+        match line.split_at_checked(1) {
+            Some(("-", message)) => Err(io::Error::other(message.to_string())),
+            Some(("$", length)) => {
+                if let Ok(length) = length.parse::<usize>() {
+                    let mut bulk = vec![0u8; length + 2];
+                    io::Read::read_exact(&mut self.reader, &mut bulk)?;
+                }
+                Ok(())
+            },
+            Some(("+" | ":", _)) => Ok(()),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected redis reply: {}", line)))
+        }
+    }
+}
+
+impl Publisher for RedisPublisher {
+    fn send_snapshot(&mut self, symbol: &str, snapshot: &BookSnapshot) -> io::Result<()> {
+        let payload = serde_json::to_string(&redis_snapshot(symbol, snapshot))?;
+        self.set_latest(symbol, &payload);
+        self.commands.push(vec![String::from("PUBLISH"), format!("{}:{}:snapshots", self.prefix, symbol), payload]);
+        Ok(())
+    }
+
+    fn send_delta(&mut self, symbol: &str, delta: &DepthDelta) -> io::Result<()> {
+        let latest = serde_json::to_string(&redis_snapshot(symbol, &delta.top))?;
+        self.set_latest(symbol, &latest);
+        let payload = serde_json::to_string(&RedisDelta {
+            symbol: symbol.to_string(),
+            timestamp: delta.top.timestamp(),
+            changes: delta.changes.iter().map(|change| row_change(&delta.top, change)).collect()
+        })?;
+        self.commands.push(vec![String::from("PUBLISH"), format!("{}:{}:deltas", self.prefix, symbol), payload]);
+        Ok(())
+    }
+
+    /*
+    Write the pipelined commands, then read one reply per command. A Redis error reply becomes an io error
+    */
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_commands()
+    }
+}
+
+fn redis_snapshot(symbol: &str, snapshot: &BookSnapshot) -> RedisSnapshot {
+    let unscale = |(price, quantity): &(i64, u64)| (snapshot.unscale_price(*price), snapshot.unscale_qty(*quantity));
+    RedisSnapshot {
+        symbol: symbol.to_string(),
+        timestamp: snapshot.timestamp(),
+        bids: snapshot.bids().iter().map(unscale).collect(),
+        asks: snapshot.asks().iter().map(unscale).collect()
+    }
+}
+
+fn row_change(book: &BookSnapshot, change: &RowChange) -> RedisRowChange {
+    let side_name = |side: Side| match side {
+        Side::Bid => "bid",
+        Side::Ask => "ask"
+    };
+    match *change {
+        RowChange::Inserted { side, index, price, quantity } => RedisRowChange {
+            side: side_name(side),
+            action: "insert",
+            index,
+            price: book.unscale_price(price),
+            quantity: Some(book.unscale_qty(quantity))
+        },
+        RowChange::Removed { side, index, price } => RedisRowChange {
+            side: side_name(side),
+            action: "remove",
+            index,
+            price: book.unscale_price(price),
+            quantity: None
+        },
+        RowChange::Updated { side, index, price, quantity } => RedisRowChange {
+            side: side_name(side),
+            action: "update",
+            index,
+            price: book.unscale_price(price),
+            quantity: Some(book.unscale_qty(quantity))
+        }
+    }
+}
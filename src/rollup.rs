@@ -0,0 +1,293 @@
+/*
+Purpose: Periodic per-symbol statistics rollups (hourly, end of day) with JSON and CSV reports
+*/
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{BookState, Orderbook, TradingCalendar};
+
+pub const HOURLY_MS: u64 = 3_600_000;
+pub const DAILY_MS: u64 = 86_400_000;
+
+/*
+Summary of one window [start, end) in ms. Volume is unscaled tape volume; spread is the time-weighted
+unscaled spread over two-sided time; depth percentiles are over per-observation unscaled bid plus ask
+quantity within depth_bps of mid. live_ms is time spent Live, observed_ms the time covered by observations.
+Gaps and resyncs are counted from state changes between observations
+*/
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RollupReport {
+    pub symbol: String,
+    pub start: u64,
+    pub end: u64,
+    pub traded_volume: f64,
+    pub trades: u64,
+    pub average_spread: Option<f64>,
+    pub depth_p50: Option<f64>,
+    pub depth_p90: Option<f64>,
+    pub depth_p99: Option<f64>,
+    pub observed_ms: u64,
+    pub live_ms: u64,
+    pub gaps: u64,
+    pub resyncs: u64
+}
+
+pub const ROLLUP_CSV_HEADER: &str = "symbol,start,end,traded_volume,trades,average_spread,depth_p50,depth_p90,depth_p99,observed_ms,live_ms,gaps,resyncs";
+
+impl RollupReport {
+    /*
+    Fraction of observed time the book was Live, None if nothing was observed
+    */
+    pub fn uptime(&self) -> Option<f64> {
+        match self.observed_ms {
+            0 => None,
+            observed_ms => Some(self.live_ms as f64 / observed_ms as f64)
+        }
+    }
+
+    /*
+    One line matching ROLLUP_CSV_HEADER, empty fields for None
+    */
+    pub fn to_csv_row(&self) -> String {
+        let optional = |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.symbol,
+            self.start,
+            self.end,
+            self.traded_volume,
+            self.trades,
+            optional(self.average_spread),
+            optional(self.depth_p50),
+            optional(self.depth_p90),
+            optional(self.depth_p99),
+            self.observed_ms,
+            self.live_ms,
+            self.gaps,
+            self.resyncs
+        )
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+/*
+Header plus one row per report
+*/
+pub fn rollups_to_csv(reports: &[RollupReport]) -> String {
+    let mut csv = String::from(ROLLUP_CSV_HEADER);
+    csv.push('\n');
+    for report in reports.iter() {
+        csv.push_str(&report.to_csv_row());
+        csv.push('\n');
+    }
+    csv
+}
+
+/*
+Book state as of the previous observation, held until the next one
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Observed {
+    timestamp: u64,
+    spread: Option<f64>,
+    state: BookState
+}
+
+/*
+Accumulates one symbol's statistics and closes a window each time an observation or flush crosses a
+multiple of period_ms (e.g. HOURLY_MS, DAILY_MS; boundaries are aligned to the epoch). Observe the book
+after every update and record trades from the tape. Windows with no observations are not reported.
+With a calendar, only open trading time is credited to observed_ms, live_ms and the average spread and
+depth is not sampled while closed; flush at TradingCalendar::session_close for session-aligned end of day
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollupScheduler {
+    pub symbol: String,
+    pub period_ms: u64,
+    pub depth_bps: f64,
+    pub calendar: Option<TradingCalendar>,
+    window_start: Option<u64>,
+    last: Option<Observed>,
+    traded_volume: f64,
+    trades: u64,
+    spread_total: f64,
+    spread_ms: u64,
+    depths: Vec<f64>,
+    observed_ms: u64,
+    live_ms: u64,
+    gaps: u64,
+    resyncs: u64
+}
+
+impl RollupScheduler {
+    pub fn new(symbol: &str, period_ms: u64, depth_bps: f64) -> RollupScheduler {
+        RollupScheduler {
+            symbol: symbol.to_string(),
+            period_ms: period_ms.max(1),
+            depth_bps,
+            calendar: None,
+            window_start: None,
+            last: None,
+            traded_volume: 0.0,
+            trades: 0,
+            spread_total: 0.0,
+            spread_ms: 0,
+            depths: Vec::new(),
+            observed_ms: 0,
+            live_ms: 0,
+            gaps: 0,
+            resyncs: 0
+        }
+    }
+
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> RollupScheduler {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    pub fn record_trade(&mut self, quantity: f64) {
+        if quantity.is_finite() && quantity > 0.0 {
+            self.traded_volume += quantity;
+            self.trades += 1;
+        }
+    }
+
+    /*
+    Sample the book at timestamp (ms), which must not decrease. Returns the report of the window this
+    observation closed, if any
+    */
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) -> Option<RollupReport> {
+        let report = self.roll(timestamp);
+        self.advance(timestamp);
+        let state = book.state();
+        if let Some(last) = self.last {
+            match (last.state, state) {
+                (BookState::Syncing, BookState::Syncing) => (),
+                (_, BookState::Syncing) => self.gaps += 1,
+                (BookState::Syncing, BookState::Live) => self.resyncs += 1,
+                _ => ()
+            }
+        }
+        if self.calendar.as_ref().is_none_or(|calendar| calendar.is_open(timestamp)) {
+            let depth = book.depth_grid(self.depth_bps, 1).map_or(0.0, |grid| grid.bids[0] + grid.asks[0]);
+            self.depths.push(depth);
+        }
+        self.last = Some(Observed {
+            timestamp,
+            spread: book.summary(Some(1)).spread.map(|spread| book.unscale_price(spread)),
+            state
+        });
+        report
+    }
+
+    /*
+    Close the current window at now regardless of boundaries, e.g. at shutdown or a manual end of day.
+    The last observed state carries into the next window
+    */
+    pub fn flush(&mut self, now: u64) -> Option<RollupReport> {
+        let start = self.window_start?;
+        self.advance(now);
+        let report = self.report(start, now.max(start));
+        self.window_start = Some(now.max(start));
+        report
+    }
+
+    /*
+    Close the current window if timestamp falls past its end and open the window containing timestamp
+    */
+    fn roll(&mut self, timestamp: u64) -> Option<RollupReport> {
+        let boundary = timestamp - timestamp % self.period_ms.max(1);
+        let start = match self.window_start {
+            Some(start) => start,
+            None => {
+                self.window_start = Some(boundary);
+                return None;
+            }
+        };
+        let end = start.saturating_add(self.period_ms.max(1));
+        if timestamp < end {
+            return None;
+        }
+        self.advance(end);
+        let report = self.report(start, end);
+        self.window_start = Some(boundary);
+        if let Some(last) = self.last.as_mut() {
+            last.timestamp = last.timestamp.max(boundary);
+        }
+        report
+    }
+
+    /*
+    Credit the (open) time since the last observation to its state, up to until
+    */
+    fn advance(&mut self, until: u64) {
+        if let Some(last) = self.last.as_mut() {
+            let duration = match &self.calendar {
+                Some(calendar) => calendar.open_ms(last.timestamp, until),
+                None => until.saturating_sub(last.timestamp)
+            };
+            self.observed_ms += duration;
+            if last.state == BookState::Live {
+                self.live_ms += duration;
+            }
+            if let Some(spread) = last.spread {
+                self.spread_total += spread * duration as f64;
+                self.spread_ms += duration;
+            }
+            last.timestamp = last.timestamp.max(until);
+        }
+    }
+
+    /*
+    Build the report for [start, end) and reset the accumulators. None if nothing was observed
+    */
+    fn report(&mut self, start: u64, end: u64) -> Option<RollupReport> {
+        let mut depths = std::mem::take(&mut self.depths);
+        depths.sort_unstable_by(f64::total_cmp);
+        let report = RollupReport {
+            symbol: self.symbol.clone(),
+            start,
+            end,
+            traded_volume: self.traded_volume,
+            trades: self.trades,
+            average_spread: match self.spread_ms {
+                0 => None,
+                spread_ms => Some(self.spread_total / spread_ms as f64)
+            },
+            depth_p50: percentile(&depths, 0.5),
+            depth_p90: percentile(&depths, 0.9),
+            depth_p99: percentile(&depths, 0.99),
+            observed_ms: self.observed_ms,
+            live_ms: self.live_ms,
+            gaps: self.gaps,
+            resyncs: self.resyncs
+        };
+        let observed = !depths.is_empty() || self.observed_ms > 0;
+        self.traded_volume = 0.0;
+        self.trades = 0;
+        self.spread_total = 0.0;
+        self.spread_ms = 0;
+        self.observed_ms = 0;
+        self.live_ms = 0;
+        self.gaps = 0;
+        self.resyncs = 0;
+        match observed {
+            true => Some(report),
+            false => None
+        }
+    }
+}
+
+fn percentile(sorted: &[f64], quantile: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (quantile * (sorted.len() - 1) as f64).round() as usize;
+    Some(sorted[rank])
+}
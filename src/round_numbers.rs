@@ -0,0 +1,164 @@
+/*
+Purpose: Liquidity concentration at round-number prices and the nearest round-number wall
+*/
+
+use std::collections::BTreeMap;
+
+use crate::events::rescale_tree;
+use crate::{BookEvent, DepthTotals, Orderbook, Projection, Side};
+
+/*
+A round-number level holding at least the wall quantity. distance_bps is from mid, always non-negative
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundWall {
+    pub side: Side,
+    pub price: i64,
+    pub quantity: u64,
+    pub distance_bps: f64
+}
+
+/*
+Share of each side's quantity resting on round-number prices, and size_lift: average quantity of a
+round level over average quantity of any level, both sides together (above 1 means round numbers attract
+size). Shares are None for an empty side, size_lift without round levels
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundNumberReport {
+    pub granularity: i64,
+    pub bid_share: Option<f64>,
+    pub ask_share: Option<f64>,
+    pub size_lift: Option<f64>,
+    pub nearest_wall: Option<RoundWall>
+}
+
+/*
+Projection keeping the levels whose scaled price is a multiple of granularity (e.g. 100_00 for whole
+hundreds at 2 price decimals) with their running quantity and depth totals, so reports need no pass over the book.
+wall_quantity is the scaled quantity that makes a round level a wall. A rescale of the book's decimals
+rescales granularity and wall_quantity with the levels, so the same prices stay round
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundNumbers {
+    pub granularity: i64,
+    pub wall_quantity: u64,
+    pub bid_levels: BTreeMap<i64, u64>,
+    pub ask_levels: BTreeMap<i64, u64>,
+    pub bid_round_quantity: u64,
+    pub ask_round_quantity: u64,
+    pub totals: DepthTotals
+}
+
+impl RoundNumbers {
+    pub fn new(granularity: i64, wall_quantity: u64) -> RoundNumbers {
+        RoundNumbers {
+            granularity: granularity.max(1),
+            wall_quantity,
+            bid_levels: BTreeMap::new(),
+            ask_levels: BTreeMap::new(),
+            bid_round_quantity: 0,
+            ask_round_quantity: 0,
+            totals: DepthTotals::default()
+        }
+    }
+
+    pub fn is_round(&self, price: i64) -> bool {
+        price.rem_euclid(self.granularity.max(1)) == 0
+    }
+
+    /*
+    Report against mid_price (scaled, as in BookSummary)
+    */
+    pub fn report(&self, mid_price: Option<f64>) -> RoundNumberReport {
+        let share = |round: u64, total: u64| match total {
+            0 => None,
+            total => Some(round as f64 / total as f64)
+        };
+        let round_levels = self.bid_levels.len() + self.ask_levels.len();
+        let levels = self.totals.bid_levels + self.totals.ask_levels;
+        let round_quantity = self.bid_round_quantity as f64 + self.ask_round_quantity as f64;
+        let quantity = self.totals.bid_quantity as f64 + self.totals.ask_quantity as f64;
+        RoundNumberReport {
+            granularity: self.granularity.max(1),
+            bid_share: share(self.bid_round_quantity, self.totals.bid_quantity),
+            ask_share: share(self.ask_round_quantity, self.totals.ask_quantity),
+            size_lift: match round_levels > 0 && quantity > 0.0 {
+                true => Some((round_quantity / round_levels as f64) / (quantity / levels as f64)),
+                false => None
+            },
+            nearest_wall: mid_price.and_then(|mid_price| self.nearest_wall(mid_price))
+        }
+    }
+
+    /*
+    Closest wall to mid_price (scaled) among bids at or below it and asks at or above it
+    */
+    pub fn nearest_wall(&self, mid_price: f64) -> Option<RoundWall> {
+        if mid_price == 0.0 {
+            return None;
+        }
+        let is_wall = |(_, quantity): &(&i64, &u64)| **quantity >= self.wall_quantity;
+        let wall = |side: Side, (price, quantity): (&i64, &u64)| RoundWall {
+            side,
+            price: *price,
+            quantity: *quantity,
+            distance_bps: (*price as f64 - mid_price).abs() / mid_price.abs() * 10_000.0
+        };
+        let bid = self.bid_levels.range(..=mid_price.floor() as i64).rev().find(is_wall).map(|level| wall(Side::Bid, level));
+        let ask = self.ask_levels.range(mid_price.ceil() as i64..).find(is_wall).map(|level| wall(Side::Ask, level));
+        match (bid, ask) {
+            (Some(bid), Some(ask)) if ask.distance_bps < bid.distance_bps => Some(ask),
+            (Some(bid), _) => Some(bid),
+            (None, ask) => ask
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> (&mut BTreeMap<i64, u64>, &mut u64) {
+        match side {
+            Side::Bid => (&mut self.bid_levels, &mut self.bid_round_quantity),
+            Side::Ask => (&mut self.ask_levels, &mut self.ask_round_quantity)
+        }
+    }
+}
+
+impl Projection for RoundNumbers {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        self.totals.apply(event, timestamp);
+        match *event {
+            BookEvent::LevelSet { side, price, quantity, .. } if self.is_round(price) => {
+                let (levels, total) = self.side_mut(side);
+                let previous = levels.insert(price, quantity).unwrap_or(0);
+                *total = total.saturating_sub(previous).saturating_add(quantity);
+            },
+            BookEvent::LevelRemoved { side, price, .. } => {
+                let (levels, total) = self.side_mut(side);
+                *total = total.saturating_sub(levels.remove(&price).unwrap_or(0));
+            },
+            BookEvent::Cleared => {
+                self.bid_levels.clear();
+                self.ask_levels.clear();
+                self.bid_round_quantity = 0;
+                self.ask_round_quantity = 0;
+            },
+            BookEvent::Rescaled { price_multiplier, quantity_multiplier } => {
+                self.granularity = self.granularity.saturating_mul(price_multiplier);
+                self.wall_quantity = self.wall_quantity.saturating_mul(quantity_multiplier);
+                rescale_tree(&mut self.bid_levels, price_multiplier, quantity_multiplier);
+                rescale_tree(&mut self.ask_levels, price_multiplier, quantity_multiplier);
+                self.bid_round_quantity = self.bid_round_quantity.saturating_mul(quantity_multiplier);
+                self.ask_round_quantity = self.ask_round_quantity.saturating_mul(quantity_multiplier);
+            },
+            _ => ()
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Report from the registered RoundNumbers projection at the current mid. None without one
+    */
+    pub fn round_numbers(&self) -> Option<RoundNumberReport> {
+        let mid_price = self.summary(Some(1)).mid_price;
+        self.projection::<RoundNumbers>().map(|round_numbers| round_numbers.report(mid_price))
+    }
+}
@@ -0,0 +1,307 @@
+/*
+Purpose: Symbol-sharded book ownership across worker threads with a routing ingress
+*/
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::{BookReader, BookUpdate, Orderbook};
+
+/*
+How a shard worker waits for updates. Park blocks on the queue; BusySpin polls it without yielding,
+trading a full core for wake-up latency
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitStrategy {
+    #[default]
+    Park,
+    BusySpin
+}
+
+/*
+Per-shard thread options. core pins the worker to that core id when built with the affinity feature;
+if the feature is off, the core does not exist or the platform refuses, the worker runs unpinned
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShardOptions {
+    pub core: Option<usize>,
+    pub wait: WaitStrategy
+}
+
+/*
+Counters for one shard. pending is updates routed to the shard and not yet applied;
+pinned is whether the worker is running on its requested core
+*/
+#[derive(Debug, Default)]
+pub struct ShardMetrics {
+    pub books: usize,
+    pub pinned: AtomicBool,
+    pub processed: AtomicU64,
+    pub pending: AtomicU64,
+    pub unknown_symbol: AtomicU64
+}
+
+enum ShardMessage {
+    Update(String, BookUpdate),
+    Close
+}
+
+/*
+Ingress side: hashes a symbol to its shard and queues the update there. Cloneable across feed threads
+*/
+#[derive(Clone)]
+pub struct ShardRouter {
+    senders: Vec<SyncSender<ShardMessage>>,
+    metrics: Vec<Arc<ShardMetrics>>
+}
+
+impl ShardRouter {
+    pub fn shard_count(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn shard_of(&self, symbol: &str) -> usize {
+        shard_index(symbol, self.senders.len())
+    }
+
+    /*
+    Queue an update for the shard owning symbol, blocking while that shard's queue is full.
+    Returns false once the shards have been joined
+    */
+    pub fn send(&self, symbol: &str, update: BookUpdate) -> bool {
+        let shard = self.shard_of(symbol);
+        self.metrics[shard].pending.fetch_add(1, Ordering::Relaxed);
+        match self.senders[shard].send(ShardMessage::Update(symbol.to_string(), update)) {
+            Ok(()) => true,
+            Err(_) => {
+                self.metrics[shard].pending.fetch_sub(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> &[Arc<ShardMetrics>] {
+        &self.metrics
+    }
+}
+
+/*
+Books hash-sharded by symbol over shards worker threads, each owning its books single-threaded and
+fed through a bounded queue of capacity. on_update runs on the owning shard after each applied update.
+A shard applies what is queued as one batch of at most capacity updates and publishes the touched books
+to their readers after it, so a shard that never drains still publishes. Updates for symbols not
+registered at spawn are counted and dropped
+*/
+pub struct ShardedBooks<M> {
+    router: ShardRouter,
+    readers: HashMap<String, BookReader>,
+    handles: Vec<JoinHandle<HashMap<String, Orderbook<M>>>>
+}
+
+impl<M: Send + 'static> ShardedBooks<M> {
+    pub fn spawn<F>(books: Vec<(String, Orderbook<M>)>, shards: usize, capacity: usize, on_update: F) -> ShardedBooks<M>
+    where F: FnMut(&str, &Orderbook<M>) + Clone + Send + 'static {
+        ShardedBooks::spawn_with(books, vec![ShardOptions::default(); shards.max(1)], capacity, on_update)
+    }
+
+    /*
+    One shard per entry of options
+    */
+    pub fn spawn_with<F>(books: Vec<(String, Orderbook<M>)>, options: Vec<ShardOptions>, capacity: usize, on_update: F) -> ShardedBooks<M>
+    where F: FnMut(&str, &Orderbook<M>) + Clone + Send + 'static {
+        let options = match options.is_empty() {
+            true => vec![ShardOptions::default()],
+            false => options
+        };
+        let shards = options.len();
+        let mut owned: Vec<HashMap<String, Orderbook<M>>> = (0..shards).map(|_| HashMap::new()).collect();
+        let mut readers = HashMap::with_capacity(books.len());
+        for (symbol, book) in books {
+            readers.insert(symbol.clone(), BookReader::new(&book));
+            owned[shard_index(&symbol, shards)].insert(symbol, book);
+        }
+        let mut senders = Vec::with_capacity(shards);
+        let mut metrics = Vec::with_capacity(shards);
+        let mut handles = Vec::with_capacity(shards);
+        for (books, shard_options) in owned.into_iter().zip(options) {
+            let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+            let shard_metrics = Arc::new(ShardMetrics {
+                books: books.len(),
+                ..ShardMetrics::default()
+            });
+            let worker_metrics = shard_metrics.clone();
+            let worker_readers: HashMap<String, BookReader> = books.keys().filter_map(|symbol| Some((symbol.clone(), readers.get(symbol)?.clone()))).collect();
+            let on_update = on_update.clone();
+            handles.push(thread::spawn(move || run_shard(books, worker_readers, receiver, capacity.max(1), shard_options, worker_metrics, on_update)));
+            senders.push(sender);
+            metrics.push(shard_metrics);
+        }
+        ShardedBooks {
+            router: ShardRouter { senders, metrics },
+            readers,
+            handles
+        }
+    }
+
+    pub fn router(&self) -> ShardRouter {
+        self.router.clone()
+    }
+
+    /*
+    Reader of symbol's latest snapshot, published after each batch that touched it. None for symbols not
+    registered at spawn
+    */
+    pub fn reader(&self, symbol: &str) -> Option<BookReader> {
+        self.readers.get(symbol).cloned()
+    }
+
+    /*
+    Let every shard drain what is already queued, then return all books keyed by symbol
+    */
+    pub fn join(self) -> thread::Result<HashMap<String, Orderbook<M>>> {
+        for sender in self.router.senders.iter() {
+            let _ = sender.send(ShardMessage::Close);
+        }
+        let mut books = HashMap::new();
+        for handle in self.handles {
+            books.extend(handle.join()?);
+        }
+        Ok(books)
+    }
+}
+
+fn run_shard<M, F>(mut books: HashMap<String, Orderbook<M>>, readers: HashMap<String, BookReader>, receiver: Receiver<ShardMessage>, batch_limit: usize, options: ShardOptions, metrics: Arc<ShardMetrics>, mut on_update: F) -> HashMap<String, Orderbook<M>>
+where F: FnMut(&str, &Orderbook<M>) {
+    if let Some(core) = options.core {
+        metrics.pinned.store(pin_current_thread(core), Ordering::Relaxed);
+    }
+    let mut touched: HashSet<String> = HashSet::new();
+    let mut batched = 0;
+    let mut message = next_message(&receiver, options.wait);
+    while let Some(ShardMessage::Update(symbol, update)) = message {
+        metrics.pending.fetch_sub(1, Ordering::Relaxed);
+        match books.get_mut(&symbol) {
+            Some(book) => {
+                book.process(update.bids, update.asks, update.is_snapshot);
+                metrics.processed.fetch_add(1, Ordering::Relaxed);
+                on_update(&symbol, book);
+                touched.insert(symbol);
+            },
+            None => {
+                metrics.unknown_symbol.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        batched += 1;
+        // A full batch ends as if the queue had run dry
+        let next = match batched < batch_limit {
+            true => receiver.try_recv(),
+            false => Err(TryRecvError::Empty)
+        };
+        message = match next {
+            Ok(next) => Some(next),
+            Err(TryRecvError::Disconnected) => None,
+            Err(TryRecvError::Empty) => {
+                publish(&books, &readers, &mut touched);
+                batched = 0;
+                next_message(&receiver, options.wait)
+            }
+        };
+    }
+    publish(&books, &readers, &mut touched);
+    books
+}
+
+/*
+End of a batch: publish every book it touched
+*/
+fn publish<M>(books: &HashMap<String, Orderbook<M>>, readers: &HashMap<String, BookReader>, touched: &mut HashSet<String>) {
+    for symbol in touched.drain() {
+        if let (Some(book), Some(reader)) = (books.get(&symbol), readers.get(&symbol)) {
+            reader.publish(book);
+        }
+    }
+}
+
+fn next_message(receiver: &Receiver<ShardMessage>, wait: WaitStrategy) -> Option<ShardMessage> {
+    match wait {
+        WaitStrategy::Park => receiver.recv().ok(),
+        WaitStrategy::BusySpin => loop {
+            match receiver.try_recv() {
+                Ok(message) => return Some(message),
+                Err(TryRecvError::Empty) => hint::spin_loop(),
+                Err(TryRecvError::Disconnected) => return None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "affinity")]
+fn pin_current_thread(core: usize) -> bool {
+    core_affinity::get_core_ids()
+        .and_then(|core_ids| core_ids.into_iter().find(|core_id| core_id.id == core))
+        .is_some_and(core_affinity::set_for_current)
+}
+
+#[cfg(not(feature = "affinity"))]
+fn pin_current_thread(_core: usize) -> bool {
+    false
+}
+
+/*
+DefaultHasher::new is unkeyed, so a symbol maps to the same shard across runs
+*/
+fn shard_index(symbol: &str, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    symbol.hash(&mut hasher);
+    (hasher.finish() % (shards as u64)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn saturated_shard_publishes_after_each_full_batch() {
+        let slot: Arc<Mutex<Option<(ShardRouter, BookReader)>>> = Arc::new(Mutex::new(None));
+        let seen = Arc::new(AtomicU64::new(0));
+        let (worker_slot, worker_seen) = (slot.clone(), seen.clone());
+        let mut remaining = 40;
+        // Every applied update queues the next one, so the queue never runs dry until remaining is spent
+        let sharded = ShardedBooks::spawn(vec![("A".to_string(), Orderbook::<()>::new(Some(2), Some(2)))], 1, 4, move |_, book: &Orderbook| {
+            if let Ok(guard) = worker_slot.lock() {
+                if let Some((router, reader)) = guard.as_ref() {
+                    worker_seen.fetch_max(reader.version(), Ordering::Relaxed);
+                    if remaining > 0 {
+                        remaining -= 1;
+                        let bid = book.bids.len() as f64 + 1.0;
+                        router.send("A", BookUpdate { bids: vec![(bid, 1.0)], asks: Vec::new(), is_snapshot: false });
+                    }
+                }
+            }
+        });
+        let reader = sharded.reader("A");
+        if let (Ok(mut guard), Some(reader)) = (slot.lock(), reader) {
+            *guard = Some((sharded.router(), reader));
+        }
+        assert!(sharded.router().send("A", BookUpdate { bids: vec![(1.0, 1.0)], asks: Vec::new(), is_snapshot: true }));
+        let metrics = sharded.router().metrics()[0].clone();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while metrics.processed.load(Ordering::Relaxed) < 41 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let books = sharded.join().unwrap_or_default();
+        assert_eq!(books.get("A").map(|book| book.bids.len()), Some(41));
+        // Published after every 4 updates, so the last update sees the tenth publication
+        assert_eq!(seen.load(Ordering::Relaxed), 10);
+    }
+}
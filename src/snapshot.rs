@@ -0,0 +1,259 @@
+/*
+Purpose: Immutable point-in-time book value with queries over contiguous level arrays
+*/
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::l2::{scale, unscale, unscale_signed};
+use crate::{BookState, Orderbook};
+
+/*
+Levels are scaled (price, quantity) sorted best first: bids descending, asks ascending.
+Queries mirror the Orderbook getters but read the arrays directly, and the value holds no
+reference to the live book. Simulations ignore require_live; check state instead
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSnapshot {
+    bids: Vec<(i64, u64)>,
+    asks: Vec<(i64, u64)>,
+    timestamp: u64,
+    state: BookState,
+    price_factor: f64,
+    quantity_factor: f64
+}
+
+impl<M> Orderbook<M> {
+    pub fn snapshot(&self) -> BookSnapshot {
+        self.snapshot_depth(usize::MAX)
+    }
+
+    /*
+    Snapshot of only the best depth levels per side
+    */
+    pub fn snapshot_depth(&self, depth: usize) -> BookSnapshot {
+        BookSnapshot {
+            bids: self.bids.iter().rev().take(depth).map(|(price, quantity)| (*price, *quantity)).collect(),
+            asks: self.asks.iter().take(depth).map(|(price, quantity)| (*price, *quantity)).collect(),
+            timestamp: self.timestamp,
+            state: self.state,
+            price_factor: self.price_factor,
+            quantity_factor: self.quantity_factor
+        }
+    }
+}
+
+impl BookSnapshot {
+    pub(crate) fn from_parts(bids: Vec<(i64, u64)>, asks: Vec<(i64, u64)>, timestamp: u64, state: BookState, price_factor: f64, quantity_factor: f64) -> BookSnapshot {
+        BookSnapshot {
+            bids,
+            asks,
+            timestamp,
+            state,
+            price_factor,
+            quantity_factor
+        }
+    }
+
+    pub fn bids(&self) -> &[(i64, u64)] {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &[(i64, u64)] {
+        &self.asks
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn state(&self) -> BookState {
+        self.state
+    }
+
+    pub fn price_factor(&self) -> f64 {
+        self.price_factor
+    }
+
+    pub fn quantity_factor(&self) -> f64 {
+        self.quantity_factor
+    }
+
+    pub fn unscale_price(&self, price: i64) -> f64 {
+        unscale_signed(price, self.price_factor)
+    }
+
+    pub fn unscale_qty(&self, quantity: u64) -> f64 {
+        unscale(quantity, self.quantity_factor)
+    }
+
+    pub fn get_best_bid(&self) -> Option<(i64, u64)> {
+        self.bids.first().copied()
+    }
+
+    pub fn get_best_ask(&self) -> Option<(i64, u64)> {
+        self.asks.first().copied()
+    }
+
+    /*
+    Scaled ask minus bid, negative for a crossed book
+    */
+    pub fn get_spread(&self) -> Option<i64> {
+        Some(self.get_best_ask()?.0.saturating_sub(self.get_best_bid()?.0))
+    }
+
+    /*
+    Scaled midpoint of the best bid and ask
+    */
+    pub fn get_mid_price(&self) -> Option<f64> {
+        Some(((self.get_best_bid()?.0 as f64) + (self.get_best_ask()?.0 as f64)) / 2.0)
+    }
+
+    pub fn get_weighted_bid(&self) -> Option<f64> {
+        weighted_price(&self.bids)
+    }
+
+    pub fn get_weighted_ask(&self) -> Option<f64> {
+        weighted_price(&self.asks)
+    }
+
+    pub fn get_total_bid_quantity(&self) -> f64 {
+        self.unscale_qty(total_quantity(&self.bids))
+    }
+
+    pub fn get_total_ask_quantity(&self) -> f64 {
+        self.unscale_qty(total_quantity(&self.asks))
+    }
+
+    /*
+    Scaled average price for taking quantity (unscaled) from the asks, None if depth is insufficient
+    */
+    pub fn simulate_taker_buy(&self, quantity: f64) -> Option<f64> {
+        self.simulate(&self.asks, quantity)
+    }
+
+    pub fn simulate_taker_sell(&self, quantity: f64) -> Option<f64> {
+        self.simulate(&self.bids, quantity)
+    }
+
+    fn simulate(&self, levels: &[(i64, u64)], quantity: f64) -> Option<f64> {
+        if !(quantity.is_finite() && quantity > 0.0) {
+            return None;
+        }
+        let mut amount_remaining = scale(quantity, self.quantity_factor);
+        let mut price_numerator: i128 = 0;
+        for (price, level_quantity) in levels.iter() {
+            let taken = amount_remaining.min(*level_quantity);
+            price_numerator = price_numerator.saturating_add((taken as i128) * (*price as i128));
+            amount_remaining -= taken;
+            if amount_remaining == 0 {
+                return Some((price_numerator as f64) / (self.quantity_factor * quantity));
+            }
+        }
+        None
+    }
+}
+
+struct Published {
+    snapshot: Arc<BookSnapshot>,
+    version: u64
+}
+
+struct ReaderCell {
+    published: Mutex<Published>
+}
+
+/*
+Latest snapshot of a book owned by another thread (Pipeline, ShardedBooks). The owner replaces it whole
+after a batch of exchange messages, never part-way through one, so readers see each message applied
+entirely or not at all. The owner publishes after every batch whether or not a reader has been handed
+out, so a reader taken late starts from the last batch rather than from the book at spawn
+*/
+#[derive(Clone)]
+pub struct BookReader {
+    cell: Arc<ReaderCell>
+}
+
+impl BookReader {
+    pub(crate) fn new<M>(book: &Orderbook<M>) -> BookReader {
+        BookReader {
+            cell: Arc::new(ReaderCell {
+                published: Mutex::new(Published { snapshot: Arc::new(book.snapshot()), version: 0 })
+            })
+        }
+    }
+
+    pub fn load(&self) -> Arc<BookSnapshot> {
+        self.cell.published.lock().unwrap_or_else(PoisonError::into_inner).snapshot.clone()
+    }
+
+    /*
+    Number of snapshots published so far; unchanged means there is nothing new to load
+    */
+    pub fn version(&self) -> u64 {
+        self.cell.published.lock().unwrap_or_else(PoisonError::into_inner).version
+    }
+
+    /*
+    Called by the owner between messages. The snapshot is built before taking the lock, so readers only
+    ever wait for a pointer swap
+    */
+    pub(crate) fn publish<M>(&self, book: &Orderbook<M>) {
+        let snapshot = Arc::new(book.snapshot());
+        let mut published = self.cell.published.lock().unwrap_or_else(PoisonError::into_inner);
+        published.snapshot = snapshot;
+        published.version += 1;
+    }
+}
+
+fn weighted_price(levels: &[(i64, u64)]) -> Option<f64> {
+    if levels.is_empty() {
+        return None;
+    }
+    let numerator = levels.iter().fold(0i128, |numerator, (price, quantity)| numerator.saturating_add((*price as i128) * (*quantity as i128)));
+    Some((numerator as f64) / (total_quantity(levels) as f64))
+}
+
+fn total_quantity(levels: &[(i64, u64)]) -> u64 {
+    levels.iter().fold(0u64, |total, (_, quantity)| total.saturating_add(*quantity))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::{BookUpdate, OverflowPolicy, Pipeline, ShardedBooks};
+
+    use super::*;
+
+    fn update(bid: f64) -> BookUpdate {
+        BookUpdate { bids: vec![(bid, 1.0)], asks: vec![(bid + 1.0, 1.0)], is_snapshot: true }
+    }
+
+    fn wait_until(done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn reader_taken_after_updates_sees_last_batch() {
+        let pipeline = Pipeline::spawn(Orderbook::<()>::new(Some(2), Some(2)), 8, OverflowPolicy::Block, |_| {});
+        let metrics = pipeline.sender().metrics();
+        assert!(pipeline.sender().send(update(100.0)));
+        wait_until(|| metrics.processed.load(Ordering::Relaxed) == 1);
+        let reader = pipeline.reader();
+        wait_until(|| reader.version() == 1);
+        assert_eq!(reader.load().get_best_bid(), Some((10_000, 100)));
+
+        let sharded = ShardedBooks::spawn(vec![("A".to_string(), Orderbook::<()>::new(Some(2), Some(2)))], 1, 8, |_, _| {});
+        assert!(sharded.router().send("A", update(50.0)));
+        let metrics = sharded.router().metrics()[0].clone();
+        wait_until(|| metrics.processed.load(Ordering::Relaxed) == 1);
+        let reader = sharded.reader("A");
+        wait_until(|| reader.as_ref().is_some_and(|reader| reader.version() == 1));
+        assert_eq!(reader.map(|reader| reader.load().get_best_bid()), Some(Some((5_000, 100))));
+    }
+}
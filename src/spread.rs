@@ -0,0 +1,106 @@
+/*
+Purpose: Derived executable spread book (A minus B, or A over B) from two leg books
+*/
+
+use crate::{DepthView, Orderbook};
+
+/*
+Difference prices the spread as A - hedge_ratio * B; Ratio as A / B
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreadKind {
+    Difference,
+    Ratio
+}
+
+/*
+Executable levels for trading one unit of A against hedge_ratio units of B, unscaled (price, quantity in
+units of A), best first. Buying the spread lifts A's asks and hits B's bids (asks); selling it hits A's
+bids and lifts B's asks (bids). Levels are marginal: each is filled after the ones before it.
+Only the best depth levels of each leg are used, and the spread is recomputed only when those change
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpreadBook {
+    pub kind: SpreadKind,
+    pub hedge_ratio: f64,
+    pub depth: usize,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+    legs: Option<(DepthView, DepthView)>
+}
+
+impl SpreadBook {
+    pub fn new(kind: SpreadKind, hedge_ratio: f64, depth: usize) -> SpreadBook {
+        SpreadBook {
+            kind,
+            hedge_ratio,
+            depth,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            legs: None
+        }
+    }
+
+    /*
+    Refresh from the legs after either changed. Returns whether the spread levels were recomputed
+    */
+    pub fn update<M, N>(&mut self, a: &Orderbook<M>, b: &Orderbook<N>) -> bool {
+        let legs = (DepthView::from_book(a, self.depth), DepthView::from_book(b, self.depth));
+        if self.legs.as_ref() == Some(&legs) {
+            return false;
+        }
+        let unscale_a = |levels: &[(i64, u64)]| -> Vec<(f64, f64)> { levels.iter().map(|(price, quantity)| (a.unscale_price(*price), a.unscale_qty(*quantity))).collect() };
+        let unscale_b = |levels: &[(i64, u64)]| -> Vec<(f64, f64)> { levels.iter().map(|(price, quantity)| (b.unscale_price(*price), b.unscale_qty(*quantity))).collect() };
+        self.asks = self.convolve(&unscale_a(&legs.0.asks), &unscale_b(&legs.1.bids));
+        self.bids = self.convolve(&unscale_a(&legs.0.bids), &unscale_b(&legs.1.asks));
+        self.legs = Some(legs);
+        true
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.first().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.first().copied()
+    }
+
+    /*
+    Walk both legs best first, pairing quantity (B quantity converted to units of A by hedge_ratio)
+    */
+    fn convolve(&self, a_levels: &[(f64, f64)], b_levels: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        let mut levels: Vec<(f64, f64)> = Vec::new();
+        if !(self.hedge_ratio.is_finite() && self.hedge_ratio > 0.0) {
+            return levels;
+        }
+        let mut a_levels = a_levels.iter().copied();
+        let mut b_levels = b_levels.iter().copied();
+        let (mut a_level, mut b_level) = (a_levels.next(), b_levels.next());
+        let mut a_remaining = a_level.map_or(0.0, |(_, quantity)| quantity);
+        let mut b_remaining = b_level.map_or(0.0, |(_, quantity)| quantity / self.hedge_ratio);
+        while let (Some((a_price, _)), Some((b_price, _))) = (a_level, b_level) {
+            let quantity = a_remaining.min(b_remaining);
+            let price = match self.kind {
+                SpreadKind::Difference => a_price - self.hedge_ratio * b_price,
+                SpreadKind::Ratio => a_price / b_price
+            };
+            if quantity > 0.0 && price.is_finite() {
+                match levels.last_mut() {
+                    Some((last_price, last_quantity)) if *last_price == price => *last_quantity += quantity,
+                    _ => levels.push((price, quantity))
+                }
+            }
+            a_remaining -= quantity;
+            b_remaining -= quantity;
+            if a_remaining <= 0.0 {
+                a_level = a_levels.next();
+                a_remaining = a_level.map_or(0.0, |(_, quantity)| quantity);
+            }
+            if b_remaining <= 0.0 {
+                b_level = b_levels.next();
+                b_remaining = b_level.map_or(0.0, |(_, quantity)| quantity / self.hedge_ratio);
+            }
+        }
+        levels
+    }
+}
@@ -0,0 +1,60 @@
+/*
+Purpose: Level-count and memory introspection for capacity planning
+*/
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use crate::Orderbook;
+
+/*
+Entry counts of the level trees and the auxiliary trees kept alongside them, the prune counters,
+and an estimate of heap bytes held by the trees (not by metadata payloads' own allocations or projections)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookStats {
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub order_count_entries: usize,
+    pub update_time_entries: usize,
+    pub meta_entries: usize,
+    pub projections: usize,
+    pub pruned_levels: u64,
+    pub pruned_quantity: u64,
+    pub approximate_heap_bytes: usize
+}
+
+impl<M> Orderbook<M> {
+    pub fn stats(&self) -> BookStats {
+        BookStats {
+            bid_levels: self.bids.len(),
+            ask_levels: self.asks.len(),
+            order_count_entries: self.bid_order_counts.len() + self.ask_order_counts.len(),
+            update_time_entries: self.bid_update_times.len() + self.ask_update_times.len(),
+            meta_entries: self.bid_meta.len() + self.ask_meta.len(),
+            projections: self.projections.len(),
+            pruned_levels: self.pruned_levels,
+            pruned_quantity: self.pruned_quantity,
+            approximate_heap_bytes: tree_bytes(&self.bids)
+                + tree_bytes(&self.asks)
+                + tree_bytes(&self.bid_order_counts)
+                + tree_bytes(&self.ask_order_counts)
+                + tree_bytes(&self.bid_update_times)
+                + tree_bytes(&self.ask_update_times)
+                + tree_bytes(&self.bid_meta)
+                + tree_bytes(&self.ask_meta)
+        }
+    }
+}
+
+/*
+BTreeMap nodes hold up to 11 entries and run about two thirds full, plus a small header per node
+*/
+fn tree_bytes<K, V>(tree: &BTreeMap<K, V>) -> usize {
+    let entries = tree.len();
+    if entries == 0 {
+        return 0;
+    }
+    let nodes = entries.div_ceil(7);
+    nodes * (11 * (size_of::<K>() + size_of::<V>()) + 16)
+}
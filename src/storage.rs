@@ -0,0 +1,284 @@
+/*
+Purpose: SQLite storage of sampled snapshots, trades and rollups with range queries for replay
+*/
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, Error, Result};
+
+use crate::{BookSnapshot, BookUpdate, Fill, RollupReport, Side};
+
+/*
+Snapshots are stored scaled with the factors needed to unscale them; levels keep their position,
+0 being the best level of the side. Timestamps are ms on the book clock. Ranges are [from, to)
+*/
+pub const SQLITE_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    price_factor REAL NOT NULL,
+    quantity_factor REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snapshots_symbol_timestamp ON snapshots (symbol, timestamp);
+CREATE TABLE IF NOT EXISTS snapshot_levels (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id),
+    side TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    quantity INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snapshot_levels_snapshot ON snapshot_levels (snapshot_id);
+CREATE TABLE IF NOT EXISTS trades (
+    symbol TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    side TEXT NOT NULL,
+    price REAL NOT NULL,
+    quantity REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS trades_symbol_timestamp ON trades (symbol, timestamp);
+CREATE TABLE IF NOT EXISTS rollups (
+    symbol TEXT NOT NULL,
+    start_ms INTEGER NOT NULL,
+    end_ms INTEGER NOT NULL,
+    traded_volume REAL NOT NULL,
+    trades INTEGER NOT NULL,
+    average_spread REAL,
+    depth_p50 REAL,
+    depth_p90 REAL,
+    depth_p99 REAL,
+    observed_ms INTEGER NOT NULL,
+    live_ms INTEGER NOT NULL,
+    gaps INTEGER NOT NULL,
+    resyncs INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS rollups_symbol_start ON rollups (symbol, start_ms);
+";
+
+/*
+Each insert_* call writes its whole batch in one transaction
+*/
+pub struct SqliteStore {
+    connection: Connection
+}
+
+impl SqliteStore {
+    /*
+    Open or create the database at path and apply SQLITE_SCHEMA
+    */
+    pub fn open(path: &Path) -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<SqliteStore> {
+        connection.execute_batch(SQLITE_SCHEMA)?;
+        Ok(SqliteStore { connection })
+    }
+
+    /*
+    Fails with a conversion error, writing nothing, if any level holds more than i64::MAX scaled units
+    */
+    pub fn insert_snapshots(&mut self, snapshots: &[(&str, &BookSnapshot)]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert_snapshot = transaction.prepare_cached(
+                "INSERT INTO snapshots (symbol, timestamp, price_factor, quantity_factor) VALUES (?1, ?2, ?3, ?4)"
+            )?;
+            let mut insert_level = transaction.prepare_cached(
+                "INSERT INTO snapshot_levels (snapshot_id, side, position, price, quantity) VALUES (?1, ?2, ?3, ?4, ?5)"
+            )?;
+            for (symbol, snapshot) in snapshots.iter() {
+                let id = insert_snapshot.insert(params![symbol, snapshot.timestamp() as i64, snapshot.price_factor(), snapshot.quantity_factor()])?;
+                for (side, levels) in [(Side::Bid, snapshot.bids()), (Side::Ask, snapshot.asks())] {
+                    for (position, (price, quantity)) in levels.iter().enumerate() {
+                        // Quantities above i64::MAX would wrap negative in an INTEGER column
+                        let quantity = i64::try_from(*quantity).map_err(|error| Error::ToSqlConversionFailure(Box::new(error)))?;
+                        insert_level.execute(params![id, side_name(side), position as i64, price, quantity])?;
+                    }
+                }
+            }
+        }
+        transaction.commit()
+    }
+
+    pub fn insert_trades(&mut self, trades: &[(&str, Fill)]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO trades (symbol, timestamp, side, price, quantity) VALUES (?1, ?2, ?3, ?4, ?5)"
+            )?;
+            for (symbol, fill) in trades.iter() {
+                insert.execute(params![symbol, fill.timestamp as i64, side_name(fill.side), fill.price, fill.quantity])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    pub fn insert_rollups(&mut self, reports: &[RollupReport]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO rollups (symbol, start_ms, end_ms, traded_volume, trades, average_spread, depth_p50, depth_p90, depth_p99, observed_ms, live_ms, gaps, resyncs)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"
+            )?;
+            for report in reports.iter() {
+                insert.execute(params![
+                    report.symbol,
+                    report.start as i64,
+                    report.end as i64,
+                    report.traded_volume,
+                    report.trades as i64,
+                    report.average_spread,
+                    report.depth_p50,
+                    report.depth_p90,
+                    report.depth_p99,
+                    report.observed_ms as i64,
+                    report.live_ms as i64,
+                    report.gaps as i64,
+                    report.resyncs as i64
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /*
+    Stored snapshots of symbol in time order as (timestamp, unscaled snapshot update), ready for
+    Orderbook::process in a replay
+    */
+    pub fn load_snapshots(&self, symbol: &str, from: u64, to: u64) -> Result<Vec<(u64, BookUpdate)>> {
+        let mut select_snapshots = self.connection.prepare_cached(
+            "SELECT id, timestamp, price_factor, quantity_factor FROM snapshots
+            WHERE symbol = ?1 AND timestamp >= ?2 AND timestamp < ?3 ORDER BY timestamp, id"
+        )?;
+        let headers = select_snapshots
+            .query_map(params![symbol, sql_bound(from), sql_bound(to)], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?, row.get::<_, f64>(3)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut select_levels = self.connection.prepare_cached(
+            "SELECT side, price, quantity FROM snapshot_levels WHERE snapshot_id = ?1 ORDER BY side, position"
+        )?;
+        let mut snapshots = Vec::with_capacity(headers.len());
+        for (id, timestamp, price_factor, quantity_factor) in headers {
+            let mut update = BookUpdate {
+                bids: Vec::new(),
+                asks: Vec::new(),
+                is_snapshot: true
+            };
+            let levels = select_levels.query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)))?;
+            for level in levels {
+                let (side, price, quantity) = level?;
+                let level = ((price as f64) / price_factor, (quantity as f64) / quantity_factor);
+                match side.as_str() {
+                    "bid" => update.bids.push(level),
+                    _ => update.asks.push(level)
+                }
+            }
+            snapshots.push((timestamp as u64, update));
+        }
+        Ok(snapshots)
+    }
+
+    pub fn load_trades(&self, symbol: &str, from: u64, to: u64) -> Result<Vec<Fill>> {
+        let mut select = self.connection.prepare_cached(
+            "SELECT timestamp, side, price, quantity FROM trades
+            WHERE symbol = ?1 AND timestamp >= ?2 AND timestamp < ?3 ORDER BY timestamp, rowid"
+        )?;
+        let trades = select.query_map(params![symbol, sql_bound(from), sql_bound(to)], |row| {
+            Ok(Fill {
+                timestamp: row.get::<_, i64>(0)? as u64,
+                side: parse_side(&row.get::<_, String>(1)?),
+                price: row.get(2)?,
+                quantity: row.get(3)?
+            })
+        })?;
+        trades.collect()
+    }
+
+    /*
+    Rollups of symbol whose window starts in [from, to)
+    */
+    pub fn load_rollups(&self, symbol: &str, from: u64, to: u64) -> Result<Vec<RollupReport>> {
+        let mut select = self.connection.prepare_cached(
+            "SELECT symbol, start_ms, end_ms, traded_volume, trades, average_spread, depth_p50, depth_p90, depth_p99, observed_ms, live_ms, gaps, resyncs
+            FROM rollups WHERE symbol = ?1 AND start_ms >= ?2 AND start_ms < ?3 ORDER BY start_ms, rowid"
+        )?;
+        let reports = select.query_map(params![symbol, sql_bound(from), sql_bound(to)], |row| {
+            Ok(RollupReport {
+                symbol: row.get(0)?,
+                start: row.get::<_, i64>(1)? as u64,
+                end: row.get::<_, i64>(2)? as u64,
+                traded_volume: row.get(3)?,
+                trades: row.get::<_, i64>(4)? as u64,
+                average_spread: row.get(5)?,
+                depth_p50: row.get(6)?,
+                depth_p90: row.get(7)?,
+                depth_p99: row.get(8)?,
+                observed_ms: row.get::<_, i64>(9)? as u64,
+                live_ms: row.get::<_, i64>(10)? as u64,
+                gaps: row.get::<_, i64>(11)? as u64,
+                resyncs: row.get::<_, i64>(12)? as u64
+            })
+        })?;
+        reports.collect()
+    }
+}
+
+/*
+SQLite integers are signed; bounds past i64::MAX clamp there so open-ended ranges such as
+u64::MAX still match
+*/
+fn sql_bound(timestamp: u64) -> i64 {
+    timestamp.min(i64::MAX as u64) as i64
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Bid => "bid",
+        Side::Ask => "ask"
+    }
+}
+
+fn parse_side(name: &str) -> Side {
+    match name {
+        "bid" => Side::Bid,
+        _ => Side::Ask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BookState;
+
+    fn snapshot(timestamp: u64, quantity: u64) -> BookSnapshot {
+        BookSnapshot::from_parts(vec![(100, quantity)], vec![(101, 1)], timestamp, BookState::Live, 1.0, 1.0)
+    }
+
+    #[test]
+    fn open_ended_ranges_load_everything() -> Result<()> {
+        let mut store = SqliteStore::open_in_memory()?;
+        store.insert_snapshots(&[("BTC", &snapshot(5, 2)), ("BTC", &snapshot(10, 3))])?;
+        store.insert_trades(&[("BTC", Fill { timestamp: 7, side: Side::Bid, price: 100.0, quantity: 1.0 })])?;
+        assert_eq!(store.load_snapshots("BTC", 0, u64::MAX)?.len(), 2);
+        assert_eq!(store.load_snapshots("BTC", 6, u64::MAX)?.len(), 1);
+        assert_eq!(store.load_trades("BTC", 0, u64::MAX)?.len(), 1);
+        assert!(store.load_rollups("BTC", 0, u64::MAX)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_quantities_sqlite_cannot_hold() -> Result<()> {
+        let mut store = SqliteStore::open_in_memory()?;
+        let oversized = snapshot(5, i64::MAX as u64 + 1);
+        assert!(store.insert_snapshots(&[("BTC", &snapshot(1, 2)), ("BTC", &oversized)]).is_err());
+        assert!(store.load_snapshots("BTC", 0, u64::MAX)?.is_empty());
+        store.insert_snapshots(&[("BTC", &snapshot(5, i64::MAX as u64))])?;
+        let loaded = store.load_snapshots("BTC", 0, u64::MAX)?;
+        assert_eq!(loaded[0].1.bids, vec![(100.0, i64::MAX as f64)]);
+        Ok(())
+    }
+}
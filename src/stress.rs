@@ -0,0 +1,74 @@
+/*
+Purpose: Stressed copies of a book for what-if analytics
+*/
+
+use std::collections::BTreeMap;
+
+use crate::{Orderbook, Side};
+
+/*
+RemoveDepth scales quantities on one side (or both with None) down by fraction (0.5 = half the depth).
+WidenSpread moves bids down and asks up by ticks in scaled price units.
+ShiftPrices moves every price up by fraction of its magnitude (0.01 = up 1%); levels that collide are merged
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shock {
+    RemoveDepth { side: Option<Side>, fraction: f64 },
+    WidenSpread { ticks: i64 },
+    ShiftPrices { fraction: f64 }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Copy the book's levels into a new Live book and apply shocks in order.
+    Metadata, listeners and configuration other than decimals are not carried over
+    */
+    pub fn stressed(&self, shocks: &[Shock]) -> Orderbook {
+        let mut book = Orderbook::new(None, None);
+        book.price_factor = self.price_factor;
+        book.quantity_factor = self.quantity_factor;
+        book.bids = self.bids.clone();
+        book.asks = self.asks.clone();
+        book.timestamp = self.timestamp;
+        book.on_processed(true);
+        for shock in shocks.iter() {
+            match *shock {
+                Shock::RemoveDepth { side, fraction } => {
+                    let keep = (1.0 - fraction).clamp(0.0, 1.0);
+                    if side != Some(Side::Ask) {
+                        scale_quantities(&mut book.bids, keep);
+                    }
+                    if side != Some(Side::Bid) {
+                        scale_quantities(&mut book.asks, keep);
+                    }
+                },
+                Shock::WidenSpread { ticks } => {
+                    book.bids = map_prices(&book.bids, |price| price.saturating_sub(ticks));
+                    book.asks = map_prices(&book.asks, |price| price.saturating_add(ticks));
+                },
+                Shock::ShiftPrices { fraction } => {
+                    let shift = |price: i64| (price as f64 + (price as f64).abs() * fraction).round() as i64;
+                    book.bids = map_prices(&book.bids, shift);
+                    book.asks = map_prices(&book.asks, shift);
+                }
+            }
+        }
+        book
+    }
+}
+
+fn scale_quantities(levels: &mut BTreeMap<i64, u64>, keep: f64) {
+    for quantity in levels.values_mut() {
+        *quantity = ((*quantity as f64) * keep) as u64;
+    }
+    levels.retain(|_, quantity| *quantity > 0);
+}
+
+fn map_prices<F: Fn(i64) -> i64>(levels: &BTreeMap<i64, u64>, f: F) -> BTreeMap<i64, u64> {
+    let mut mapped: BTreeMap<i64, u64> = BTreeMap::new();
+    for (price, quantity) in levels.iter() {
+        let level = mapped.entry(f(*price)).or_insert(0);
+        *level = level.saturating_add(*quantity);
+    }
+    mapped
+}
@@ -0,0 +1,222 @@
+/*
+Purpose: Detection of aggressive sweeps through several levels from the trade tape and book deltas
+*/
+
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::{BookEvent, Projection, Side};
+
+/*
+A taker run that cleared at least min_levels resting levels. aggressor is Side::Bid for a buy sweep
+through the asks. Prices are scaled; first_price is the first trade's and last_price the last trade's,
+so the range is last_price - first_price in the sweep's direction. quantity is the scaled total traded
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sweep {
+    pub aggressor: Side,
+    pub start: u64,
+    pub end: u64,
+    pub first_price: i64,
+    pub last_price: i64,
+    pub quantity: u64,
+    pub trades: usize,
+    pub levels_cleared: usize
+}
+
+impl Sweep {
+    /*
+    Scaled price distance covered, always non-negative
+    */
+    pub fn price_range(&self) -> i64 {
+        self.last_price.saturating_sub(self.first_price).saturating_abs()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Run {
+    start: u64,
+    last: u64,
+    first_price: i64,
+    last_price: i64,
+    quantity: u64,
+    trades: usize,
+    cleared: BTreeSet<i64>
+}
+
+/*
+Projection grouping same-direction trades into runs: a trade joins the aggressor's current run if it
+comes within window ms of the run's last trade at the same or a worse price for the taker. Removals of
+resting levels within the run's traded price range, within window ms of its last trade, count as levels
+cleared by it. A run closes when a trade no longer fits it or when a book event arrives more than window
+ms after its last trade; it is reported as a Sweep if it cleared at least min_levels levels. Trades must
+be recorded before the book update they caused. A rescale of the book's decimals rescales open runs and
+retained sweeps. Sweeps are kept in a ring of the most recent capacity
+for drain_sweeps
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepDetector {
+    pub window: u64,
+    pub min_levels: usize,
+    pub capacity: usize,
+    buy: Option<Run>,
+    sell: Option<Run>,
+    sweeps: VecDeque<Sweep>
+}
+
+impl SweepDetector {
+    pub fn new(window: u64, min_levels: usize, capacity: usize) -> SweepDetector {
+        SweepDetector {
+            window,
+            min_levels: min_levels.max(1),
+            capacity: capacity.max(1),
+            buy: None,
+            sell: None,
+            sweeps: VecDeque::with_capacity(capacity.max(1))
+        }
+    }
+
+    /*
+    Record a trade by aggressor side (Side::Bid for buyer-initiated) at a scaled price and quantity.
+    Use Orderbook::projection_mut to reach the detector registered on the book
+    */
+    pub fn record_trade(&mut self, aggressor: Side, price: i64, quantity: u64, timestamp: u64) {
+        let window = self.window;
+        let joins = match self.run(aggressor) {
+            Some(run) => timestamp.saturating_sub(run.last) <= window && match aggressor {
+                Side::Bid => price >= run.last_price,
+                Side::Ask => price <= run.last_price
+            },
+            None => false
+        };
+        if !joins {
+            self.close(aggressor);
+        }
+        let run = self.run_mut(aggressor).get_or_insert(Run {
+            start: timestamp,
+            last: timestamp,
+            first_price: price,
+            last_price: price,
+            quantity: 0,
+            trades: 0,
+            cleared: BTreeSet::new()
+        });
+        run.last = timestamp;
+        run.last_price = price;
+        run.quantity = run.quantity.saturating_add(quantity);
+        run.trades += 1;
+    }
+
+    /*
+    Close runs idle for more than window ms at now, e.g. on a timer when no book events arrive
+    */
+    pub fn flush(&mut self, now: u64) {
+        for aggressor in [Side::Bid, Side::Ask] {
+            if self.run(aggressor).is_some_and(|run| now.saturating_sub(run.last) > self.window) {
+                self.close(aggressor);
+            }
+        }
+    }
+
+    /*
+    Sweeps detected since the last drain, oldest first
+    */
+    pub fn drain_sweeps(&mut self) -> Vec<Sweep> {
+        self.sweeps.drain(..).collect()
+    }
+
+    fn run(&self, aggressor: Side) -> Option<&Run> {
+        match aggressor {
+            Side::Bid => self.buy.as_ref(),
+            Side::Ask => self.sell.as_ref()
+        }
+    }
+
+    fn run_mut(&mut self, aggressor: Side) -> &mut Option<Run> {
+        match aggressor {
+            Side::Bid => &mut self.buy,
+            Side::Ask => &mut self.sell
+        }
+    }
+
+    fn rescale(&mut self, price_multiplier: i64, quantity_multiplier: u64) {
+        for run in [&mut self.buy, &mut self.sell].into_iter().flatten() {
+            run.first_price = run.first_price.saturating_mul(price_multiplier);
+            run.last_price = run.last_price.saturating_mul(price_multiplier);
+            run.quantity = run.quantity.saturating_mul(quantity_multiplier);
+            run.cleared = run.cleared.iter().map(|price| price.saturating_mul(price_multiplier)).collect();
+        }
+        for sweep in self.sweeps.iter_mut() {
+            sweep.first_price = sweep.first_price.saturating_mul(price_multiplier);
+            sweep.last_price = sweep.last_price.saturating_mul(price_multiplier);
+            sweep.quantity = sweep.quantity.saturating_mul(quantity_multiplier);
+        }
+    }
+
+    fn close(&mut self, aggressor: Side) {
+        let run = match self.run_mut(aggressor).take() {
+            Some(run) if run.cleared.len() >= self.min_levels => run,
+            _ => return
+        };
+        // capacity is pub and may have been lowered or zeroed since the last sweep
+        while self.sweeps.len() >= self.capacity.max(1) {
+            self.sweeps.pop_front();
+        }
+        self.sweeps.push_back(Sweep {
+            aggressor,
+            start: run.start,
+            end: run.last,
+            first_price: run.first_price,
+            last_price: run.last_price,
+            quantity: run.quantity,
+            trades: run.trades,
+            levels_cleared: run.cleared.len()
+        });
+    }
+}
+
+impl Projection for SweepDetector {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        self.flush(timestamp);
+        if let BookEvent::Rescaled { price_multiplier, quantity_multiplier } = *event {
+            self.rescale(price_multiplier, quantity_multiplier);
+        }
+        if let BookEvent::LevelRemoved { side, price, .. } = *event {
+            let aggressor = match side {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid
+            };
+            if let Some(run) = self.run_mut(aggressor).as_mut() {
+                let (low, high) = (run.first_price.min(run.last_price), run.first_price.max(run.last_price));
+                if (low..=high).contains(&price) {
+                    run.cleared.insert(price);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sweep_at(detector: &mut SweepDetector, timestamp: u64) {
+        detector.record_trade(Side::Bid, 100, 1, timestamp);
+        detector.apply(&BookEvent::LevelRemoved { side: Side::Ask, price: 100, quantity: 1 }, timestamp);
+        detector.flush(timestamp + detector.window + 1);
+    }
+
+    #[test]
+    fn lowered_capacity_trims_retained_sweeps() {
+        let mut detector = SweepDetector::new(10, 1, 8);
+        for run in 0..8 {
+            sweep_at(&mut detector, run * 100);
+        }
+        detector.capacity = 3;
+        sweep_at(&mut detector, 800);
+        assert_eq!(detector.drain_sweeps().iter().map(|sweep| sweep.start).collect::<Vec<_>>(), vec![600, 700, 800]);
+        detector.capacity = 0;
+        sweep_at(&mut detector, 900);
+        sweep_at(&mut detector, 1000);
+        assert_eq!(detector.drain_sweeps().len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+/*
+Purpose: Model-based two-sided quotes for sparse books derived from a related liquid book
+*/
+
+use crate::{ConvertedLevel, ConvertedView, Orderbook};
+
+/*
+fair = ratio * reference mid + offset, in unscaled prices of the illiquid instrument
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasisModel {
+    pub ratio: f64,
+    pub offset: f64
+}
+
+impl BasisModel {
+    pub fn fair_value(&self, reference_mid: f64) -> f64 {
+        self.ratio * reference_mid + self.offset
+    }
+
+    /*
+    Move offset toward an observed (price, reference mid) pair, e.g. a trade in the illiquid
+    instrument, with smoothing alpha in (0, 1]
+    */
+    pub fn observe(&mut self, price: f64, reference_mid: f64, alpha: f64) {
+        if price.is_finite() && reference_mid.is_finite() {
+            let alpha = alpha.clamp(0.0, 1.0);
+            self.offset += alpha * (price - self.fair_value(reference_mid));
+        }
+    }
+}
+
+/*
+Unscaled model quote. stale is set when the reference book is not Live
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyntheticQuote {
+    pub fair: f64,
+    pub bid: f64,
+    pub ask: f64,
+    pub quantity: f64,
+    pub stale: bool
+}
+
+/*
+Quotes quantity on each side of the model fair value, half_spread_bps wide on each side but never
+tighter than the reference book's own spread scaled by the model ratio. A book is sparse when either
+side has fewer than min_levels levels
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuoteSynthesizer {
+    pub model: BasisModel,
+    pub half_spread_bps: f64,
+    pub quantity: f64,
+    pub min_levels: usize
+}
+
+impl QuoteSynthesizer {
+    pub fn is_sparse<M>(&self, book: &Orderbook<M>) -> bool {
+        book.bids.len() < self.min_levels || book.asks.len() < self.min_levels
+    }
+
+    /*
+    None when target is not sparse or reference is not two-sided
+    */
+    pub fn quote<M, N>(&self, target: &Orderbook<M>, reference: &Orderbook<N>) -> Option<SyntheticQuote> {
+        if !self.is_sparse(target) {
+            return None;
+        }
+        let summary = reference.summary(Some(1));
+        let reference_mid = summary.mid_price? / reference.price_factor;
+        let reference_spread = reference.unscale_price(summary.spread?);
+        let fair = self.model.fair_value(reference_mid);
+        let half_spread = (fair.abs() * self.half_spread_bps / 10_000.0).max((self.model.ratio * reference_spread).abs() / 2.0);
+        Some(SyntheticQuote {
+            fair,
+            bid: fair - half_spread,
+            ask: fair + half_spread,
+            quantity: self.quantity,
+            stale: !reference.is_live()
+        })
+    }
+}
+
+impl ConvertedView {
+    /*
+    Add a synthetic quote, already in the view's currency, as one flagged level per side from source
+    */
+    pub fn insert_synthetic(&mut self, quote: &SyntheticQuote, source: usize) {
+        let level = |price: f64| ConvertedLevel {
+            price,
+            quantity: quote.quantity,
+            source,
+            stale: quote.stale,
+            synthetic: true
+        };
+        self.bids.push(level(quote.bid));
+        self.asks.push(level(quote.ask));
+        self.bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+        self.asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+        self.stale |= quote.stale;
+    }
+}
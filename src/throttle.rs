@@ -0,0 +1,255 @@
+/*
+Purpose: Token-bucket and sliding-window rate limiters for simulated outbound order flow
+*/
+
+use std::collections::VecDeque;
+
+/*
+A limit on outbound messages over simulated time (ms). available_at is the earliest time at or after
+now when cost could be acquired, None if it never can
+*/
+pub trait RateLimiter: Send {
+    fn available_at(&self, now: u64, cost: u64) -> Option<u64>;
+    fn acquire(&mut self, now: u64, cost: u64);
+
+    /*
+    Acquire cost if allowed at now
+    */
+    fn try_acquire(&mut self, now: u64, cost: u64) -> bool {
+        match self.available_at(now, cost) {
+            Some(at) if at <= now => {
+                self.acquire(now, cost);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+/*
+Holds up to capacity tokens, refilled continuously at rate_per_second; starts full. Allows bursts of
+capacity messages on top of the sustained rate
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    pub capacity: f64,
+    pub rate_per_second: f64,
+    tokens: f64,
+    updated: Option<u64>
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u64, rate_per_second: f64) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity as f64,
+            rate_per_second: rate_per_second.max(0.0),
+            tokens: capacity as f64,
+            updated: None
+        }
+    }
+
+    pub fn tokens(&self, now: u64) -> f64 {
+        match self.updated {
+            Some(updated) => (self.tokens + now.saturating_sub(updated) as f64 * self.rate_per_second / 1000.0).min(self.capacity),
+            None => self.tokens
+        }
+    }
+}
+
+impl RateLimiter for TokenBucket {
+    fn available_at(&self, now: u64, cost: u64) -> Option<u64> {
+        let missing = cost as f64 - self.tokens(now);
+        match missing <= 0.0 {
+            true => Some(now),
+            false if cost as f64 <= self.capacity && self.rate_per_second > 0.0 =>
+                Some(now.saturating_add((missing * 1000.0 / self.rate_per_second).ceil() as u64)),
+            false => None
+        }
+    }
+
+    fn acquire(&mut self, now: u64, cost: u64) {
+        self.tokens = self.tokens(now) - cost as f64;
+        self.updated = Some(self.updated.map_or(now, |updated| updated.max(now)));
+    }
+}
+
+/*
+At most limit messages in any window_ms: a message sent at t counts until t + window_ms
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlidingWindow {
+    pub limit: u64,
+    pub window_ms: u64,
+    sent: VecDeque<(u64, u64)>
+}
+
+impl SlidingWindow {
+    pub fn new(limit: u64, window_ms: u64) -> SlidingWindow {
+        SlidingWindow {
+            limit,
+            window_ms: window_ms.max(1),
+            sent: VecDeque::new()
+        }
+    }
+
+    /*
+    Messages counting against the limit at now
+    */
+    pub fn used(&self, now: u64) -> u64 {
+        self.sent.iter().filter(|(sent, _)| sent.saturating_add(self.window_ms) > now).fold(0u64, |used, (_, cost)| used.saturating_add(*cost))
+    }
+}
+
+impl RateLimiter for SlidingWindow {
+    fn available_at(&self, now: u64, cost: u64) -> Option<u64> {
+        if cost > self.limit {
+            return None;
+        }
+        let mut used = self.used(now);
+        let mut at = now;
+        for (sent, sent_cost) in self.sent.iter().filter(|(sent, _)| sent.saturating_add(self.window_ms) > now) {
+            if used.saturating_add(cost) <= self.limit {
+                break;
+            }
+            used = used.saturating_sub(*sent_cost);
+            at = sent.saturating_add(self.window_ms);
+        }
+        Some(at)
+    }
+
+    fn acquire(&mut self, now: u64, cost: u64) {
+        while self.sent.front().is_some_and(|(sent, _)| sent.saturating_add(self.window_ms) <= now) {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((now, cost));
+    }
+}
+
+/*
+Messages: every outbound message (new orders, amends, cancels). Orders: new orders only
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitScope {
+    Messages,
+    Orders
+}
+
+/*
+What to do with a message that would breach a limit. Queue holds up to max_queued messages in arrival
+order and releases them from poll as the limits allow; anything beyond is rejected
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottlePolicy {
+    Reject,
+    Queue { max_queued: usize }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThrottleOutcome<T> {
+    Sent(T),
+    Queued,
+    Rejected(T)
+}
+
+struct ScopedLimit {
+    scope: LimitScope,
+    limiter: Box<dyn RateLimiter>
+}
+
+/*
+Gate for simulated outbound messages of type T under a set of exchange limits, e.g. 10 orders/s and
+50 messages/s. A message is sent only when every limit in its scope allows it; queued messages keep
+their order, so a queued message holds back later ones until it is released
+*/
+pub struct Throttle<T> {
+    pub policy: ThrottlePolicy,
+    pub sent: u64,
+    pub rejected: u64,
+    limits: Vec<ScopedLimit>,
+    queue: VecDeque<(T, bool)>
+}
+
+impl<T> Throttle<T> {
+    pub fn new(policy: ThrottlePolicy) -> Throttle<T> {
+        Throttle {
+            policy,
+            sent: 0,
+            rejected: 0,
+            limits: Vec::new(),
+            queue: VecDeque::new()
+        }
+    }
+
+    pub fn with_limit<L: RateLimiter + 'static>(mut self, scope: LimitScope, limiter: L) -> Throttle<T> {
+        self.limits.push(ScopedLimit { scope, limiter: Box::new(limiter) });
+        self
+    }
+
+    /*
+    Offer message at now. is_order marks a new order, which also counts against Orders limits
+    */
+    pub fn submit(&mut self, message: T, is_order: bool, now: u64) -> ThrottleOutcome<T> {
+        if self.queue.is_empty() && self.try_send(is_order, now) {
+            return ThrottleOutcome::Sent(message);
+        }
+        match self.policy {
+            ThrottlePolicy::Queue { max_queued } if self.queue.len() < max_queued && self.ready_at(is_order, now).is_some() => {
+                self.queue.push_back((message, is_order));
+                ThrottleOutcome::Queued
+            },
+            _ => {
+                self.rejected += 1;
+                ThrottleOutcome::Rejected(message)
+            }
+        }
+    }
+
+    /*
+    Queued messages released at now, oldest first
+    */
+    pub fn poll(&mut self, now: u64) -> Vec<T> {
+        let mut released = Vec::new();
+        while let Some((_, is_order)) = self.queue.front() {
+            if !self.try_send(*is_order, now) {
+                break;
+            }
+            if let Some((message, _)) = self.queue.pop_front() {
+                released.push(message);
+            }
+        }
+        released
+    }
+
+    /*
+    When the next queued message can be released, for scheduling the next poll
+    */
+    pub fn next_release(&self, now: u64) -> Option<u64> {
+        let (_, is_order) = self.queue.front()?;
+        self.ready_at(*is_order, now)
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn applies(scope: LimitScope, is_order: bool) -> bool {
+        scope == LimitScope::Messages || is_order
+    }
+
+    fn ready_at(&self, is_order: bool, now: u64) -> Option<u64> {
+        self.limits.iter()
+            .filter(|limit| Self::applies(limit.scope, is_order))
+            .try_fold(now, |at, limit| Some(at.max(limit.limiter.available_at(now, 1)?)))
+    }
+
+    fn try_send(&mut self, is_order: bool, now: u64) -> bool {
+        if self.ready_at(is_order, now).is_none_or(|at| at > now) {
+            return false;
+        }
+        for limit in self.limits.iter_mut().filter(|limit| Self::applies(limit.scope, is_order)) {
+            limit.limiter.acquire(now, 1);
+        }
+        self.sent += 1;
+        true
+    }
+}
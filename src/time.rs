@@ -0,0 +1,334 @@
+/*
+Purpose: UTC nanosecond timestamps, exchange timestamp parsing and exchange-local conversion
+*/
+
+use std::error::Error;
+use std::fmt;
+
+pub const NANOS_PER_MICRO: u64 = 1_000;
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos
+}
+
+impl TimeUnit {
+    fn nanos(self) -> u64 {
+        match self {
+            TimeUnit::Seconds => NANOS_PER_SECOND,
+            TimeUnit::Millis => NANOS_PER_MILLI,
+            TimeUnit::Micros => NANOS_PER_MICRO,
+            TimeUnit::Nanos => 1
+        }
+    }
+
+    /*
+    Unit of an epoch count from its integer digits: up to 10 seconds, 13 ms, 16 us, otherwise ns.
+    Unambiguous for dates between 2001 and 2286
+    */
+    fn from_digits(digits: usize) -> TimeUnit {
+        match digits {
+            0..=10 => TimeUnit::Seconds,
+            11..=13 => TimeUnit::Millis,
+            14..=16 => TimeUnit::Micros,
+            _ => TimeUnit::Nanos
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampError {
+    Empty,
+    Invalid(String),
+    OutOfRange
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::Empty => write!(f, "empty timestamp"),
+            TimestampError::Invalid(text) => write!(f, "unrecognized timestamp {:?}", text),
+            TimestampError::OutOfRange => write!(f, "timestamp outside 1970 to 2554")
+        }
+    }
+}
+
+impl Error for TimestampError {}
+
+/*
+UTC nanoseconds since the epoch of an epoch count in unit. None on overflow
+*/
+pub fn to_nanos(value: u64, unit: TimeUnit) -> Option<u64> {
+    value.checked_mul(unit.nanos())
+}
+
+/*
+UTC nanoseconds to the ms book clock used by Orderbook::process_at and the analytics, truncating
+*/
+pub fn nanos_to_millis(nanos: u64) -> u64 {
+    nanos / NANOS_PER_MILLI
+}
+
+pub fn millis_to_nanos(millis: u64) -> u64 {
+    millis.saturating_mul(NANOS_PER_MILLI)
+}
+
+/*
+Parse an exchange timestamp to UTC nanoseconds. Accepts epoch counts in s, ms, us or ns (unit inferred
+from the integer digits, optional fraction) and ISO 8601 / RFC 3339 date-times. ISO strings without a
+zone are taken as UTC; use parse_timestamp_in for venues that send exchange-local time
+*/
+pub fn parse_timestamp(text: &str) -> Result<u64, TimestampError> {
+    parse_timestamp_in(text, 0)
+}
+
+/*
+parse_timestamp with ISO strings lacking a zone read as local time at utc_offset_minutes
+*/
+pub fn parse_timestamp_in(text: &str, utc_offset_minutes: i32) -> Result<u64, TimestampError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(TimestampError::Empty);
+    }
+    let invalid = || TimestampError::Invalid(text.to_string());
+    match text.contains('-') && text.find('-') != Some(0) {
+        true => parse_iso(text, utc_offset_minutes).ok_or_else(invalid)?,
+        false => {
+            let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+            let unit = TimeUnit::from_digits(integer.len());
+            parse_epoch(integer, fraction, unit).ok_or_else(invalid)?
+        }
+    }
+    .ok_or(TimestampError::OutOfRange)
+}
+
+/*
+Parse an epoch count in a known unit, with an optional fraction of that unit
+*/
+pub fn parse_timestamp_as(text: &str, unit: TimeUnit) -> Result<u64, TimestampError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(TimestampError::Empty);
+    }
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    parse_epoch(integer, fraction, unit)
+        .ok_or_else(|| TimestampError::Invalid(text.to_string()))?
+        .ok_or(TimestampError::OutOfRange)
+}
+
+/*
+A UTC instant as wall-clock time at a fixed offset, e.g. a TradingCalendar's utc_offset_minutes
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanosecond: u32,
+    pub utc_offset_minutes: i32
+}
+
+impl LocalTime {
+    pub fn from_nanos(nanos: u64, utc_offset_minutes: i32) -> LocalTime {
+        let seconds = (nanos / NANOS_PER_SECOND) as i64 + utc_offset_minutes as i64 * 60;
+        let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+        let second_of_day = seconds.rem_euclid(SECONDS_PER_DAY) as u32;
+        LocalTime {
+            year,
+            month,
+            day,
+            hour: second_of_day / 3_600,
+            minute: second_of_day / 60 % 60,
+            second: second_of_day % 60,
+            nanosecond: (nanos % NANOS_PER_SECOND) as u32,
+            utc_offset_minutes
+        }
+    }
+
+    pub fn from_millis(millis: u64, utc_offset_minutes: i32) -> LocalTime {
+        LocalTime::from_nanos(millis_to_nanos(millis), utc_offset_minutes)
+    }
+
+    /*
+    Back to UTC nanoseconds. None for dates before the epoch or past u64 nanoseconds
+    */
+    pub fn to_nanos(&self) -> Option<u64> {
+        let days = days_from_civil(self.year, self.month, self.day)?;
+        let seconds = days * SECONDS_PER_DAY + (self.hour * 3_600 + self.minute * 60 + self.second) as i64 - self.utc_offset_minutes as i64 * 60;
+        u64::try_from(seconds).ok()?.checked_mul(NANOS_PER_SECOND)?.checked_add(self.nanosecond as u64)
+    }
+
+    /*
+    Minutes after local midnight, the clock TradingSession uses
+    */
+    pub fn minute_of_day(&self) -> u32 {
+        self.hour * 60 + self.minute
+    }
+}
+
+/*
+RFC 3339 with nanoseconds and the offset, e.g. 2026-01-05T09:00:00.000000000+09:00
+*/
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}", self.year, self.month, self.day, self.hour, self.minute, self.second, self.nanosecond)?;
+        match self.utc_offset_minutes {
+            0 => write!(f, "Z"),
+            offset => {
+                let sign = match offset < 0 {
+                    true => '-',
+                    false => '+'
+                };
+                write!(f, "{}{:02}:{:02}", sign, offset.unsigned_abs() / 60, offset.unsigned_abs() % 60)
+            }
+        }
+    }
+}
+
+/*
+Outer None: not a number. Inner None: overflow
+*/
+fn parse_epoch(integer: &str, fraction: &str, unit: TimeUnit) -> Option<Option<u64>> {
+    if integer.is_empty() || !integer.bytes().all(|byte| byte.is_ascii_digit()) || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let whole = match integer.parse::<u64>() {
+        Ok(whole) => whole,
+        Err(_) => return Some(None)
+    };
+    Some(to_nanos(whole, unit).and_then(|nanos| nanos.checked_add(fraction_nanos(fraction, unit.nanos()))))
+}
+
+/*
+Value of a decimal fraction of a unit spanning unit_nanos, truncated to whole nanoseconds
+*/
+fn fraction_nanos(fraction: &str, unit_nanos: u64) -> u64 {
+    let mut nanos = 0;
+    let mut scale = unit_nanos;
+    for digit in fraction.bytes() {
+        scale /= 10;
+        if scale == 0 {
+            break;
+        }
+        nanos += (digit - b'0') as u64 * scale;
+    }
+    nanos
+}
+
+/*
+YYYY-MM-DD, optionally followed by T or a space and HH:MM[:SS[.fraction]] and a zone of Z, ±HH:MM or ±HHMM.
+Outer None: malformed. Inner None: before the epoch or past u64 nanoseconds
+*/
+fn parse_iso(text: &str, default_offset_minutes: i32) -> Option<Option<u64>> {
+    let number = |part: &str| match part.bytes().all(|byte| byte.is_ascii_digit()) && !part.is_empty() {
+        true => part.parse::<u32>().ok(),
+        false => None
+    };
+    let (date, rest) = match text.find(['T', 't', ' ']) {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, "")
+    };
+    let mut date_parts = date.splitn(3, '-');
+    let year = number(date_parts.next()?)? as i32;
+    let month = number(date_parts.next()?)?;
+    let day = number(date_parts.next()?)?;
+    let (clock, offset) = split_zone(rest)?;
+    let offset = offset.unwrap_or(default_offset_minutes);
+    let (clock, fraction) = clock.split_once(['.', ',']).unwrap_or((clock, ""));
+    let mut clock_parts = clock.split_terminator(':');
+    let (hour, minute, second) = match clock.is_empty() {
+        true => (0, 0, 0),
+        false => (number(clock_parts.next()?)?, number(clock_parts.next()?)?, clock_parts.next().map_or(Some(0), number)?)
+    };
+    if clock_parts.next().is_some() || hour > 23 || minute > 59 || second > 60 || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    days_from_civil(year, month, day)?;
+    let local = LocalTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second: second.min(59),
+        nanosecond: fraction_nanos(fraction, NANOS_PER_SECOND) as u32,
+        utc_offset_minutes: offset
+    };
+    Some(local.to_nanos())
+}
+
+/*
+Split a clock into its time and zone offset in minutes, None for a zone that is present but malformed
+*/
+fn split_zone(clock: &str) -> Option<(&str, Option<i32>)> {
+    if let Some(clock) = clock.strip_suffix(['Z', 'z']) {
+        return Some((clock, Some(0)));
+    }
+    let index = match clock.rfind(['+', '-']) {
+        Some(index) => index,
+        None => return Some((clock, None))
+    };
+    let negative = clock[index..].starts_with('-');
+    let (clock, zone) = (&clock[..index], &clock[index + 1..]);
+    let digits: String = zone.chars().filter(|character| *character != ':').collect();
+    if digits.len() != 4 || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let minutes = digits[..2].parse::<i32>().ok()? * 60 + digits[2..].parse::<i32>().ok()?;
+    match (minutes > 18 * 60, negative) {
+        (true, _) => None,
+        (false, true) => Some((clock, Some(-minutes))),
+        (false, false) => Some((clock, Some(minutes)))
+    }
+}
+
+/*
+Days from 1970-01-01 to a proleptic Gregorian date, None for an invalid date
+*/
+pub(crate) fn days_from_civil(year: i32, month: u32, day: u32) -> Option<i64> {
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let month_days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => 0
+    };
+    if day == 0 || day > month_days {
+        return None;
+    }
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/*
+Inverse of days_from_civil
+*/
+pub(crate) fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = match month_index < 10 {
+        true => month_index + 3,
+        false => month_index - 9
+    } as u32;
+    ((year_of_era + era * 400 + (month <= 2) as i64) as i32, month, day)
+}
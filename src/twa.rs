@@ -0,0 +1,169 @@
+/*
+Purpose: Time-weighted spread and depth over arbitrary windows and as-of joins with external series
+*/
+
+use std::collections::VecDeque;
+
+use crate::Orderbook;
+
+/*
+Book state observed at timestamp (ms) and assumed to hold until the next sample.
+Best prices and spread are unscaled, spread None for a one-sided book; depths are unscaled quantity
+within depth_bps of mid
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookSample {
+    pub timestamp: u64,
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    pub spread: Option<f64>,
+    pub bid_depth: f64,
+    pub ask_depth: f64
+}
+
+/*
+Averages weighted by how long each sample was in effect. spread covers only time with a two-sided book
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWeighted {
+    pub spread: Option<f64>,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+    pub covered_ms: u64
+}
+
+/*
+A left record with the latest right record at or before its timestamp, if any within tolerance
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsOfRecord<L, R> {
+    pub timestamp: u64,
+    pub left: L,
+    pub right: Option<R>,
+    pub right_timestamp: Option<u64>
+}
+
+/*
+Backward as-of join of two series sorted by timestamp (ms): every left record is paired with the last
+right record at or before it, no more than tolerance ms older when given. Ties take the last right record
+*/
+pub fn as_of_join<L: Clone, R: Clone>(left: &[(u64, L)], right: &[(u64, R)], tolerance: Option<u64>) -> Vec<AsOfRecord<L, R>> {
+    let mut joined = Vec::with_capacity(left.len());
+    let mut next = 0;
+    for (timestamp, value) in left.iter() {
+        while next < right.len() && right[next].0 <= *timestamp {
+            next += 1;
+        }
+        let matched = next
+            .checked_sub(1)
+            .map(|index| &right[index])
+            .filter(|(right_timestamp, _)| tolerance.is_none_or(|tolerance| timestamp - right_timestamp <= tolerance));
+        joined.push(AsOfRecord {
+            timestamp: *timestamp,
+            left: value.clone(),
+            right: matched.map(|(_, right)| right.clone()),
+            right_timestamp: matched.map(|(right_timestamp, _)| *right_timestamp)
+        });
+    }
+    joined
+}
+
+/*
+Ring buffer of the most recent capacity samples, taken on every observed update
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeWeightedSampler {
+    pub depth_bps: f64,
+    pub capacity: usize,
+    pub samples: VecDeque<BookSample>
+}
+
+impl TimeWeightedSampler {
+    pub fn new(depth_bps: f64, capacity: usize) -> TimeWeightedSampler {
+        TimeWeightedSampler {
+            depth_bps,
+            capacity: capacity.max(1),
+            samples: VecDeque::with_capacity(capacity.max(1))
+        }
+    }
+
+    /*
+    Sample the book after an update. Timestamps must not decrease
+    */
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) {
+        let summary = book.summary(Some(1));
+        let (bid_depth, ask_depth) = match book.depth_grid(self.depth_bps, 1) {
+            Some(grid) => (grid.bids[0], grid.asks[0]),
+            None => (0.0, 0.0)
+        };
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(BookSample {
+            timestamp,
+            best_bid: summary.best_bid.map(|(price, _)| book.unscale_price(price)),
+            best_ask: summary.best_ask.map(|(price, _)| book.unscale_price(price)),
+            spread: summary.spread.map(|spread| book.unscale_price(spread)),
+            bid_depth,
+            ask_depth
+        });
+    }
+
+    /*
+    Time-weighted averages over [from, to). The last sample at or before from sets the state at the
+    start of the window. None if no retained sample is in effect during the window
+    */
+    pub fn average(&self, from: u64, to: u64) -> Option<TimeWeighted> {
+        let mut covered_ms: u64 = 0;
+        let mut spread_ms: u64 = 0;
+        let mut spread_total = 0.0;
+        let mut bid_total = 0.0;
+        let mut ask_total = 0.0;
+        for (index, sample) in self.samples.iter().enumerate() {
+            let end = self.samples.get(index + 1).map_or(to, |next| next.timestamp);
+            let start = sample.timestamp.max(from);
+            let end = end.min(to);
+            if end <= start {
+                continue;
+            }
+            let duration = end - start;
+            covered_ms += duration;
+            bid_total += sample.bid_depth * duration as f64;
+            ask_total += sample.ask_depth * duration as f64;
+            if let Some(spread) = sample.spread {
+                spread_ms += duration;
+                spread_total += spread * duration as f64;
+            }
+        }
+        if covered_ms == 0 {
+            return None;
+        }
+        Some(TimeWeighted {
+            spread: match spread_ms {
+                0 => None,
+                _ => Some(spread_total / spread_ms as f64)
+            },
+            bid_depth: bid_total / covered_ms as f64,
+            ask_depth: ask_total / covered_ms as f64,
+            covered_ms
+        })
+    }
+
+    /*
+    The retained samples as the left series of an as-of join against an external series such as funding
+    rates: each sample with the series value in effect at that time
+    */
+    pub fn join_series<R: Clone>(&self, series: &[(u64, R)], tolerance: Option<u64>) -> Vec<AsOfRecord<BookSample, R>> {
+        let samples: Vec<(u64, BookSample)> = self.samples.iter().map(|sample| (sample.timestamp, *sample)).collect();
+        as_of_join(&samples, series, tolerance)
+    }
+
+    /*
+    The reverse join: each external record, e.g. another venue's trades, with the book sample in effect
+    when it happened. Records before the oldest retained sample get none
+    */
+    pub fn align_series<L: Clone>(&self, series: &[(u64, L)], tolerance: Option<u64>) -> Vec<AsOfRecord<L, BookSample>> {
+        let samples: Vec<(u64, BookSample)> = self.samples.iter().map(|sample| (sample.timestamp, *sample)).collect();
+        as_of_join(series, &samples, tolerance)
+    }
+}
@@ -0,0 +1,298 @@
+/*
+Purpose: Validation and quarantine of feed updates before they reach the book
+*/
+
+use std::collections::VecDeque;
+
+use crate::{BookUpdate, Orderbook, Side};
+
+/*
+Why a level was rejected. Values are as received (unscaled). Zero quantities are valid removals
+and prices may be negative; only non-finite prices are invalid
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationError {
+    NonFinitePrice { side: Side, price: f64 },
+    InvalidQuantity { side: Side, price: f64, quantity: f64 },
+    QuantityTooLarge { side: Side, price: f64, quantity: f64 },
+    PriceOutOfRange { side: Side, price: f64, mid_price: f64 }
+}
+
+/*
+A rejected update with the caller's context (e.g. venue and raw sequence) and the time it was received
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarantinedUpdate {
+    pub context: String,
+    pub timestamp: u64,
+    pub update: BookUpdate,
+    pub error: ValidationError
+}
+
+/*
+Structural checks every level must pass: finite price, finite non-negative quantity
+*/
+pub fn validate_levels(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Result<(), ValidationError> {
+    for (side, levels) in [(Side::Bid, bids), (Side::Ask, asks)] {
+        for (price, quantity) in levels.iter() {
+            if !price.is_finite() {
+                return Err(ValidationError::NonFinitePrice { side, price: *price });
+            }
+            if !(quantity.is_finite() && *quantity >= 0.0) {
+                return Err(ValidationError::InvalidQuantity { side, price: *price, quantity: *quantity });
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Validate every level, then process the update. On error the book is left untouched
+    */
+    pub fn try_process(&mut self, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, is_snapshot: bool) -> Result<(), ValidationError> {
+        validate_levels(&bids, &asks)?;
+        self.process(bids, asks, is_snapshot);
+        Ok(())
+    }
+}
+
+/*
+Sits between parsing and Orderbook::process. On top of the structural checks, max_quantity bounds
+level size and max_deviation bounds delta prices to a fraction of the current mid (0.5 = 50%), taken by
+magnitude; a NaN max_deviation rejects every delta while the book has a mid. Rejected updates are kept in a bounded quarantine and the book is left untouched
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedValidator {
+    pub max_quantity: Option<f64>,
+    pub max_deviation: Option<f64>,
+    pub quarantine_capacity: usize,
+    pub quarantine: VecDeque<QuarantinedUpdate>,
+    pub accepted: u64,
+    pub rejected: u64
+}
+
+impl FeedValidator {
+    pub fn new(max_quantity: Option<f64>, max_deviation: Option<f64>, quarantine_capacity: usize) -> FeedValidator {
+        FeedValidator {
+            max_quantity,
+            max_deviation,
+            quarantine_capacity,
+            quarantine: VecDeque::new(),
+            accepted: 0,
+            rejected: 0
+        }
+    }
+
+    /*
+    Snapshots are exempt from the deviation check since they may legitimately move the whole book
+    */
+    pub fn check<M>(&self, book: &Orderbook<M>, update: &BookUpdate) -> Result<(), ValidationError> {
+        validate_levels(&update.bids, &update.asks)?;
+        for (side, levels) in [(Side::Bid, &update.bids), (Side::Ask, &update.asks)] {
+            for (price, quantity) in levels.iter() {
+                if let Some(max_quantity) = self.max_quantity {
+                    if *quantity > max_quantity {
+                        return Err(ValidationError::QuantityTooLarge { side, price: *price, quantity: *quantity });
+                    }
+                }
+            }
+        }
+        if let (Some(max_deviation), false) = (self.max_deviation, update.is_snapshot) {
+            if let Some(mid_price) = book.summary(Some(1)).mid_price.map(|mid_price| mid_price / book.price_factor) {
+                let limit = mid_price.abs() * max_deviation.abs();
+                for (side, levels) in [(Side::Bid, &update.bids), (Side::Ask, &update.asks)] {
+                    if let Some((price, _)) = levels.iter().find(|(price, _)| limit.is_nan() || (price - mid_price).abs() > limit) {
+                        return Err(ValidationError::PriceOutOfRange { side, price: *price, mid_price });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /*
+    Process update if it passes validation, otherwise quarantine it. Returns whether it was applied
+    */
+    pub fn apply<M>(&mut self, book: &mut Orderbook<M>, update: BookUpdate, context: &str, timestamp: u64) -> bool {
+        match self.check(book, &update) {
+            Ok(()) => {
+                book.process(update.bids, update.asks, update.is_snapshot);
+                self.accepted += 1;
+                true
+            },
+            Err(error) => {
+                self.rejected += 1;
+                if self.quarantine_capacity > 0 {
+                    if self.quarantine.len() == self.quarantine_capacity {
+                        self.quarantine.pop_front();
+                    }
+                    self.quarantine.push_back(QuarantinedUpdate {
+                        context: context.to_string(),
+                        timestamp,
+                        update,
+                        error
+                    });
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QuoteBook;
+
+    const NON_FINITE: [f64; 3] = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+    const BAD_QUANTITIES: [f64; 4] = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0];
+    const BAD_TAKER_QUANTITIES: [f64; 5] = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.0, -1.0];
+
+    fn bids() -> Vec<(f64, f64)> {
+        vec![(99.0, 1.0), (98.0, 2.0)]
+    }
+
+    fn asks() -> Vec<(f64, f64)> {
+        vec![(101.0, 1.0), (102.0, 2.0)]
+    }
+
+    /*
+    Every invalid level at a price that exists in the book and at one that does not
+    */
+    fn bad_levels() -> Vec<(f64, f64)> {
+        let prices = NON_FINITE.iter().map(|price| (*price, 1.0));
+        let quantities = BAD_QUANTITIES.iter().flat_map(|quantity| [(99.0, *quantity), (97.0, *quantity)]);
+        prices.chain(quantities).collect()
+    }
+
+    type Levels = Vec<(i64, u64)>;
+
+    fn levels(book: &Orderbook) -> (Levels, Levels) {
+        let snapshot = book.snapshot();
+        (snapshot.bids().to_vec(), snapshot.asks().to_vec())
+    }
+
+    fn with_level(mut levels: Vec<(f64, f64)>, level: (f64, f64)) -> Vec<(f64, f64)> {
+        levels.push(level);
+        levels
+    }
+
+    #[test]
+    fn book_entry_points_skip_invalid_levels() {
+        let expected = levels(&Orderbook::from_snapshot(Some(2), Some(2), bids(), asks()));
+        for level in bad_levels() {
+            let mut book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+            book.process(vec![level], vec![level], false);
+            assert_eq!(levels(&book), expected, "process delta with {level:?}");
+            book.process(with_level(bids(), level), with_level(asks(), level), true);
+            assert_eq!(levels(&book), expected, "process snapshot with {level:?}");
+            book.load_snapshot(with_level(bids(), level), with_level(asks(), level));
+            assert_eq!(levels(&book), expected, "load_snapshot with {level:?}");
+            let book = Orderbook::from_snapshot(Some(2), Some(2), with_level(bids(), level), with_level(asks(), level));
+            assert_eq!(levels(&book), expected, "from_snapshot with {level:?}");
+            let mut book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+            book.process_with_order_counts(vec![(level.0, level.1, 3)], vec![(level.0, level.1, 3)], false);
+            assert_eq!(levels(&book), expected, "process_with_order_counts with {level:?}");
+            assert_eq!(book.get_bid_levels(1), vec![(9900, 100, None)]);
+        }
+    }
+
+    #[test]
+    fn zero_quantity_removes_or_is_ignored() {
+        let mut book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+        book.process(vec![(99.0, 0.0), (97.0, 0.0)], vec![(101.0, 0.001)], false);
+        assert_eq!(levels(&book), (vec![(9800, 200)], vec![(10200, 200)]));
+        let mut book = Orderbook::from_snapshot(Some(2), Some(2), with_level(bids(), (97.0, 0.0)), asks());
+        assert_eq!(book.get_bid_levels(10).len(), 2);
+        assert_eq!(book.try_process(vec![(99.0, 0.0)], Vec::new(), false), Ok(()));
+    }
+
+    #[test]
+    fn try_process_rejects_and_leaves_the_book_untouched() {
+        for (side, price) in [Side::Bid, Side::Ask].into_iter().flat_map(|side| NON_FINITE.map(|price| (side, price))) {
+            let mut book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+            let expected = levels(&book);
+            let (bid_levels, ask_levels) = match side {
+                Side::Bid => (vec![(98.5, 1.0), (price, 1.0)], Vec::new()),
+                Side::Ask => (Vec::new(), vec![(101.5, 1.0), (price, 1.0)])
+            };
+            let error = book.try_process(bid_levels, ask_levels, false).err();
+            assert!(matches!(error, Some(ValidationError::NonFinitePrice { side: rejected, .. }) if rejected == side));
+            assert_eq!(levels(&book), expected);
+        }
+        for quantity in BAD_QUANTITIES {
+            let mut book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+            let expected = levels(&book);
+            let error = book.try_process(vec![(98.5, 1.0)], vec![(101.5, quantity)], true).err();
+            assert!(matches!(error, Some(ValidationError::InvalidQuantity { side: Side::Ask, .. })), "{quantity}");
+            assert_eq!(levels(&book), expected);
+        }
+    }
+
+    #[test]
+    fn feed_validator_rejects_invalid_levels() {
+        let book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+        let mut validator = FeedValidator::new(Some(10.0), Some(0.5), 4);
+        for level in bad_levels() {
+            let update = BookUpdate { bids: vec![level], asks: Vec::new(), is_snapshot: false };
+            assert!(validator.check(&book, &update).is_err(), "{level:?}");
+        }
+        let update = BookUpdate { bids: vec![(99.5, 1.0)], asks: Vec::new(), is_snapshot: false };
+        for (max_deviation, accepted) in [(0.5, true), (-0.5, true), (0.001, false), (f64::NAN, false), (f64::INFINITY, true)] {
+            validator.max_deviation = Some(max_deviation);
+            assert_eq!(validator.check(&book, &update).is_ok(), accepted, "{max_deviation}");
+        }
+        validator.max_deviation = None;
+        let mut rejected = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+        let update = BookUpdate { bids: vec![(f64::NAN, 1.0)], asks: Vec::new(), is_snapshot: false };
+        assert!(!validator.apply(&mut rejected, update, "venue", 1));
+        assert_eq!(levels(&rejected), levels(&book));
+        assert_eq!(validator.quarantine.len(), 1);
+    }
+
+    #[test]
+    fn quote_book_skips_invalid_and_zero_quotes() {
+        let mut quotes = QuoteBook::new(Some(2), Some(2));
+        let invalid = with_level(bad_levels(), (100.0, 0.0));
+        quotes.replace_quotes(1, invalid.clone(), invalid);
+        assert_eq!(quotes.get_best_bid(&[]), None);
+        assert_eq!(quotes.get_best_ask(&[]), None);
+        quotes.replace_quotes(1, with_level(bids(), (f64::NAN, 1.0)), with_level(asks(), (102.0, f64::INFINITY)));
+        assert_eq!(quotes.get_best_bid(&[]), Some((9900, 100)));
+        assert_eq!(quotes.get_ask_providers(10200), vec![(1, 200)]);
+    }
+
+    #[test]
+    fn analytics_reject_invalid_quantities() {
+        let book = Orderbook::from_snapshot(Some(2), Some(2), bids(), asks());
+        let snapshot = book.snapshot();
+        for quantity in BAD_TAKER_QUANTITIES {
+            assert_eq!(book.simulate_taker_buy(quantity), None, "{quantity}");
+            assert_eq!(book.simulate_taker_sell(quantity), None, "{quantity}");
+            assert_eq!(snapshot.simulate_taker_buy(quantity), None, "{quantity}");
+            assert_eq!(snapshot.simulate_taker_sell(quantity), None, "{quantity}");
+        }
+        for value in NON_FINITE {
+            assert_eq!(book.checked_scale_price(value), None);
+            assert_eq!(book.checked_scale_qty(value), None);
+        }
+        assert_eq!(book.checked_scale_qty(-1.0), None);
+        assert_eq!(book.checked_scale_price(-1.0), Some(-100));
+        assert_eq!(book.simulate_taker_buy(1.0), Some(10_100.0));
+        assert_eq!(book.simulate_taker_sell(3.0), Some(29_500.0 / 3.0));
+    }
+
+    #[test]
+    fn empty_book_analytics() {
+        let book = Orderbook::new(Some(2), Some(2));
+        let summary = book.summary(None);
+        assert_eq!((summary.spread, summary.mid_price, summary.microprice, summary.imbalance), (None, None, None, None));
+        assert_eq!((summary.weighted_bid, summary.weighted_ask), (None, None));
+        assert_eq!(summary.total_bid_quantity, 0.0);
+        assert_eq!(book.get_weighted_mid_price(), None);
+        assert_eq!(book.simulate_taker_buy(1.0), None);
+        assert_eq!(book.snapshot().get_spread(), None);
+        assert_eq!(book.snapshot().get_weighted_bid(), None);
+    }
+}
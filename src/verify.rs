@@ -0,0 +1,132 @@
+/*
+Purpose: Level-by-level comparison of two books, e.g. locally built vs exchange snapshot
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Orderbook, Side};
+
+/*
+Quantities are unscaled. Missing means the level exists only in the reference book,
+Unexpected means it exists only in the local book
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelDifference {
+    Missing { side: Side, price: i64, reference_quantity: f64 },
+    Unexpected { side: Side, price: i64, local_quantity: f64 },
+    QuantityMismatch { side: Side, price: i64, local_quantity: f64, reference_quantity: f64 }
+}
+
+/*
+Reported prices are scaled by price_factor, the finer of the two books' price factors
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+    pub differences: Vec<LevelDifference>,
+    pub levels_compared: usize,
+    pub price_factor: f64
+}
+
+impl VerifyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /*
+    One line per difference: side,price,kind,local_quantity,reference_quantity with empty fields where absent
+    */
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("side,price,kind,local_quantity,reference_quantity\n");
+        for difference in self.differences.iter() {
+            let line = match difference {
+                LevelDifference::Missing { side, price, reference_quantity } =>
+                    format!("{:?},{},missing,,{}\n", side, price, reference_quantity),
+                LevelDifference::Unexpected { side, price, local_quantity } =>
+                    format!("{:?},{},unexpected,{},\n", side, price, local_quantity),
+                LevelDifference::QuantityMismatch { side, price, local_quantity, reference_quantity } =>
+                    format!("{:?},{},quantity_mismatch,{},{}\n", side, price, local_quantity, reference_quantity)
+            };
+            output.push_str(&line);
+        }
+        output
+    }
+}
+
+/*
+Compare the best depth levels per side (None for the full book) of local against reference.
+Books with different price decimals are compared at the finer of the two, where both sets of keys are
+exact; quantity differences up to tolerance (unscaled) are accepted
+*/
+pub fn verify<M, N>(local: &Orderbook<M>, reference: &Orderbook<N>, depth: Option<usize>, tolerance: f64) -> VerifyReport {
+    let depth = depth.unwrap_or(usize::MAX);
+    let price_factor = local.price_factor.max(reference.price_factor);
+    let mut report = VerifyReport {
+        differences: Vec::new(),
+        levels_compared: 0,
+        price_factor
+    };
+    // Factors are powers of ten, so the finer one is an exact multiple of the other
+    let local_multiplier = (price_factor / local.price_factor).round() as i64;
+    let reference_multiplier = (price_factor / reference.price_factor).round() as i64;
+    let rekey = |multiplier: i64| move |(price, quantity): (&i64, &u64)| (price.saturating_mul(multiplier), *quantity);
+    let local_bids: BTreeMap<i64, u64> = local.bids.iter().rev().take(depth).map(rekey(local_multiplier)).collect();
+    let reference_bids: BTreeMap<i64, u64> = reference.bids.iter().rev().take(depth).map(rekey(reference_multiplier)).collect();
+    compare_side(&mut report, Side::Bid, &local_bids, local.quantity_factor, &reference_bids, reference.quantity_factor, tolerance);
+    let local_asks: BTreeMap<i64, u64> = local.asks.iter().take(depth).map(rekey(local_multiplier)).collect();
+    let reference_asks: BTreeMap<i64, u64> = reference.asks.iter().take(depth).map(rekey(reference_multiplier)).collect();
+    compare_side(&mut report, Side::Ask, &local_asks, local.quantity_factor, &reference_asks, reference.quantity_factor, tolerance);
+    report
+}
+
+fn compare_side(
+    report: &mut VerifyReport,
+    side: Side,
+    local: &BTreeMap<i64, u64>,
+    local_factor: f64,
+    reference: &BTreeMap<i64, u64>,
+    reference_factor: f64,
+    tolerance: f64
+) {
+    let prices: BTreeSet<i64> = local.keys().chain(reference.keys()).copied().collect();
+    for price in prices {
+        report.levels_compared += 1;
+        let local_quantity = local.get(&price).map(|quantity| (*quantity as f64) / local_factor);
+        let reference_quantity = reference.get(&price).map(|quantity| (*quantity as f64) / reference_factor);
+        let difference = match (local_quantity, reference_quantity) {
+            (Some(local_quantity), Some(reference_quantity)) => {
+                if (local_quantity - reference_quantity).abs() <= tolerance {
+                    continue;
+                }
+                LevelDifference::QuantityMismatch { side, price, local_quantity, reference_quantity }
+            },
+            (None, Some(reference_quantity)) => LevelDifference::Missing { side, price, reference_quantity },
+            (Some(local_quantity), None) => LevelDifference::Unexpected { side, price, local_quantity },
+            (None, None) => continue
+        };
+        report.differences.push(difference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_books_at_different_decimals() {
+        let local = Orderbook::from_snapshot(Some(2), Some(2), vec![(99.5, 1.0), (99.0, 2.0)], vec![(100.5, 1.0)]);
+        let reference = Orderbook::from_snapshot(Some(4), Some(6), vec![(99.5, 1.0), (99.0, 2.5)], vec![(100.5, 1.0), (100.25, 3.0)]);
+        let report = verify(&local, &reference, None, 0.0);
+        assert_eq!(report.price_factor, 10_000.0);
+        assert_eq!(report.levels_compared, 4);
+        assert_eq!(report.differences, vec![
+            LevelDifference::QuantityMismatch { side: Side::Bid, price: 990_000, local_quantity: 2.0, reference_quantity: 2.5 },
+            LevelDifference::Missing { side: Side::Ask, price: 1_002_500, reference_quantity: 3.0 }
+        ]);
+        assert!(verify(&local, &local, None, 0.0).is_consistent());
+        let reversed = verify(&reference, &local, Some(1), 0.0);
+        assert_eq!(reversed.differences, vec![
+            LevelDifference::Unexpected { side: Side::Ask, price: 1_002_500, local_quantity: 3.0 },
+            LevelDifference::Missing { side: Side::Ask, price: 1_005_000, reference_quantity: 1.0 }
+        ]);
+    }
+}
@@ -0,0 +1,136 @@
+/*
+Purpose: Rolling mid-price volatility and depth analytics normalized by it
+*/
+
+use std::collections::VecDeque;
+
+use crate::Orderbook;
+
+/*
+Depth and imbalance within multiples of sigma of mid. sigma_bps is one interval sigma in basis points
+of mid; depths are unscaled quantity
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NormalizedDepth {
+    pub multiples: f64,
+    pub sigma_bps: f64,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+    pub imbalance: Option<f64>
+}
+
+/*
+Standard deviation of mid log returns between consecutive interval_ms buckets (e.g. 60_000 for
+1-minute sigma) over the last window returns. The mid is sampled at the first observation in each
+bucket; a gap of several buckets yields a single return. Books with a non-positive mid are not sampled
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollingVolatility {
+    pub interval_ms: u64,
+    pub window: usize,
+    pub returns: VecDeque<f64>,
+    last_sample: Option<(u64, f64)>
+}
+
+impl RollingVolatility {
+    pub fn new(interval_ms: u64, window: usize) -> RollingVolatility {
+        RollingVolatility {
+            interval_ms: interval_ms.max(1),
+            window: window.max(2),
+            returns: VecDeque::with_capacity(window.max(2)),
+            last_sample: None
+        }
+    }
+
+    pub fn observe<M>(&mut self, book: &Orderbook<M>, timestamp: u64) {
+        let mid_price = match book.summary(Some(1)).mid_price {
+            Some(mid_price) if mid_price > 0.0 => mid_price,
+            _ => return
+        };
+        let bucket = timestamp - timestamp % self.interval_ms.max(1);
+        match self.last_sample {
+            Some((last_bucket, _)) if bucket <= last_bucket => (),
+            Some((_, last_mid)) => {
+                // window is pub and may have been lowered since the last return
+                while self.returns.len() >= self.window.max(2) {
+                    self.returns.pop_front();
+                }
+                self.returns.push_back((mid_price / last_mid).ln());
+                self.last_sample = Some((bucket, mid_price));
+            },
+            None => self.last_sample = Some((bucket, mid_price))
+        }
+    }
+
+    /*
+    Per-interval sigma of log returns, None until two returns are available
+    */
+    pub fn sigma(&self) -> Option<f64> {
+        if self.returns.len() < 2 {
+            return None;
+        }
+        let count = self.returns.len() as f64;
+        let mean = self.returns.iter().sum::<f64>() / count;
+        let variance = self.returns.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        Some(variance.sqrt())
+    }
+
+    pub fn sigma_bps(&self) -> Option<f64> {
+        self.sigma().map(|sigma| sigma * 10_000.0)
+    }
+
+    /*
+    Express a basis-point distance in units of sigma, e.g. a spread_bps for cross-regime comparison
+    */
+    pub fn to_sigmas(&self, bps: f64) -> Option<f64> {
+        match self.sigma_bps()? {
+            sigma_bps if sigma_bps > 0.0 => Some(bps / sigma_bps),
+            _ => None
+        }
+    }
+
+    /*
+    Depth within multiples sigma of mid. None until sigma is known and non-zero, or without a two-sided book
+    */
+    pub fn normalized_depth<M>(&self, book: &Orderbook<M>, multiples: f64) -> Option<NormalizedDepth> {
+        let sigma_bps = self.sigma_bps().filter(|sigma_bps| *sigma_bps > 0.0)?;
+        let grid = book.depth_grid(sigma_bps * multiples, 1)?;
+        let (bid_depth, ask_depth) = (grid.bids[0], grid.asks[0]);
+        Some(NormalizedDepth {
+            multiples,
+            sigma_bps,
+            bid_depth,
+            ask_depth,
+            imbalance: match bid_depth + ask_depth > 0.0 {
+                true => Some((bid_depth - ask_depth) / (bid_depth + ask_depth)),
+                false => None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowered_window_trims_returns() {
+        let mut book = Orderbook::new(Some(2), Some(2));
+        let mut volatility = RollingVolatility::new(1000, 8);
+        for bucket in 0..9u64 {
+            let mid = 100.0 + bucket as f64;
+            book.process(vec![(mid - 0.5, 1.0)], vec![(mid + 0.5, 1.0)], true);
+            volatility.observe(&book, bucket * 1000);
+        }
+        assert_eq!(volatility.returns.len(), 8);
+        volatility.window = 3;
+        book.process(vec![(108.5, 1.0)], vec![(109.5, 1.0)], true);
+        volatility.observe(&book, 9000);
+        assert_eq!(volatility.returns.len(), 3);
+        volatility.window = 0;
+        book.process(vec![(109.5, 1.0)], vec![(110.5, 1.0)], true);
+        volatility.observe(&book, 10_000);
+        assert_eq!(volatility.returns.len(), 2);
+        assert!(volatility.sigma().is_some());
+    }
+}
@@ -0,0 +1,154 @@
+/*
+Purpose: Volume-synchronized probability of informed trading over the trade tape
+*/
+
+use std::collections::VecDeque;
+
+use crate::Side;
+
+// Relative shortfall at which a bucket counts as full, so sums of fractional trades close it
+const BUCKET_TOLERANCE: f64 = 1e-9;
+
+/*
+Trades fill equal-volume buckets of bucket_volume (unscaled); a trade spanning a bucket boundary is split.
+VPIN is the mean absolute buy/sell imbalance over the last window buckets as a fraction of bucket volume
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vpin {
+    pub bucket_volume: f64,
+    pub window: usize,
+    pub imbalances: VecDeque<f64>,
+    buy_volume: f64,
+    sell_volume: f64
+}
+
+impl Vpin {
+    /*
+    A non-finite or non-positive bucket_volume leaves the estimator inert: trades are ignored
+    */
+    pub fn new(bucket_volume: f64, window: usize) -> Vpin {
+        Vpin {
+            bucket_volume: match bucket_volume.is_finite() && bucket_volume > 0.0 {
+                true => bucket_volume,
+                false => 0.0
+            },
+            window: window.max(1),
+            imbalances: VecDeque::new(),
+            buy_volume: 0.0,
+            sell_volume: 0.0
+        }
+    }
+
+    /*
+    Add a trade by aggressor side (Side::Bid for buyer-initiated). Returns the number of buckets it completed,
+    saturating at usize::MAX. Non-finite or non-positive quantities are ignored. Buckets filled entirely by
+    this trade are counted in one step, each with an imbalance of the full bucket, so a quantity far larger
+    than bucket_volume costs no more than window buckets of work
+    */
+    pub fn record_trade(&mut self, side: Side, quantity: f64) -> usize {
+        if !(quantity.is_finite() && quantity > 0.0 && self.bucket_volume.is_finite() && self.bucket_volume > 0.0) {
+            return 0;
+        }
+        let taken = quantity.min((self.bucket_volume - self.pending_volume()).max(0.0));
+        self.add_volume(side, taken);
+        if !self.bucket_full() {
+            return 0;
+        }
+        self.close_bucket();
+        let mut remaining = quantity - taken;
+        let mut whole = (remaining / self.bucket_volume).floor();
+        remaining -= whole * self.bucket_volume;
+        if remaining >= self.bucket_volume * (1.0 - BUCKET_TOLERANCE) {
+            whole += 1.0;
+            remaining = 0.0;
+        }
+        for _ in 0..(whole as usize).min(self.window) {
+            if self.imbalances.len() == self.window {
+                self.imbalances.pop_front();
+            }
+            self.imbalances.push_back(self.bucket_volume);
+        }
+        self.add_volume(side, remaining.max(0.0));
+        1usize.saturating_add(whole as usize)
+    }
+
+    /*
+    None until window buckets have completed
+    */
+    pub fn value(&self) -> Option<f64> {
+        match self.imbalances.len() == self.window {
+            true => Some(self.imbalances.iter().sum::<f64>() / (self.window as f64 * self.bucket_volume)),
+            false => None
+        }
+    }
+
+    /*
+    Volume in the bucket currently filling
+    */
+    pub fn pending_volume(&self) -> f64 {
+        self.buy_volume + self.sell_volume
+    }
+
+    fn add_volume(&mut self, side: Side, volume: f64) {
+        match side {
+            Side::Bid => self.buy_volume += volume,
+            Side::Ask => self.sell_volume += volume
+        }
+    }
+
+    fn bucket_full(&self) -> bool {
+        self.pending_volume() >= self.bucket_volume * (1.0 - BUCKET_TOLERANCE)
+    }
+
+    fn close_bucket(&mut self) {
+        if self.imbalances.len() == self.window {
+            self.imbalances.pop_front();
+        }
+        self.imbalances.push_back((self.buy_volume - self.sell_volume).abs());
+        self.buy_volume = 0.0;
+        self.sell_volume = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_non_finite_input() {
+        let mut vpin = Vpin::new(10.0, 2);
+        for quantity in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN, 0.0, -1.0] {
+            assert_eq!(vpin.record_trade(Side::Bid, quantity), 0);
+        }
+        assert_eq!(vpin.pending_volume(), 0.0);
+        for bucket_volume in [f64::INFINITY, f64::NAN, 0.0, -10.0] {
+            let mut vpin = Vpin::new(bucket_volume, 2);
+            assert_eq!(vpin.record_trade(Side::Bid, 5.0), 0);
+            assert_eq!(vpin.value(), None);
+        }
+        vpin.bucket_volume = f64::INFINITY;
+        assert_eq!(vpin.record_trade(Side::Ask, 5.0), 0);
+    }
+
+    #[test]
+    fn fills_buckets_across_trades() {
+        let mut vpin = Vpin::new(10.0, 2);
+        assert_eq!(vpin.record_trade(Side::Bid, 6.0), 0);
+        assert_eq!(vpin.record_trade(Side::Ask, 6.0), 1);
+        assert_eq!(vpin.pending_volume(), 2.0);
+        assert_eq!(vpin.record_trade(Side::Ask, 8.0), 1);
+        assert_eq!(vpin.imbalances, VecDeque::from(vec![2.0, 10.0]));
+        assert_eq!(vpin.value(), Some(0.6));
+    }
+
+    #[test]
+    fn large_trade_completes_in_bounded_work() {
+        let mut vpin = Vpin::new(1.0, 3);
+        assert_eq!(vpin.record_trade(Side::Bid, 1e12), 1_000_000_000_000);
+        assert_eq!(vpin.record_trade(Side::Bid, 1e20), usize::MAX);
+        assert_eq!(vpin.imbalances.len(), 3);
+        assert_eq!(vpin.value(), Some(1.0));
+        assert_eq!(vpin.record_trade(Side::Ask, 2.5), 2);
+        assert_eq!(vpin.pending_volume(), 0.5);
+    }
+}
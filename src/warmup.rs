@@ -0,0 +1,164 @@
+/*
+Purpose: Warm-up confidence scoring of a book after a snapshot or resync
+*/
+
+use std::collections::VecDeque;
+
+use crate::{BookEvent, Orderbook, Projection, Side};
+
+/*
+How settled the book is elapsed_ms after the last snapshot (since, book clock). depth_restored is the
+weaker side's level count over its expected count, capped at 1. rate_stability compares update counts
+in the last two complete buckets (1 when equal, 0 until two buckets have completed). confidence is the
+weakest of those and the elapsed fraction of settle_ms, so all three must recover for a trusted book
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmUpScore {
+    pub since: u64,
+    pub elapsed_ms: u64,
+    pub depth_restored: f64,
+    pub rate_stability: f64,
+    pub confidence: f64
+}
+
+impl WarmUpScore {
+    pub fn is_settled(&self, threshold: f64) -> bool {
+        self.confidence >= threshold
+    }
+}
+
+/*
+Projection restarting its warm-up clock on every Cleared (a snapshot, resync or registration). The
+expected level count per side is the larger of expected_levels and the levels the side held before the
+clear, so a partial snapshot is scored against the book it replaced. Levels re-listed by the snapshot
+itself do not count as updates
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmUp {
+    pub settle_ms: u64,
+    pub bucket_ms: u64,
+    pub expected_levels: usize,
+    since: Option<u64>,
+    bid_levels: usize,
+    ask_levels: usize,
+    expected_bid: usize,
+    expected_ask: usize,
+    buckets: VecDeque<(u64, u64)>
+}
+
+impl WarmUp {
+    pub fn new(settle_ms: u64, bucket_ms: u64, expected_levels: usize) -> WarmUp {
+        WarmUp {
+            settle_ms,
+            bucket_ms: bucket_ms.max(1),
+            expected_levels,
+            since: None,
+            bid_levels: 0,
+            ask_levels: 0,
+            expected_bid: expected_levels,
+            expected_ask: expected_levels,
+            buckets: VecDeque::new()
+        }
+    }
+
+    /*
+    Score at now (book clock, ms). None before the first snapshot
+    */
+    pub fn score(&self, now: u64) -> Option<WarmUpScore> {
+        let since = self.since?;
+        let elapsed_ms = now.saturating_sub(since);
+        let side = |levels: usize, expected: usize| match expected {
+            0 => match levels {
+                0 => 0.0,
+                _ => 1.0
+            },
+            expected => (levels as f64 / expected as f64).min(1.0)
+        };
+        let depth_restored = side(self.bid_levels, self.expected_bid).min(side(self.ask_levels, self.expected_ask));
+        let completed = elapsed_ms / self.bucket_ms.max(1);
+        let rate_stability = match completed >= 2 {
+            true => {
+                let count = |index: u64| self.buckets.iter().find(|(bucket, _)| *bucket == index).map_or(0, |(_, count)| *count);
+                let (last, previous) = (count(completed - 1), count(completed - 2));
+                match last.max(previous) {
+                    0 => 1.0,
+                    most => 1.0 - last.abs_diff(previous) as f64 / most as f64
+                }
+            },
+            false => 0.0
+        };
+        let elapsed = match self.settle_ms {
+            0 => 1.0,
+            settle_ms => (elapsed_ms as f64 / settle_ms as f64).min(1.0)
+        };
+        Some(WarmUpScore {
+            since,
+            elapsed_ms,
+            depth_restored,
+            rate_stability,
+            confidence: depth_restored.min(rate_stability).min(elapsed)
+        })
+    }
+
+    fn count_update(&mut self, timestamp: u64) {
+        let since = match self.since {
+            Some(since) if timestamp != since => since,
+            _ => return
+        };
+        let index = timestamp.saturating_sub(since) / self.bucket_ms.max(1);
+        match self.buckets.back_mut() {
+            Some((bucket, count)) if *bucket == index => *count += 1,
+            _ => {
+                self.buckets.push_back((index, 1));
+                while self.buckets.len() > 3 {
+                    self.buckets.pop_front();
+                }
+            }
+        }
+    }
+}
+
+impl Projection for WarmUp {
+    fn apply(&mut self, event: &BookEvent, timestamp: u64) {
+        match *event {
+            BookEvent::LevelSet { side, previous: None, .. } => {
+                match side {
+                    Side::Bid => self.bid_levels += 1,
+                    Side::Ask => self.ask_levels += 1
+                }
+                self.count_update(timestamp);
+            },
+            BookEvent::LevelSet { .. } => self.count_update(timestamp),
+            BookEvent::LevelRemoved { side, .. } => {
+                match side {
+                    Side::Bid => self.bid_levels = self.bid_levels.saturating_sub(1),
+                    Side::Ask => self.ask_levels = self.ask_levels.saturating_sub(1)
+                }
+                self.count_update(timestamp);
+            },
+            BookEvent::Cleared => {
+                self.expected_bid = self.expected_levels.max(self.bid_levels);
+                self.expected_ask = self.expected_levels.max(self.ask_levels);
+                self.bid_levels = 0;
+                self.ask_levels = 0;
+                self.since = Some(timestamp);
+                self.buckets.clear();
+            },
+            BookEvent::Reset | BookEvent::Rescaled { .. } => ()
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Warm-up score from the registered WarmUp at the book clock, with zero confidence unless the book is
+    Live. None without a registered WarmUp or before its first snapshot
+    */
+    pub fn warm_up(&self) -> Option<WarmUpScore> {
+        let mut score = self.projection::<WarmUp>()?.score(self.timestamp)?;
+        if !self.is_live() {
+            score.confidence = 0.0;
+        }
+        Some(score)
+    }
+}
@@ -0,0 +1,216 @@
+/*
+Purpose: Partial books materializing only the levels within a price window around mid
+*/
+
+use std::collections::BTreeMap;
+
+use crate::{Orderbook, Side};
+
+/*
+Levels within fraction of mid (0.01 = 1%) are kept in the book's trees. Levels between that and
+reserve_fraction are parked here, out of the trees and unseen by projections, and promoted as mid moves
+toward them; levels beyond reserve_fraction are dropped. Mid is taken over the trees and the parked levels
+together, so a side emptied inside the window refills from its parked edge. bounds and outer are the
+scaled (lowest bid, highest ask) of each band as of the last update, None while the book is one-sided,
+when every level is kept in the trees
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceWindow {
+    pub fraction: f64,
+    pub reserve_fraction: f64,
+    pub(crate) bounds: Option<(i64, i64)>,
+    pub(crate) outer: Option<(i64, i64)>,
+    pub(crate) bids: BTreeMap<i64, u64>,
+    pub(crate) asks: BTreeMap<i64, u64>
+}
+
+impl PriceWindow {
+    pub fn bounds(&self) -> Option<(i64, i64)> {
+        self.bounds
+    }
+
+    /*
+    Parked scaled levels outside the window, keyed by price
+    */
+    pub fn parked_bids(&self) -> &BTreeMap<i64, u64> {
+        &self.bids
+    }
+
+    pub fn parked_asks(&self) -> &BTreeMap<i64, u64> {
+        &self.asks
+    }
+
+    pub fn parked_levels(&self) -> usize {
+        self.bids.len() + self.asks.len()
+    }
+
+    fn parked_mut(&mut self, side: Side) -> &mut BTreeMap<i64, u64> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks
+        }
+    }
+}
+
+impl<M> Orderbook<M> {
+    /*
+    Materialize only levels within fraction of mid, parking those out to reserve_fraction (raised to at
+    least fraction). Levels already outside the window move out of the trees at once
+    */
+    pub fn set_price_window(&mut self, fraction: f64, reserve_fraction: f64) {
+        let (bids, asks) = match self.price_window.take() {
+            Some(window) => (window.bids, window.asks),
+            None => (BTreeMap::new(), BTreeMap::new())
+        };
+        self.price_window = Some(PriceWindow {
+            fraction: fraction.abs(),
+            reserve_fraction: reserve_fraction.abs().max(fraction.abs()),
+            bounds: None,
+            outer: None,
+            bids,
+            asks
+        });
+        self.shift_window();
+    }
+
+    /*
+    Return to a full book, restoring the parked levels to the trees
+    */
+    pub fn clear_price_window(&mut self) {
+        if let Some(window) = self.price_window.take() {
+            for (price, quantity) in window.bids {
+                self.restore_level(Side::Bid, price, quantity);
+            }
+            for (price, quantity) in window.asks {
+                self.restore_level(Side::Ask, price, quantity);
+            }
+        }
+    }
+
+    /*
+    Route a change at a price outside the current window to the parked levels, dropping it beyond the
+    reserve band. Returns false when the change belongs in the trees
+    */
+    pub(crate) fn window_absorbs(&mut self, side: Side, price: i64, quantity: u64) -> bool {
+        let window = match self.price_window.as_mut() {
+            Some(window) => window,
+            None => return false
+        };
+        let ((lowest, highest), (outer_lowest, outer_highest)) = match (window.bounds, window.outer) {
+            (Some(bounds), Some(outer)) => (bounds, outer),
+            _ => return false
+        };
+        let (outside, kept) = match side {
+            Side::Bid => (price < lowest, price >= outer_lowest),
+            Side::Ask => (price > highest, price <= outer_highest)
+        };
+        if !outside {
+            return false;
+        }
+        let parked = window.parked_mut(side);
+        match quantity > 0 && kept {
+            true => parked.insert(price, quantity),
+            false => parked.remove(&price)
+        };
+        true
+    }
+
+    /*
+    Forget parked levels and bounds, e.g. when a snapshot replaces the book
+    */
+    pub(crate) fn reset_window(&mut self) {
+        if let Some(window) = self.price_window.as_mut() {
+            window.bids.clear();
+            window.asks.clear();
+            window.bounds = None;
+            window.outer = None;
+        }
+    }
+
+    /*
+    Recenter the window on the current mid: park tree levels now outside it, promote parked levels now
+    inside it and drop parked levels beyond the reserve band
+    */
+    pub(crate) fn shift_window(&mut self) {
+        let (fraction, reserve_fraction, parked_bid, parked_ask) = match self.price_window.as_ref() {
+            Some(window) => (window.fraction, window.reserve_fraction, window.bids.keys().next_back().copied(), window.asks.keys().next().copied()),
+            None => return
+        };
+        let best_bid = self.bids.keys().next_back().copied().max(parked_bid);
+        let best_ask = match (self.asks.keys().next().copied(), parked_ask) {
+            (Some(ask), Some(parked)) => Some(ask.min(parked)),
+            (ask, parked) => ask.or(parked)
+        };
+        let (best_bid, best_ask) = match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) => (bid, ask),
+            _ => {
+                let (bids, asks) = match self.price_window.as_mut() {
+                    Some(window) => (std::mem::take(&mut window.bids), std::mem::take(&mut window.asks)),
+                    None => return
+                };
+                for (price, quantity) in bids {
+                    self.restore_level(Side::Bid, price, quantity);
+                }
+                for (price, quantity) in asks {
+                    self.restore_level(Side::Ask, price, quantity);
+                }
+                if let Some(window) = self.price_window.as_mut() {
+                    window.bounds = None;
+                    window.outer = None;
+                }
+                return;
+            }
+        };
+        let mid_price = (best_bid as f64 + best_ask as f64) / 2.0;
+        let band = |fraction: f64| (
+            (mid_price - mid_price.abs() * fraction).ceil() as i64,
+            (mid_price + mid_price.abs() * fraction).floor() as i64
+        );
+        let ((lowest, highest), (outer_lowest, outer_highest)) = (band(fraction), band(reserve_fraction));
+        let demoted_bids: Vec<i64> = self.bids.range(..lowest).map(|(price, _)| *price).collect();
+        let demoted_asks: Vec<i64> = self.asks.range(highest.saturating_add(1)..).map(|(price, _)| *price).collect();
+        let mut parked = Vec::new();
+        for price in demoted_bids {
+            if let Some(quantity) = self.remove_level(Side::Bid, price) {
+                parked.push((Side::Bid, price, quantity));
+            }
+        }
+        for price in demoted_asks {
+            if let Some(quantity) = self.remove_level(Side::Ask, price) {
+                parked.push((Side::Ask, price, quantity));
+            }
+        }
+        let promoted = match self.price_window.as_mut() {
+            Some(window) => {
+                for (side, price, quantity) in parked {
+                    window.parked_mut(side).insert(price, quantity);
+                }
+                window.bids = window.bids.split_off(&outer_lowest);
+                window.asks.split_off(&outer_highest.saturating_add(1));
+                window.bounds = Some((lowest, highest));
+                window.outer = Some((outer_lowest, outer_highest));
+                let promoted_bids = window.bids.split_off(&lowest);
+                let promoted_asks = window.asks.split_off(&highest.saturating_add(1));
+                let promoted_asks = std::mem::replace(&mut window.asks, promoted_asks);
+                (promoted_bids, promoted_asks)
+            },
+            None => return
+        };
+        for (price, quantity) in promoted.0 {
+            self.restore_level(Side::Bid, price, quantity);
+        }
+        for (price, quantity) in promoted.1 {
+            self.restore_level(Side::Ask, price, quantity);
+        }
+    }
+
+    fn restore_level(&mut self, side: Side, price: i64, quantity: u64) {
+        self.set_level(side, price, quantity);
+        if self.level_ttl.is_some() {
+            match side {
+                Side::Bid => self.bid_update_times.insert(price, self.timestamp),
+                Side::Ask => self.ask_update_times.insert(price, self.timestamp)
+            };
+        }
+    }
+}